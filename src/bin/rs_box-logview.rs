@@ -0,0 +1,294 @@
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Duration;
+use rs_box::{
+    list_log_modules, parse_log_view_time, read_log_entries, strip_log_colors, verify_log_files, LogFileLayout, LogIntegrity,
+    LogIntegrityIssue, LogLevel, LogViewEntry, LogViewFilter, LogViewFollower,
+};
+
+const USAGE: &str = "\
+Usage: rs_box-logview [OPTIONS] [MODULE...]
+
+Shows the logs of the given modules (all modules below the log directory by default)
+merged in time order.
+
+Options:
+  -d, --dir <DIR>           log directory (default ./logs)
+  -l, --level <LEVEL>       only LEVEL and more severe records (error, warning, info, debug, trace)
+      --since <TIME>        only records at or after TIME (YYYY-MM-DD[ HH:MM:SS] local, or RFC 3339)
+      --until <TIME>        only records before TIME
+  -m, --module <NAME>       only this module or logger and those below it; may repeat
+  -g, --grep <REGEX>        only records matching REGEX
+  -f, --follow              after the history, follow each module's run.log
+      --color <WHEN>        auto, always or never (default auto)
+      --json                print records as JSON lines
+      --pattern <PATTERN>   log file pattern, if not the default {project}/{date}/{date}_{hour}.log
+      --symlink <PATTERN>   current file link, if not the default {project}/run.log
+      --list                list the modules found and exit
+      --verify <KEY_FILE>   check the integrity chain of every log file, and the links between
+                            them, with the key in KEY_FILE
+                            and exit; only the newest file of a module may be unsealed
+  -h, --help                show this help
+";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
+struct Options {
+    dir: String,
+    filter: LogViewFilter,
+    modules: Vec<String>,
+    follow: bool,
+    color: ColorMode,
+    json: bool,
+    layout: LogFileLayout,
+    list: bool,
+    verify: Option<String>,
+}
+
+fn parse_args(args: Vec<String>) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        dir: "./logs".to_string(),
+        filter: LogViewFilter::new(),
+        modules: Vec::new(),
+        follow: false,
+        color: ColorMode::Auto,
+        json: false,
+        layout: LogFileLayout::default(),
+        list: false,
+        verify: None,
+    };
+    let mut pattern = None;
+    let mut symlink = None;
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline_value) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+            _ => (arg.clone(), None),
+        };
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("{} needs a value", flag))
+        };
+        match flag.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-d" | "--dir" => options.dir = value()?,
+            "-l" | "--level" => {
+                let name = value()?;
+                let level = LogLevel::from_name(&name).ok_or_else(|| format!("unknown level {:?}", name))?;
+                options.filter = options.filter.with_level(level);
+            }
+            "--since" => options.filter = options.filter.with_since(parse_log_view_time(&value()?)?),
+            "--until" => options.filter = options.filter.with_until(parse_log_view_time(&value()?)?),
+            "-m" | "--module" => {
+                let name = value()?;
+                options.filter = options.filter.with_module(&name);
+                options.modules.push(name);
+            }
+            "-g" | "--grep" => options.filter = options.filter.with_regex(&value()?)?,
+            "-f" | "--follow" => options.follow = true,
+            "--color" => {
+                options.color = match value()?.as_str() {
+                    "auto" => ColorMode::Auto,
+                    "always" => ColorMode::Always,
+                    "never" => ColorMode::Never,
+                    other => return Err(format!("--color takes auto, always or never, not {:?}", other)),
+                }
+            }
+            "--json" => options.json = true,
+            "--pattern" => pattern = Some(value()?),
+            "--symlink" => symlink = Some(value()?),
+            "--list" => options.list = true,
+            "--verify" => options.verify = Some(value()?),
+            other if other.starts_with('-') => return Err(format!("unknown option {}", other)),
+            _ => {
+                options.filter = options.filter.with_module(&arg);
+                options.modules.push(arg);
+            }
+        }
+    }
+
+    if let Some(pattern) = pattern {
+        options.layout = LogFileLayout::new(&pattern)?.with_symlink(symlink.as_deref().or(Some("{project}/run.log")))?;
+    } else if let Some(symlink) = symlink {
+        options.layout = options.layout.with_symlink(Some(&symlink))?;
+    }
+    Ok(Some(options))
+}
+
+/// Modules to read: the requested ones, those below them and, since child loggers may write
+/// into a parent's files, those above them
+fn selected_modules(options: &Options) -> Vec<String> {
+    let found = list_log_modules(&options.dir, &options.layout);
+    if options.modules.is_empty() {
+        return found;
+    }
+    let within = |value: &str, name: &str| value.strip_prefix(name).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+    found
+        .into_iter()
+        .filter(|module| {
+            options.modules.iter().any(|name| {
+                let name = name.trim_end_matches('/');
+                let as_path = name.replace('.', "/");
+                [name, as_path.as_str()]
+                    .iter()
+                    .any(|name| within(module, name) || within(name, module))
+            })
+        })
+        .collect()
+}
+
+struct Printer {
+    json: bool,
+    colors: bool,
+    show_module: bool,
+}
+
+impl Printer {
+    fn print(&self, out: &mut impl Write, entry: &LogViewEntry) -> std::io::Result<()> {
+        if self.json {
+            return writeln!(out, "{}", entry.to_json());
+        }
+        let line = if self.colors { entry.line.clone() } else { strip_log_colors(&entry.line) };
+        if self.show_module {
+            writeln!(out, "{} {}", entry.module, line)
+        } else {
+            writeln!(out, "{}", line)
+        }
+    }
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let modules = selected_modules(&options);
+    if options.list {
+        for module in modules {
+            println!("{}", module);
+        }
+        return Ok(());
+    }
+    if modules.is_empty() {
+        return Err(format!("no logs found in {}", options.dir));
+    }
+    if let Some(ref key_file) = options.verify {
+        return verify(&options, &modules, &LogIntegrity::from_key_file(Path::new(key_file))?);
+    }
+
+    let stdout = std::io::stdout();
+    let printer = Printer {
+        json: options.json,
+        colors: match options.color {
+            ColorMode::Auto => stdout.is_terminal(),
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        },
+        show_module: modules.len() > 1,
+    };
+    let mut out = stdout.lock();
+    // Followers start at the current end, so nothing written while reading the history is lost
+    let mut followers: Vec<LogViewFollower> = if options.follow {
+        modules
+            .iter()
+            .filter_map(|module| {
+                let path = options.layout.symlink_path(&options.dir, module)?;
+                Some(LogViewFollower::new(module, &path))
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    for entry in read_log_entries(&options.dir, &options.layout, &modules, &options.filter) {
+        if printer.print(&mut out, &entry).is_err() {
+            return Ok(());
+        }
+    }
+    if !options.follow {
+        return Ok(());
+    }
+    if followers.is_empty() {
+        return Err("--follow needs a run.log link; the layout has none".to_string());
+    }
+    drop(out);
+
+    loop {
+        let mut entries: Vec<LogViewEntry> = followers.iter_mut().flat_map(|follower| follower.poll()).collect();
+        entries.sort_by_key(|entry| entry.time);
+        let mut out = std::io::stdout().lock();
+        for entry in entries.iter().filter(|entry| options.filter.matches(entry)) {
+            if printer.print(&mut out, entry).is_err() {
+                return Ok(());
+            }
+        }
+        if out.flush().is_err() {
+            return Ok(());
+        }
+        drop(out);
+        std::thread::sleep(Duration::from_millis(250));
+    }
+}
+
+/// Prints one line per log file and fails if any file does not verify or does not
+/// continue the file before it
+fn verify(options: &Options, modules: &[String], integrity: &LogIntegrity) -> Result<(), String> {
+    let mut checked = 0;
+    let mut failed = 0;
+    for module in modules {
+        let files = options.layout.list_files(&options.dir, module);
+        let reports = verify_log_files(&files, integrity).map_err(|e| format!("failed to read logs of {}: {}", module, e))?;
+        for (index, report) in reports.iter().enumerate() {
+            checked += 1;
+            let newest = index + 1 == reports.len();
+            match report.issue {
+                None => println!(
+                    "OK        {} ({} records, {} runs, sealed)",
+                    report.path.display(),
+                    report.records,
+                    report.runs
+                ),
+                Some(LogIntegrityIssue::LogIntegrityIssueUnsealed) if newest => println!(
+                    "UNSEALED  {} ({} records, {} runs, still open?)",
+                    report.path.display(),
+                    report.records,
+                    report.runs
+                ),
+                Some(ref issue) => {
+                    failed += 1;
+                    println!("FAILED    {}: {}", report.path.display(), issue);
+                }
+            }
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{} of {} log files failed verification", failed, checked)),
+    }
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1).collect()) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("rs_box-logview: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match run(options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("rs_box-logview: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -1,4 +1,5 @@
 pub mod coin_flags;
 pub mod hash_rate;
+#[cfg(test)]
 mod hash_rate_test;
 mod coin_flags_test;
\ No newline at end of file
@@ -6,17 +6,33 @@
 #![allow(clippy::type_complexity)]
 #![allow(clippy::unnecessary_cast)]
 #![allow(clippy::arc_with_non_send_sync)]
+#![allow(clippy::module_inception)]
 
 mod lib_tests;
 pub mod block_chain;
 
 pub mod rs_box_log;
-pub use rs_box_log::*;
-use std::fs;
-use toml::Value;
+pub use rs_box_log::rs_box_log::*;
+
+mod rs_box_app;
+mod rs_box_app_test;
+pub use rs_box_app::{get_http_request_timeout, RsBox, RsBoxBuilder, RsBoxError};
+
+mod rs_box_config;
+mod rs_box_config_test;
+pub use rs_box_config::{RsBoxConfig, RsBoxConfigError, RsBoxConfigLoader};
+
+use std::fmt;
+use std::str::FromStr;
+use once_cell::sync::OnceCell;
 
 const LIB_VERSION: &str = "0.0.28";
 
+/// Environment variable read by `RunMode::detect`
+pub const RUN_MODE_ENV: &str = "RS_BOX_RUN_MODE";
+/// Command line argument read by `RunMode::detect`, as `--run-mode test` or `--run-mode=test`
+pub const RUN_MODE_ARG: &str = "--run-mode";
+
 #[derive(Clone, Copy, Debug,PartialEq)]
 pub enum RunMode {
     RunModeUnknown,
@@ -25,7 +41,7 @@ pub enum RunMode {
     RunModeTest,
 }
 impl RunMode {
-    pub fn to_string(&self) -> &'static str {
+    pub fn to_str(&self) -> &'static str {
         match *self {
             RunMode::RunModeDebug => "Debug",
             RunMode::RunModeRelease => "Release",
@@ -33,44 +49,110 @@ impl RunMode {
             RunMode::RunModeUnknown => "Unknown",
         }
     }
-}
 
-static mut CURRENT_RUN_MODE: RunMode = RunMode::RunModeUnknown;
+    /// Renamed to `to_str`; this name shadows `ToString::to_string` from `Display`
+    #[deprecated(note = "renamed to `to_str`; use `to_str()` or the `Display` impl")]
+    pub fn to_string(&self) -> &'static str {
+        self.to_str()
+    }
 
-pub fn get_current_run_mode() -> RunMode {
-    unsafe { CURRENT_RUN_MODE }
+    /// Resolves the run mode from `RS_BOX_RUN_MODE`, then the `--run-mode` argument, then the
+    /// build profile (`RunModeDebug` with debug assertions, `RunModeRelease` without). Values
+    /// that do not parse are reported on stderr and skipped.
+    pub fn detect() -> RunMode {
+        detect_run_mode(std::env::var(RUN_MODE_ENV).ok(), std::env::args().skip(1), cfg!(debug_assertions))
+    }
 }
 
-pub fn rs_box_setup(project_name: &str, run_mode: RunMode, product_log_dir: &str, log_max_save_days: u64, http_request_timeout: u64) {
-
-    let enable_save_log_file = match run_mode {
-        RunMode::RunModeRelease | RunMode::RunModeTest => true,
-        _ => false,
-    };
-
-    let log_level = match run_mode {
-        RunMode::RunModeDebug | RunMode::RunModeTest => crate::rs_box_log::rs_box_log::LogLevel::LogLevelDebug,
-        RunMode::RunModeRelease => crate::rs_box_log::rs_box_log::LogLevel::LogLevelInfo,
-        _ => crate::rs_box_log::rs_box_log::LogLevel::LogLevelDebug,
-    };
+impl fmt::Display for RunMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.to_str())
+    }
+}
 
-    crate::rs_box_log::rs_box_log::setup_log_tools("test_project",enable_save_log_file,product_log_dir,rs_box_log::rs_box_log::LogLevel::LogLevelDebug,7);
+impl FromStr for RunMode {
+    type Err = String;
 
-    unsafe {
-        CURRENT_RUN_MODE = run_mode;
+    /// Parses `debug`, `release` or `test`, case-insensitively
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "debug" => Ok(RunMode::RunModeDebug),
+            "release" => Ok(RunMode::RunModeRelease),
+            "test" => Ok(RunMode::RunModeTest),
+            _ => Err(format!("unknown run mode {:?}, expected debug, release or test", name)),
+        }
     }
+}
 
-    println!("Rust Developer Tool Box Setup End");
-    println!("project    name  =[{}]", project_name);
-    println!("rs_box  version  =[v{}]", LIB_VERSION);
-    println!("Run        Mode  =[{}]", run_mode.to_string());
-    println!("Log       Level  =[{}]", log_level.to_str());
-    println!("default log dir  =[{}]", product_log_dir);
-    println!("log save days max=[{}]", log_max_save_days);
-    println!("http req timeout =[{} Second]", http_request_timeout);
+pub(crate) fn detect_run_mode(env: Option<String>, mut args: impl Iterator<Item = String>, debug_assertions: bool) -> RunMode {
+    if let Some(value) = env {
+        match value.parse() {
+            Ok(run_mode) => return run_mode,
+            Err(e) => eprintln!("rs_box: ignoring {}: {}", RUN_MODE_ENV, e),
+        }
+    }
+    while let Some(arg) = args.next() {
+        let value = match arg.strip_prefix(RUN_MODE_ARG) {
+            Some("") => args.next(),
+            Some(value) => match value.strip_prefix('=') {
+                Some(value) => Some(value.to_string()),
+                None => continue,
+            },
+            None => continue,
+        };
+        match value.as_deref().map(str::parse::<RunMode>) {
+            Some(Ok(run_mode)) => return run_mode,
+            Some(Err(e)) => eprintln!("rs_box: ignoring {}: {}", RUN_MODE_ARG, e),
+            None => eprintln!("rs_box: ignoring {}: no value given", RUN_MODE_ARG),
+        }
+    }
+    if debug_assertions { RunMode::RunModeDebug } else { RunMode::RunModeRelease }
 }
 
+static CURRENT_RUN_MODE: OnceCell<RunMode> = OnceCell::new();
 
+/// The run mode set up by `RsBoxBuilder::build`, `RunModeUnknown` before that
+pub fn get_current_run_mode() -> RunMode {
+    CURRENT_RUN_MODE.get().copied().unwrap_or(RunMode::RunModeUnknown)
+}
 
+/// Sets the run mode once per process; setting the same mode again is a no-op.
+/// Returns the mode already in place when it differs.
+pub(crate) fn set_current_run_mode(run_mode: RunMode) -> Result<(), RunMode> {
+    let current = *CURRENT_RUN_MODE.get_or_init(|| run_mode);
+    if current == run_mode { Ok(()) } else { Err(current) }
+}
 
+/// The app set up by `rs_box_setup`, kept for the rest of the process like the loggers
+/// of the versions before `LogGuard`
+static LEGACY_APP: std::sync::Mutex<Option<RsBox>> = std::sync::Mutex::new(None);
 
+/// Sets up logging and the run mode, printing errors instead of returning them. Zero days
+/// or a zero timeout fall back to the defaults, and once a run mode is set later calls keep
+/// it, as before `RsBox`. The log guard stays alive until the process exits.
+#[deprecated(note = "use `RsBox::builder()`, which returns errors and the effective settings")]
+pub fn rs_box_setup(project_name: &str, run_mode: RunMode, product_log_dir: &str, log_max_save_days: u64, http_request_timeout: u64) {
+    let run_mode = match get_current_run_mode() {
+        RunMode::RunModeUnknown => run_mode,
+        current => current,
+    };
+    let mut builder = RsBox::builder().project(project_name).log_dir(product_log_dir);
+    if run_mode != RunMode::RunModeUnknown {
+        builder = builder.run_mode(run_mode);
+    }
+    if log_max_save_days > 0 {
+        builder = builder.log_max_save_days(log_max_save_days);
+    }
+    if http_request_timeout > 0 {
+        builder = builder.http_request_timeout(std::time::Duration::from_secs(http_request_timeout));
+    }
+    match builder.build() {
+        Ok(app) => {
+            // Dropping the earlier guard would seal the files the new setup just opened
+            if let Some(previous) = LEGACY_APP.lock().unwrap_or_else(|e| e.into_inner()).replace(app) {
+                std::mem::forget(previous);
+            }
+        }
+        Err(e) => eprintln!("rs_box setup failed: {}", e),
+    }
+}
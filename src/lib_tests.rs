@@ -3,10 +3,51 @@
 #[test]
 fn test_get_version() {
     let version = crate::LIB_VERSION;
-    println!("{}", version.to_string());
+    println!("{}", version);
 }
 
 #[test]
+#[allow(deprecated)]
 fn test_rs_box_setup() {
-    crate::rs_box_setup("test_setup_rs_box",crate::RunMode::RunModeTest,"",7,60)
-}
\ No newline at end of file
+    let _global = crate::rs_box_log::rs_box_log_test::lock_global_config();
+    crate::rs_box_setup("test_setup_rs_box",crate::RunMode::RunModeTest,"",7,60);
+    // Legacy arguments the builder refuses are replaced, not a reason to skip the setup
+    crate::rs_box_setup("test_setup_rs_box",crate::RunMode::RunModeRelease,"",0,0);
+    let app = crate::LEGACY_APP.lock().unwrap();
+    let app = app.as_ref().expect("setup keeps the app alive");
+    assert_eq!(app.run_mode(), crate::RunMode::RunModeTest);
+    assert_eq!((app.log_max_save_days(), app.http_request_timeout()), (7, std::time::Duration::from_secs(60)));
+}
+#[cfg(test)]
+fn args(args: &[&str]) -> std::vec::IntoIter<String> {
+    args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().into_iter()
+}
+
+#[test]
+fn test_run_mode_parse_and_display() {
+    use crate::RunMode;
+    assert_eq!("release".parse::<RunMode>(), Ok(RunMode::RunModeRelease));
+    assert_eq!(" TEST ".parse::<RunMode>(), Ok(RunMode::RunModeTest));
+    assert!("unknown".parse::<RunMode>().is_err());
+    for run_mode in [RunMode::RunModeDebug, RunMode::RunModeRelease, RunMode::RunModeTest] {
+        assert_eq!(format!("{}", run_mode).parse::<RunMode>(), Ok(run_mode));
+        #[allow(deprecated)]
+        let old_name = run_mode.to_string();
+        assert_eq!(old_name, run_mode.to_str());
+    }
+}
+
+#[test]
+fn test_run_mode_detect_order() {
+    use crate::{detect_run_mode, RunMode};
+    // Environment first, then the argument, then the build profile
+    assert_eq!(detect_run_mode(Some("test".to_string()), args(&["--run-mode", "release"]), true), RunMode::RunModeTest);
+    assert_eq!(detect_run_mode(None, args(&["-v", "--run-mode", "release"]), true), RunMode::RunModeRelease);
+    assert_eq!(detect_run_mode(None, args(&["--run-mode=Test"]), false), RunMode::RunModeTest);
+    assert_eq!(detect_run_mode(None, args(&[]), true), RunMode::RunModeDebug);
+    assert_eq!(detect_run_mode(None, args(&[]), false), RunMode::RunModeRelease);
+    // Invalid values fall through to the next source
+    assert_eq!(detect_run_mode(Some("prod".to_string()), args(&["--run-mode=test"]), true), RunMode::RunModeTest);
+    assert_eq!(detect_run_mode(None, args(&["--run-mode"]), false), RunMode::RunModeRelease);
+    assert_eq!(detect_run_mode(None, args(&["--run-modes=test"]), true), RunMode::RunModeDebug);
+}
@@ -0,0 +1,276 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+use crate::rs_box_log::rs_box_log::{try_setup_log_tools_with_config, LogConfig, LogGuard, LogLevel};
+use crate::{set_current_run_mode, RunMode, LIB_VERSION};
+
+const DEFAULT_LOG_MAX_SAVE_DAYS: u64 = 7;
+const DEFAULT_HTTP_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+static HTTP_REQUEST_TIMEOUT: Mutex<Duration> = Mutex::new(DEFAULT_HTTP_REQUEST_TIMEOUT);
+
+/// Timeout for HTTP requests set by the last `RsBoxBuilder::build` (60 seconds before that).
+/// This is for the application's own requests; the OTLP and push log exporters keep their
+/// own `with_timeout` setting.
+pub fn get_http_request_timeout() -> Duration {
+    *HTTP_REQUEST_TIMEOUT.lock().unwrap()
+}
+
+/// Why `RsBoxBuilder::build` refused to set up
+#[derive(Debug)]
+pub enum RsBoxError {
+    /// `project` was not called or got an empty name
+    RsBoxErrorMissingProject,
+    /// The project name cannot be used as a directory name
+    RsBoxErrorInvalidProject(String),
+    /// `RunModeUnknown` was given as run mode
+    RsBoxErrorUnknownRunMode,
+    /// The process already runs in another mode; the run mode is set once
+    RsBoxErrorRunModeAlreadySet { current: RunMode, requested: RunMode },
+    /// An option is out of range, e.g. zero days of log retention
+    RsBoxErrorInvalidOption { option: &'static str, reason: String },
+    /// The log directory cannot be created or written to, or the log file cannot be opened
+    RsBoxErrorLogDir { path: PathBuf, source: io::Error },
+}
+
+impl fmt::Display for RsBoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RsBoxError::RsBoxErrorMissingProject => write!(f, "no project name given"),
+            RsBoxError::RsBoxErrorInvalidProject(name) => write!(f, "invalid project name {:?}", name),
+            RsBoxError::RsBoxErrorUnknownRunMode => write!(f, "run mode is unknown"),
+            RsBoxError::RsBoxErrorRunModeAlreadySet { current, requested } => {
+                write!(f, "run mode is already {}, cannot switch to {}", current, requested)
+            }
+            RsBoxError::RsBoxErrorInvalidOption { option, reason } => write!(f, "invalid {}: {}", option, reason),
+            RsBoxError::RsBoxErrorLogDir { path, source } => {
+                write!(f, "log directory {} is not usable: {}", path.display(), source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RsBoxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RsBoxError::RsBoxErrorLogDir { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Options for `RsBox`, see `RsBox::builder`
+#[derive(Clone, Debug)]
+pub struct RsBoxBuilder {
+    project_name: Option<String>,
+    run_mode: RunMode,
+    log_dir: String,
+    log_level: Option<LogLevel>,
+    save_log_file: Option<bool>,
+    log_max_save_days: u64,
+    http_request_timeout: Duration,
+}
+
+impl RsBoxBuilder {
+    /// Project name, used for the log directory and log records; required
+    pub fn project(mut self, project_name: &str) -> Self {
+        self.project_name = Some(project_name.to_string());
+        self
+    }
+
+    /// Run mode (default `RunMode::detect()`). Decides the log level (DEBUG, INFO in release)
+    /// and whether logs are saved (release and test).
+    pub fn run_mode(mut self, run_mode: RunMode) -> Self {
+        self.run_mode = run_mode;
+        self
+    }
+
+    /// Log directory; empty (the default) means `/var/log/<project>` on Linux, `./logs` elsewhere
+    pub fn log_dir(mut self, log_dir: &str) -> Self {
+        self.log_dir = log_dir.to_string();
+        self
+    }
+
+    /// Overrides the log level chosen by the run mode
+    pub fn log_level(mut self, log_level: LogLevel) -> Self {
+        self.log_level = Some(log_level);
+        self
+    }
+
+    /// Overrides whether the run mode saves log files
+    pub fn save_log_file(mut self, save_log_file: bool) -> Self {
+        self.save_log_file = Some(save_log_file);
+        self
+    }
+
+    /// Days log files are kept (default 7)
+    pub fn log_max_save_days(mut self, log_max_save_days: u64) -> Self {
+        self.log_max_save_days = log_max_save_days;
+        self
+    }
+
+    /// Timeout for the application's HTTP requests, read back with `get_http_request_timeout`
+    /// (default 60 seconds); log exporters are not affected
+    pub fn http_request_timeout(mut self, http_request_timeout: Duration) -> Self {
+        self.http_request_timeout = http_request_timeout;
+        self
+    }
+
+    /// Checks the options, sets up logging and the run mode and logs the effective settings
+    pub fn build(self) -> Result<RsBox, RsBoxError> {
+        let project_name = match self.project_name {
+            Some(name) if !name.trim().is_empty() => name,
+            _ => return Err(RsBoxError::RsBoxErrorMissingProject),
+        };
+        if project_name.contains(['/', '\\']) || project_name == "." || project_name == ".." {
+            return Err(RsBoxError::RsBoxErrorInvalidProject(project_name));
+        }
+        let (default_level, default_save) = match self.run_mode {
+            RunMode::RunModeDebug => (LogLevel::LogLevelDebug, false),
+            RunMode::RunModeTest => (LogLevel::LogLevelDebug, true),
+            RunMode::RunModeRelease => (LogLevel::LogLevelInfo, true),
+            RunMode::RunModeUnknown => return Err(RsBoxError::RsBoxErrorUnknownRunMode),
+        };
+        if self.log_max_save_days == 0 {
+            return Err(RsBoxError::RsBoxErrorInvalidOption {
+                option: "log_max_save_days",
+                reason: "log files must be kept at least one day".to_string(),
+            });
+        }
+        if self.http_request_timeout.is_zero() {
+            return Err(RsBoxError::RsBoxErrorInvalidOption {
+                option: "http_request_timeout",
+                reason: "the timeout must be longer than zero".to_string(),
+            });
+        }
+
+        let log_level = self.log_level.unwrap_or(default_level);
+        let save_log_file = self.save_log_file.unwrap_or(default_save);
+        let config = LogConfig::new(&project_name, save_log_file, &self.log_dir, log_level, self.log_max_save_days);
+        let log_dir = PathBuf::from(config.log_dir());
+        if save_log_file {
+            check_log_dir(&log_dir)?;
+        }
+
+        if let Err(current) = set_current_run_mode(self.run_mode) {
+            return Err(RsBoxError::RsBoxErrorRunModeAlreadySet {
+                current,
+                requested: self.run_mode,
+            });
+        }
+        let log_guard = try_setup_log_tools_with_config(config).map_err(|source| RsBoxError::RsBoxErrorLogDir {
+            path: log_dir.clone(),
+            source,
+        })?;
+        *HTTP_REQUEST_TIMEOUT.lock().unwrap() = self.http_request_timeout;
+
+        let rs_box = RsBox {
+            project_name,
+            run_mode: self.run_mode,
+            log_level,
+            save_log_file,
+            log_dir,
+            log_max_save_days: self.log_max_save_days,
+            http_request_timeout: self.http_request_timeout,
+            _log_guard: log_guard,
+        };
+        crate::log_info!(
+            project = rs_box.project_name.as_str(),
+            rs_box_version = LIB_VERSION,
+            run_mode = %rs_box.run_mode,
+            log_level = rs_box.log_level.to_str(),
+            save_log_file = rs_box.save_log_file,
+            log_dir = %rs_box.log_dir.display(),
+            log_max_save_days = rs_box.log_max_save_days,
+            http_request_timeout_secs = rs_box.http_request_timeout.as_secs_f64(),
+            "rs_box setup"
+        );
+        Ok(rs_box)
+    }
+}
+
+/// Creates `log_dir` and makes sure files can be created in it
+fn check_log_dir(log_dir: &Path) -> Result<(), RsBoxError> {
+    let probe = log_dir.join(format!(".rs_box_probe_{}", std::process::id()));
+    fs::create_dir_all(log_dir)
+        .and_then(|()| fs::write(&probe, b""))
+        .and_then(|()| fs::remove_file(&probe))
+        .map_err(|source| RsBoxError::RsBoxErrorLogDir {
+            path: log_dir.to_path_buf(),
+            source,
+        })
+}
+
+/// The set up application: the settings in effect and the log guard. Keep it alive in
+/// `main`; dropping it flushes the logs like dropping a `LogGuard`.
+#[must_use = "dropping the app flushes and closes the logs right away; bind it, e.g. `let _app = ...`"]
+pub struct RsBox {
+    project_name: String,
+    run_mode: RunMode,
+    log_level: LogLevel,
+    save_log_file: bool,
+    log_dir: PathBuf,
+    log_max_save_days: u64,
+    http_request_timeout: Duration,
+    _log_guard: LogGuard,
+}
+
+impl RsBox {
+    pub fn builder() -> RsBoxBuilder {
+        RsBoxBuilder {
+            project_name: None,
+            run_mode: RunMode::detect(),
+            log_dir: String::new(),
+            log_level: None,
+            save_log_file: None,
+            log_max_save_days: DEFAULT_LOG_MAX_SAVE_DAYS,
+            http_request_timeout: DEFAULT_HTTP_REQUEST_TIMEOUT,
+        }
+    }
+
+    pub fn project_name(&self) -> &str {
+        &self.project_name
+    }
+
+    pub fn run_mode(&self) -> RunMode {
+        self.run_mode
+    }
+
+    pub fn log_level(&self) -> LogLevel {
+        self.log_level
+    }
+
+    pub fn save_log_file(&self) -> bool {
+        self.save_log_file
+    }
+
+    /// Log directory in effect, with the default filled in
+    pub fn log_dir(&self) -> &Path {
+        &self.log_dir
+    }
+
+    pub fn log_max_save_days(&self) -> u64 {
+        self.log_max_save_days
+    }
+
+    pub fn http_request_timeout(&self) -> Duration {
+        self.http_request_timeout
+    }
+}
+
+impl fmt::Debug for RsBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RsBox")
+            .field("project_name", &self.project_name)
+            .field("run_mode", &self.run_mode)
+            .field("log_level", &self.log_level)
+            .field("save_log_file", &self.save_log_file)
+            .field("log_dir", &self.log_dir)
+            .field("log_max_save_days", &self.log_max_save_days)
+            .field("http_request_timeout", &self.http_request_timeout)
+            .finish()
+    }
+}
@@ -0,0 +1,80 @@
+#[cfg(test)]
+use crate::rs_box_log::rs_box_log_test::{lock_global_config, test_log_dir};
+#[cfg(test)]
+use crate::{LogLevel, RsBox, RsBoxError, RunMode};
+#[cfg(test)]
+use std::time::Duration;
+
+#[test]
+fn test_builder_applies_every_option() {
+    let _global = lock_global_config();
+    let log_dir = test_log_dir("rs_box_app");
+    // The run mode is set once per process, so every test uses RunModeTest
+    let app = RsBox::builder()
+        .project("app_pool")
+        .run_mode(RunMode::RunModeTest)
+        .log_dir(&log_dir)
+        .log_level(LogLevel::LogLevelInfo)
+        .log_max_save_days(3)
+        .http_request_timeout(Duration::from_secs(15))
+        .build()
+        .unwrap();
+
+    assert_eq!(app.project_name(), "app_pool");
+    assert_eq!(app.run_mode(), RunMode::RunModeTest);
+    assert_eq!(app.log_level(), LogLevel::LogLevelInfo);
+    assert!(app.save_log_file());
+    assert_eq!(app.log_dir().display().to_string(), log_dir);
+    assert_eq!(app.log_max_save_days(), 3);
+    assert_eq!(crate::get_current_run_mode(), RunMode::RunModeTest);
+    assert_eq!(crate::get_http_request_timeout(), Duration::from_secs(15));
+
+    crate::log_debug("hidden at info");
+    drop(app);
+    let content = std::fs::read_to_string(format!("{}/app_pool/run.log", log_dir)).unwrap();
+    assert!(content.contains("[rs_box setup] project=app_pool rs_box_version="));
+    assert!(content.contains(" run_mode=Test log_level=INFO save_log_file=true "));
+    assert!(content.contains(" log_max_save_days=3 http_request_timeout_secs=15"));
+    assert!(!content.contains("hidden at info"));
+
+    let app = RsBox::builder()
+        .project("app_pool")
+        .run_mode(RunMode::RunModeTest)
+        .save_log_file(false)
+        .build()
+        .unwrap();
+    assert_eq!((app.log_level(), app.save_log_file()), (LogLevel::LogLevelDebug, false));
+    assert_eq!(app.http_request_timeout(), Duration::from_secs(60));
+}
+
+#[test]
+fn test_builder_rejects_bad_options() {
+    let invalid = |builder: crate::RsBoxBuilder| builder.build().unwrap_err();
+
+    assert!(matches!(invalid(RsBox::builder()), RsBoxError::RsBoxErrorMissingProject));
+    assert!(matches!(invalid(RsBox::builder().project(" ")), RsBoxError::RsBoxErrorMissingProject));
+    assert!(matches!(invalid(RsBox::builder().project("pool/a")), RsBoxError::RsBoxErrorInvalidProject(_)));
+    assert!(matches!(
+        invalid(RsBox::builder().project("pool").run_mode(RunMode::RunModeUnknown)),
+        RsBoxError::RsBoxErrorUnknownRunMode
+    ));
+    let error = invalid(RsBox::builder().project("pool").log_max_save_days(0));
+    assert_eq!(error.to_string(), "invalid log_max_save_days: log files must be kept at least one day");
+    assert!(matches!(
+        invalid(RsBox::builder().project("pool").http_request_timeout(Duration::ZERO)),
+        RsBoxError::RsBoxErrorInvalidOption { option: "http_request_timeout", .. }
+    ));
+
+    // A regular file where the log directory should be
+    let log_dir = test_log_dir("rs_box_app_blocked");
+    std::fs::write(&log_dir, b"not a directory").unwrap();
+    let error = invalid(RsBox::builder().project("pool").run_mode(RunMode::RunModeTest).log_dir(&log_dir));
+    assert!(matches!(error, RsBoxError::RsBoxErrorLogDir { .. }));
+    assert!(std::error::Error::source(&error).is_some());
+    let _ = std::fs::remove_file(&log_dir);
+
+    let _ = crate::set_current_run_mode(RunMode::RunModeTest);
+    let error = invalid(RsBox::builder().project("pool").run_mode(RunMode::RunModeRelease));
+    assert_eq!(error.to_string(), "run mode is already Test, cannot switch to Release");
+    assert_eq!(crate::get_current_run_mode(), RunMode::RunModeTest);
+}
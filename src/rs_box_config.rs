@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::de::DeserializeOwned;
+use toml::{Table, Value};
+use crate::{get_current_run_mode, RunMode};
+
+/// Why loading or reading the configuration failed
+#[derive(Debug)]
+pub enum RsBoxConfigError {
+    /// A config file could not be read; only the run mode file may be missing
+    RsBoxConfigErrorRead { path: PathBuf, source: io::Error },
+    /// A config file is not valid TOML
+    RsBoxConfigErrorParse { path: PathBuf, source: toml::de::Error },
+    /// No value at `key`
+    RsBoxConfigErrorMissingKey { key: String },
+    /// The value at `key` does not fit the requested type; `origin` is the file or
+    /// environment variable it came from
+    RsBoxConfigErrorInvalidValue { key: String, origin: String, message: String },
+}
+
+impl fmt::Display for RsBoxConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RsBoxConfigError::RsBoxConfigErrorRead { path, source } => {
+                write!(f, "failed to read config file {}: {}", path.display(), source)
+            }
+            RsBoxConfigError::RsBoxConfigErrorParse { path, source } => {
+                write!(f, "invalid config file {}: {}", path.display(), source)
+            }
+            RsBoxConfigError::RsBoxConfigErrorMissingKey { key } => write!(f, "config key {} is not set", key),
+            RsBoxConfigError::RsBoxConfigErrorInvalidValue { key, origin, message } => {
+                write!(f, "invalid config value {} (from {}): {}", key, origin, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RsBoxConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RsBoxConfigError::RsBoxConfigErrorRead { source, .. } => Some(source),
+            RsBoxConfigError::RsBoxConfigErrorParse { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Loads a layered TOML configuration: the base file, then the file of the run mode next to
+/// it (`config.toml` → `config.release.toml`), then environment variables with a prefix.
+/// Tables are merged key by key; any other value of a later layer replaces the earlier one.
+#[derive(Clone, Debug)]
+pub struct RsBoxConfigLoader {
+    base_path: PathBuf,
+    run_mode: Option<RunMode>,
+    env_prefix: Option<String>,
+}
+
+impl RsBoxConfigLoader {
+    pub fn new(base_path: impl AsRef<Path>) -> Self {
+        RsBoxConfigLoader {
+            base_path: base_path.as_ref().to_path_buf(),
+            run_mode: None,
+            env_prefix: None,
+        }
+    }
+
+    /// Run mode whose file is overlaid; defaults to `get_current_run_mode()`, or
+    /// `RunMode::detect()` before the run mode is set up
+    pub fn with_run_mode(mut self, run_mode: RunMode) -> Self {
+        self.run_mode = Some(run_mode);
+        self
+    }
+
+    /// Overlays environment variables named `<PREFIX>_<KEY>`, with `__` between nested keys:
+    /// `POOL_SERVER__PORT=3333` sets `server.port`. Keys are lowercased. Values are kept as
+    /// strings, so a password `123456` or a version `1.10` stays as written; only when the type
+    /// a value is read into does not take a string is it read as TOML (`3333`, `true`, `["a"]`).
+    pub fn with_env_prefix(mut self, env_prefix: &str) -> Self {
+        self.env_prefix = Some(env_prefix.to_string());
+        self
+    }
+
+    /// Path of the run mode file for `run_mode`
+    pub fn run_mode_path(&self, run_mode: RunMode) -> PathBuf {
+        let stem = self.base_path.file_stem().unwrap_or_default().to_string_lossy();
+        let mode = run_mode.to_str().to_ascii_lowercase();
+        let name = match self.base_path.extension() {
+            Some(extension) => format!("{}.{}.{}", stem, mode, extension.to_string_lossy()),
+            None => format!("{}.{}", stem, mode),
+        };
+        self.base_path.with_file_name(name)
+    }
+
+    pub fn load(&self) -> Result<RsBoxConfig, RsBoxConfigError> {
+        self.load_with_env(std::env::vars())
+    }
+
+    pub(crate) fn load_with_env(&self, env: impl Iterator<Item = (String, String)>) -> Result<RsBoxConfig, RsBoxConfigError> {
+        let mut config = RsBoxConfig::default();
+        let base = read_toml_file(&self.base_path, true)?.unwrap_or_default();
+        config.merge_layer(base, &self.base_path.display().to_string());
+
+        let run_mode = self.run_mode.unwrap_or_else(|| match get_current_run_mode() {
+            RunMode::RunModeUnknown => RunMode::detect(),
+            run_mode => run_mode,
+        });
+        if run_mode != RunMode::RunModeUnknown {
+            let path = self.run_mode_path(run_mode);
+            if let Some(table) = read_toml_file(&path, false)? {
+                config.merge_layer(table, &path.display().to_string());
+            }
+        }
+
+        if let Some(prefix) = &self.env_prefix {
+            let prefix = format!("{}_", prefix);
+            let mut vars: Vec<(String, String)> = env.filter(|(name, _)| name.starts_with(&prefix)).collect();
+            vars.sort();
+            for (name, raw) in vars {
+                let keys: Vec<String> = name[prefix.len()..].split("__").map(str::to_ascii_lowercase).collect();
+                if keys.iter().any(String::is_empty) {
+                    continue;
+                }
+                let table = keys.iter().rev().fold(Value::String(raw), |value, key| {
+                    Value::Table(Table::from_iter([(key.clone(), value)]))
+                });
+                if let Value::Table(table) = table {
+                    config.merge_layer(table, &format!("env {}", name));
+                }
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Reads a TOML file; `None` when a file that is not `required` does not exist
+fn read_toml_file(path: &Path, required: bool) -> Result<Option<Table>, RsBoxConfigError> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !required => return Ok(None),
+        Err(source) => return Err(RsBoxConfigError::RsBoxConfigErrorRead { path: path.to_path_buf(), source }),
+    };
+    content
+        .parse::<Table>()
+        .map(Some)
+        .map_err(|source| RsBoxConfigError::RsBoxConfigErrorParse { path: path.to_path_buf(), source })
+}
+
+/// An environment value read as a TOML value (`3333`, `true`, `["a", "b"]`), if it is one
+fn env_toml_value(raw: &str) -> Option<Value> {
+    format!("value = {}", raw).parse::<Table>().ok()?.remove("value")
+}
+
+/// The merged configuration, see `RsBoxConfigLoader`
+#[derive(Clone, Debug, Default)]
+pub struct RsBoxConfig {
+    root: Table,
+    layers: Vec<String>,
+    // Dotted key path of every value set by a layer -> the file or variable that set it
+    origins: HashMap<String, String>,
+}
+
+impl RsBoxConfig {
+    /// The value at a dotted key path such as `"server.port"`, deserialized into `T`;
+    /// tables can be read into structs deriving `Deserialize`
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<T, RsBoxConfigError> {
+        let value = self.value(key).ok_or_else(|| RsBoxConfigError::RsBoxConfigErrorMissingKey { key: key.to_string() })?;
+        self.deserialize(key, value.clone())
+    }
+
+    /// The whole configuration deserialized into `T`
+    pub fn try_deserialize<T: DeserializeOwned>(&self) -> Result<T, RsBoxConfigError> {
+        self.deserialize("", Value::Table(self.root.clone()))
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.value(key).is_some()
+    }
+
+    /// The file (or `env <NAME>`) the value at `key` comes from
+    pub fn origin(&self, key: &str) -> Option<&str> {
+        let mut path = key;
+        loop {
+            if let Some(origin) = self.origins.get(path) {
+                return Some(origin);
+            }
+            path = &path[..path.rfind('.')?];
+        }
+    }
+
+    /// The files and variables that were merged, in order
+    pub fn layers(&self) -> &[String] {
+        &self.layers
+    }
+
+    pub fn as_table(&self) -> &Table {
+        &self.root
+    }
+
+    fn value(&self, key: &str) -> Option<&Value> {
+        let mut keys = key.split('.');
+        let mut value = self.root.get(keys.next()?)?;
+        for key in keys {
+            value = value.as_table()?.get(key)?;
+        }
+        Some(value)
+    }
+
+    fn deserialize<T: DeserializeOwned>(&self, key: &str, mut value: Value) -> Result<T, RsBoxConfigError> {
+        loop {
+            let e = match serde_path_to_error::deserialize(value.clone()) {
+                Ok(result) => return Ok(result),
+                Err(e) => e,
+            };
+            let mut relative = Vec::new();
+            for segment in e.path().iter() {
+                match segment {
+                    serde_path_to_error::Segment::Map { key } => relative.push(key.clone()),
+                    serde_path_to_error::Segment::Seq { index } => relative.push(index.to_string()),
+                    _ => {}
+                }
+            }
+            let mut path: Vec<String> = if key.is_empty() { Vec::new() } else { vec![key.to_string()] };
+            path.extend(relative.iter().cloned());
+            let full_key = path.join(".");
+            // An environment string the target type does not take is read as TOML and tried again
+            if self.coerce_env_value(&full_key, &mut value, &relative).is_some() {
+                continue;
+            }
+            return Err(RsBoxConfigError::RsBoxConfigErrorInvalidValue {
+                // Missing fields of the root table are reported against the base file
+                origin: self.origin(&full_key).or(self.layers.first().map(String::as_str)).unwrap_or_default().to_string(),
+                key: full_key,
+                message: e.into_inner().message().to_string(),
+            });
+        }
+    }
+
+    /// Replaces the string at `path` within `value` by its TOML reading, if an environment
+    /// variable set it at `key` and it reads as something other than a string
+    fn coerce_env_value(&self, key: &str, value: &mut Value, path: &[String]) -> Option<()> {
+        if !self.origins.get(key)?.starts_with("env ") {
+            return None;
+        }
+        let mut leaf = value;
+        for segment in path {
+            leaf = match leaf {
+                Value::Table(table) => table.get_mut(segment)?,
+                Value::Array(array) => array.get_mut(segment.parse::<usize>().ok()?)?,
+                _ => return None,
+            };
+        }
+        let coerced = env_toml_value(leaf.as_str()?).filter(|coerced| !coerced.is_str())?;
+        *leaf = coerced;
+        Some(())
+    }
+
+    fn merge_layer(&mut self, layer: Table, origin: &str) {
+        self.layers.push(origin.to_string());
+        merge_table(&mut self.root, layer, "", origin, &mut self.origins);
+    }
+}
+
+fn merge_table(target: &mut Table, layer: Table, prefix: &str, origin: &str, origins: &mut HashMap<String, String>) {
+    for (key, value) in layer {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match (target.get_mut(&key), value) {
+            (Some(Value::Table(existing)), Value::Table(table)) => merge_table(existing, table, &path, origin, origins),
+            (_, value) => {
+                // A replaced table takes its nested origins with it
+                origins.retain(|known, _| !known.starts_with(&format!("{}.", path)));
+                record_origins(&value, &path, origin, origins);
+                target.insert(key, value);
+            }
+        }
+    }
+}
+
+fn record_origins(value: &Value, path: &str, origin: &str, origins: &mut HashMap<String, String>) {
+    origins.insert(path.to_string(), origin.to_string());
+    if let Value::Table(table) = value {
+        for (key, value) in table {
+            record_origins(value, &format!("{}.{}", path, key), origin, origins);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+#[cfg(test)]
+use crate::rs_box_log::rs_box_log_test::test_log_dir;
+#[cfg(test)]
+use crate::{RsBoxConfigError, RsBoxConfigLoader, RunMode};
+#[cfg(test)]
+use serde::Deserialize;
+
+#[cfg(test)]
+#[derive(Debug, Deserialize, PartialEq)]
+struct ServerConfig {
+    host: String,
+    port: u16,
+    #[serde(default)]
+    tls: bool,
+}
+
+#[cfg(test)]
+#[derive(Debug, Deserialize)]
+struct AppConfig {
+    server: ServerConfig,
+    pools: Vec<String>,
+}
+
+#[cfg(test)]
+fn env(vars: &[(&str, &str)]) -> std::vec::IntoIter<(String, String)> {
+    vars.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect::<Vec<_>>().into_iter()
+}
+
+#[test]
+fn test_layers_merge_in_order() {
+    let dir = test_log_dir("config_layers");
+    std::fs::create_dir_all(&dir).unwrap();
+    let base_path = format!("{}/config.toml", dir);
+    std::fs::write(&base_path, "name = \"pool\"\npools = [\"eth\"]\n[server]\nhost = \"127.0.0.1\"\nport = 3333\n").unwrap();
+    std::fs::write(format!("{}/config.release.toml", dir), "[server]\nhost = \"0.0.0.0\"\n").unwrap();
+
+    let loader = RsBoxConfigLoader::new(&base_path).with_env_prefix("POOL");
+    let config = loader
+        .clone()
+        .with_run_mode(RunMode::RunModeRelease)
+        .load_with_env(env(&[
+            ("POOL_SERVER__PORT", "4444"),
+            ("POOL_SERVER__TLS", "true"),
+            ("POOL_POOLS", "[\"eth\", \"btc\"]"),
+            ("POOL_MOTD", "hello world"),
+            ("OTHER_SERVER__PORT", "1"),
+        ]))
+        .unwrap();
+
+    // The release file overrides the host only, the environment the rest
+    let server: ServerConfig = config.get("server").unwrap();
+    assert_eq!(server, ServerConfig { host: "0.0.0.0".to_string(), port: 4444, tls: true });
+    assert_eq!(config.get::<String>("motd").unwrap(), "hello world");
+    assert_eq!(config.get::<String>("name").unwrap(), "pool");
+    let app: AppConfig = config.try_deserialize().unwrap();
+    assert_eq!(app.pools, ["eth", "btc"]);
+    assert_eq!(app.server.port, 4444);
+
+    assert_eq!(config.origin("server.host"), Some(format!("{}/config.release.toml", dir).as_str()));
+    assert_eq!(config.origin("server.port"), Some("env POOL_SERVER__PORT"));
+    assert_eq!(config.origin("name"), Some(base_path.as_str()));
+    assert_eq!(config.layers().len(), 6);
+
+    // No file for the test mode: the base file alone
+    let config = loader.with_run_mode(RunMode::RunModeTest).load_with_env(env(&[])).unwrap();
+    assert_eq!(config.get::<String>("server.host").unwrap(), "127.0.0.1");
+    assert!(!config.contains("server.tls"));
+}
+
+#[test]
+fn test_errors_name_file_and_key() {
+    let dir = test_log_dir("config_errors");
+    std::fs::create_dir_all(&dir).unwrap();
+    let base_path = format!("{}/config.toml", dir);
+    std::fs::write(&base_path, "pools = [\"eth\", 7]\n[server]\nhost = \"127.0.0.1\"\nport = 3333\n").unwrap();
+    let loader = RsBoxConfigLoader::new(&base_path).with_run_mode(RunMode::RunModeDebug).with_env_prefix("POOL");
+
+    let config = loader.load_with_env(env(&[("POOL_SERVER__PORT", "high")])).unwrap();
+    let error = config.get::<ServerConfig>("server").unwrap_err();
+    assert!(
+        matches!(&error, RsBoxConfigError::RsBoxConfigErrorInvalidValue { key, origin, .. } if key == "server.port" && origin == "env POOL_SERVER__PORT"),
+        "{}",
+        error
+    );
+    let error = config.try_deserialize::<AppConfig>().unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        format!("invalid config value pools.1 (from {}): invalid type: integer `7`, expected a string", base_path)
+    );
+    assert!(matches!(
+        config.get::<u16>("server.listen"),
+        Err(RsBoxConfigError::RsBoxConfigErrorMissingKey { key }) if key == "server.listen"
+    ));
+
+    std::fs::write(format!("{}/config.debug.toml", dir), "[server\n").unwrap();
+    let error = loader.load_with_env(env(&[])).unwrap_err();
+    assert!(matches!(&error, RsBoxConfigError::RsBoxConfigErrorParse { path, .. } if path.ends_with("config.debug.toml")));
+
+    let error = RsBoxConfigLoader::new(format!("{}/missing.toml", dir)).load_with_env(env(&[])).unwrap_err();
+    assert!(matches!(error, RsBoxConfigError::RsBoxConfigErrorRead { .. }));
+}
+
+#[test]
+fn test_env_values_stay_strings_unless_needed() {
+    let dir = test_log_dir("config_env_strings");
+    std::fs::create_dir_all(&dir).unwrap();
+    let base_path = format!("{}/config.toml", dir);
+    std::fs::write(&base_path, "[server]\nhost = \"127.0.0.1\"\nport = 3333\n").unwrap();
+
+    let config = RsBoxConfigLoader::new(&base_path)
+        .with_run_mode(RunMode::RunModeTest)
+        .with_env_prefix("POOL")
+        .load_with_env(env(&[
+            ("POOL_RPC__PASSWORD", "123456"),
+            ("POOL_VERSION", "1.10"),
+            ("POOL_SERVER__PORT", "4444"),
+            ("POOL_SERVER__TLS", "true"),
+        ]))
+        .unwrap();
+
+    assert_eq!(config.get::<String>("rpc.password").unwrap(), "123456");
+    assert_eq!(config.get::<String>("version").unwrap(), "1.10");
+    // Read as numbers or booleans where the target type asks for one
+    assert_eq!(config.get::<u32>("rpc.password").unwrap(), 123456);
+    assert_eq!(config.get::<f64>("version").unwrap(), 1.1);
+    let server: ServerConfig = config.get("server").unwrap();
+    assert_eq!(server, ServerConfig { host: "127.0.0.1".to_string(), port: 4444, tls: true });
+    assert_eq!(config.as_table()["version"].as_str(), Some("1.10"));
+}
@@ -1,4 +1,41 @@
 pub mod rs_box_log;
-mod rs_box_log_test;
+pub(crate) mod rs_box_log_test;
 mod rs_box_log_file_handle;
+mod rs_box_log_format;
+mod rs_box_log_format_test;
+mod rs_box_log_metrics;
+mod rs_box_log_metrics_test;
+mod rs_box_log_redaction;
+mod rs_box_log_redaction_test;
+mod rs_box_log_hierarchy;
+mod rs_box_log_hierarchy_test;
+mod rs_box_log_layout;
+mod rs_box_log_layout_test;
+mod rs_box_log_hooks;
+mod rs_box_log_hooks_test;
+mod rs_box_log_integrity;
+mod rs_box_log_integrity_test;
+mod rs_box_log_clock;
+mod rs_box_log_clock_test;
+mod rs_box_log_fields;
+mod rs_box_log_fields_test;
+mod rs_box_log_http;
+mod rs_box_log_otlp;
+mod rs_box_log_otlp_test;
+mod rs_box_log_push;
+mod rs_box_log_push_test;
+#[cfg(feature = "tokio")]
+mod rs_box_log_async;
+#[cfg(all(test, feature = "tokio"))]
+mod rs_box_log_async_test;
+mod rs_box_log_sampling;
+mod rs_box_log_sampling_test;
+mod rs_box_log_summary;
+mod rs_box_log_summary_test;
+mod rs_box_log_view;
+mod rs_box_log_view_test;
 
+#[cfg(target_family = "unix")]
+mod rs_box_log_journald;
+#[cfg(target_family = "unix")]
+mod rs_box_log_journald_test;
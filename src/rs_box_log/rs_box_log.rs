@@ -1,12 +1,45 @@
-use chrono::Local;
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime};
 use std::cmp::PartialEq;
-use std::fs::{self, File, OpenOptions};
-use std::io::Write;
+use std::fs;
+use std::collections::HashMap;
+use std::fmt;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
-use backtrace;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
 use once_cell::sync::Lazy;
 use pathdiff::diff_paths;
+use super::rs_box_log_file_handle::{flush_all_file_handles, mark_log_shutdown, seal_all_file_handles, LogFileHandle};
+use super::rs_box_log_integrity::LogChainSettings;
+use super::rs_box_log_format::{current_thread_id, host_name, render_file_header, render_record};
+use super::rs_box_log_hierarchy::{configured_sinks, resolve_logger_config};
+use super::rs_box_log_hooks::run_record_hooks;
+use super::rs_box_log_metrics::{
+    logger_counters, record_bytes_written, record_deleted_file, record_dropped, record_emitted, record_error, record_rotation,
+    record_sampled_out, set_recent_errors_limit, LogLevelCounters,
+};
+use super::rs_box_log_summary::{rearm_shutdown_summary, write_shutdown_summary};
+#[cfg(feature = "tokio")]
+pub use super::rs_box_log_async::AsyncLoggerManager;
+pub use super::rs_box_log_clock::{LogClock, LogManualClock, LogSystemClock};
+pub use super::rs_box_log_fields::LogFieldValue;
+pub use super::rs_box_log_file_handle::LogWritePolicy;
+pub use super::rs_box_log_format::{LogOutputFormat, LogTextTemplate};
+pub use super::rs_box_log_hierarchy::{configure_logger, LogChildFileMode, LoggerSettings};
+pub use super::rs_box_log_hooks::{on_record, remove_record_hook, LogHookId, LogRecordFilter};
+pub use super::rs_box_log_integrity::{verify_log_file, verify_log_files, LogIntegrity, LogIntegrityIssue, LogIntegrityReport};
+#[cfg(target_family = "unix")]
+pub use super::rs_box_log_journald::{LogJournaldConfig, LogJournaldSink, JOURNALD_SOCKET_PATH};
+pub use super::rs_box_log_layout::{LogFileEntry, LogFileLayout};
+pub use super::rs_box_log_metrics::{log_metrics_snapshot, LogMetricsSnapshot};
+pub use super::rs_box_log_otlp::{LogOtlpConfig, LogOtlpSink};
+pub use super::rs_box_log_push::{LogPushConfig, LogPushFormat, LogPushSink};
+pub use super::rs_box_log_view::{
+    list_log_modules, parse_log_entries, parse_log_view_time, read_log_entries, strip_log_colors, LogViewEntry,
+    LogViewFilter, LogViewFollower,
+};
+pub use super::rs_box_log_redaction::{LogRedactionRule, LogRedactor};
+pub use super::rs_box_log_sampling::{LogSampler, LogSampling};
+pub use super::rs_box_log_summary::{log_summary, write_log_summary, LogSummary};
 
 #[derive(Clone, Copy, Debug)]
 pub enum LogFileSaveType {
@@ -33,15 +66,313 @@ impl LogLevel {
             LogLevel::LogLevelTrace => "TRACE",
         }
     }
+
+    /// Parses a level name as written by `to_str`, case-insensitively; `warn` is accepted too
+    pub fn from_name(name: &str) -> Option<LogLevel> {
+        match name.to_ascii_uppercase().as_str() {
+            "INFO" => Some(LogLevel::LogLevelInfo),
+            "WARNING" | "WARN" => Some(LogLevel::LogLevelWarning),
+            "ERROR" => Some(LogLevel::LogLevelError),
+            "DEBUG" => Some(LogLevel::LogLevelDebug),
+            "TRACE" => Some(LogLevel::LogLevelTrace),
+            _ => None,
+        }
+    }
+
+    /// Verbosity rank, from 1 (ERROR) to 5 (TRACE); a record passes a limit of equal or higher rank
+    pub const fn severity(&self) -> u8 {
+        match self {
+            LogLevel::LogLevelError => 1,
+            LogLevel::LogLevelWarning => 2,
+            LogLevel::LogLevelInfo => 3,
+            LogLevel::LogLevelDebug => 4,
+            LogLevel::LogLevelTrace => 5,
+        }
+    }
+}
+
+/// Highest severity rank the logging macros are compiled with, set by the
+/// `max_level_*` and `release_max_level_*` cargo features; 0 disables them all.
+pub const STATIC_MAX_LEVEL_SEVERITY: u8 = static_max_level_severity();
+
+const fn static_max_level_severity() -> u8 {
+    if !cfg!(debug_assertions) {
+        if cfg!(feature = "release_max_level_off") {
+            return 0;
+        } else if cfg!(feature = "release_max_level_error") {
+            return 1;
+        } else if cfg!(feature = "release_max_level_warn") {
+            return 2;
+        } else if cfg!(feature = "release_max_level_info") {
+            return 3;
+        } else if cfg!(feature = "release_max_level_debug") {
+            return 4;
+        } else if cfg!(feature = "release_max_level_trace") {
+            return 5;
+        }
+    }
+    if cfg!(feature = "max_level_off") {
+        0
+    } else if cfg!(feature = "max_level_error") {
+        1
+    } else if cfg!(feature = "max_level_warn") {
+        2
+    } else if cfg!(feature = "max_level_info") {
+        3
+    } else if cfg!(feature = "max_level_debug") {
+        4
+    } else {
+        5
+    }
+}
+
+/// True if `level` passes both the compiled-in maximum and the configured `log_level`
+#[allow(
+    clippy::absurd_extreme_comparisons,
+    reason = "the compiled-in maximum is 0 with `max_level_off`, which rejects every level"
+)]
+fn level_enabled(level: LogLevel, log_level: LogLevel) -> bool {
+    level.severity() <= STATIC_MAX_LEVEL_SEVERITY && level.severity() <= log_level.severity()
 }
 
 #[derive(Clone, Debug)]
 pub struct LogConfig {
-    project_name: String,
-    enable_save_log_file: bool,
-    log_dir: String,
-    log_level: LogLevel,
-    file_save_days_max: u64,
+    pub(crate) project_name: String,
+    pub(crate) enable_save_log_file: bool,
+    pub(crate) log_dir: String,
+    pub(crate) log_level: LogLevel,
+    pub(crate) file_save_days_max: u64,
+    pub(crate) write_policy: LogWritePolicy,
+    pub(crate) output_format: LogOutputFormat,
+    pub(crate) write_file_header: bool,
+    pub(crate) app_version: Option<String>,
+    pub(crate) redactor: Option<Arc<LogRedactor>>,
+    pub(crate) sinks: Vec<Arc<dyn LogSink>>,
+    /// Tags text lines with the logger name, set for children sharing a parent's file
+    pub(crate) show_logger_name: bool,
+    pub(crate) file_layout: LogFileLayout,
+    pub(crate) text_template: Option<LogTextTemplate>,
+    pub(crate) record_thread_name: bool,
+    pub(crate) record_thread_id: bool,
+    pub(crate) record_process_id: bool,
+    pub(crate) record_host_name: bool,
+    pub(crate) clock: Arc<dyn LogClock>,
+    pub(crate) process_lock: bool,
+    pub(crate) sampler: Option<LogSampler>,
+    /// Number of recent errors in the summary written on graceful exit, `None` for no summary
+    pub(crate) shutdown_summary: Option<usize>,
+    pub(crate) integrity: Option<LogIntegrity>,
+}
+
+impl LogConfig {
+    pub fn new(
+        project_name: &str,
+        enable_save_log_file: bool,
+        log_dir: &str,
+        log_level: LogLevel,
+        file_save_days_max: u64,
+    ) -> Self {
+        let log_dir = if log_dir.is_empty() {
+            if cfg!(target_os = "linux") {
+                format!("/var/log/{}", project_name)
+            } else {
+                "./logs".to_string()
+            }
+        } else {
+            log_dir.to_string()
+        };
+
+        LogConfig {
+            project_name: project_name.to_string(),
+            enable_save_log_file,
+            log_dir,
+            log_level,
+            file_save_days_max,
+            write_policy: LogWritePolicy::LogWritePolicyUnbuffered,
+            output_format: LogOutputFormat::LogOutputFormatText,
+            write_file_header: false,
+            app_version: None,
+            redactor: None,
+            sinks: Vec::new(),
+            show_logger_name: false,
+            file_layout: LogFileLayout::default(),
+            text_template: None,
+            record_thread_name: false,
+            record_thread_id: false,
+            record_process_id: false,
+            record_host_name: false,
+            clock: Arc::new(LogSystemClock),
+            process_lock: false,
+            sampler: None,
+            shutdown_summary: None,
+            integrity: None,
+        }
+    }
+
+    pub fn with_write_policy(mut self, write_policy: LogWritePolicy) -> Self {
+        self.write_policy = write_policy;
+        self
+    }
+
+    pub fn with_output_format(mut self, output_format: LogOutputFormat) -> Self {
+        self.output_format = output_format;
+        self
+    }
+
+    /// Writes a header block (versions, pid, host, run mode, config) into every newly opened log file.
+    pub fn with_file_header(mut self, write_file_header: bool) -> Self {
+        self.write_file_header = write_file_header;
+        self
+    }
+
+    pub fn with_app_version(mut self, app_version: &str) -> Self {
+        self.app_version = Some(app_version.to_string());
+        self
+    }
+
+    /// Masks secrets in every message before it reaches any output.
+    pub fn with_redactor(mut self, redactor: LogRedactor) -> Self {
+        self.redactor = Some(Arc::new(redactor));
+        self
+    }
+
+    /// Adds an output that receives every record next to the file/console output.
+    pub fn with_sink(mut self, sink: Arc<dyn LogSink>) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    /// File naming and directory layout below `log_dir`, see `LogFileLayout`.
+    pub fn with_file_layout(mut self, file_layout: LogFileLayout) -> Self {
+        self.file_layout = file_layout;
+        self
+    }
+
+    /// Replaces the default text line layout, see `LogTextTemplate`.
+    pub fn with_text_template(mut self, text_template: LogTextTemplate) -> Self {
+        self.text_template = Some(text_template);
+        self
+    }
+
+    pub fn with_thread_name(mut self, record_thread_name: bool) -> Self {
+        self.record_thread_name = record_thread_name;
+        self
+    }
+
+    /// Records a per-process thread number, assigned when a thread first logs
+    pub fn with_thread_id(mut self, record_thread_id: bool) -> Self {
+        self.record_thread_id = record_thread_id;
+        self
+    }
+
+    pub fn with_process_id(mut self, record_process_id: bool) -> Self {
+        self.record_process_id = record_process_id;
+        self
+    }
+
+    pub fn with_host_name(mut self, record_host_name: bool) -> Self {
+        self.record_host_name = record_host_name;
+        self
+    }
+
+    /// Replaces the wall clock, e.g. with a `LogManualClock` to drive rotation in tests
+    pub fn with_clock(mut self, clock: Arc<dyn LogClock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Samples the noisy levels of this logger, see `LogSampler`
+    pub fn with_sampler(mut self, sampler: LogSampler) -> Self {
+        self.sampler = Some(sampler);
+        self
+    }
+
+    /// Writes a summary (runtime, records per level, rotations, deleted files, dropped and
+    /// sampled out records, the last `last_errors` errors) when the `LogGuard` is dropped or
+    /// `shutdown` is called. Takes effect through `setup_log_tools_with_config`.
+    pub fn with_shutdown_summary(mut self, last_errors: usize) -> Self {
+        self.shutdown_summary = Some(last_errors);
+        self
+    }
+
+    /// Chains the records of the log files with an HMAC under `integrity`'s key, so
+    /// `verify_log_file` detects modified, removed, reordered or cut off records.
+    /// Files shared by several processes through `with_process_lock` cannot be chained and
+    /// are not opened; a `{pid}` in the file layout gives each process its own chain.
+    pub fn with_integrity(mut self, integrity: LogIntegrity) -> Self {
+        self.integrity = Some(integrity);
+        self
+    }
+
+    /// Takes an advisory lock on the log file for every write, for several processes
+    /// sharing `project_name` and `log_dir`. A `{pid}` in the file layout avoids sharing instead.
+    pub fn with_process_lock(mut self, process_lock: bool) -> Self {
+        self.process_lock = process_lock;
+        self
+    }
+
+    /// Log files written with this config that still exist, oldest first.
+    pub fn log_files(&self) -> Vec<LogFileEntry> {
+        self.file_layout.list_files(&self.log_dir, &self.project_name)
+    }
+
+    pub fn project_name(&self) -> &str {
+        &self.project_name
+    }
+
+    pub fn log_dir(&self) -> &str {
+        &self.log_dir
+    }
+
+    pub fn app_version(&self) -> Option<&str> {
+        self.app_version.as_deref()
+    }
+
+    pub fn output_format(&self) -> LogOutputFormat {
+        self.output_format
+    }
+
+    pub(crate) fn describe(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("enable_save_log_file", self.enable_save_log_file.to_string()),
+            ("log_dir", self.log_dir.clone()),
+            ("log_level", self.log_level.to_str().to_string()),
+            ("file_save_days_max", self.file_save_days_max.to_string()),
+            ("write_policy", format!("{:?}", self.write_policy)),
+            ("output_format", self.output_format.to_str().to_string()),
+            ("process_lock", self.process_lock.to_string()),
+            ("file_pattern", self.file_layout.pattern().to_string()),
+            ("text_template", self.text_template.as_ref().map_or("default", |t| t.template()).to_string()),
+            (
+                "record_fields",
+                [
+                    (self.record_thread_name, "thread_name"),
+                    (self.record_thread_id, "thread_id"),
+                    (self.record_process_id, "pid"),
+                    (self.record_host_name, "host"),
+                ]
+                .iter()
+                .filter(|(enabled, _)| *enabled)
+                .map(|(_, name)| *name)
+                .collect::<Vec<_>>()
+                .join(","),
+            ),
+            (
+                "sampling",
+                self.sampler.as_ref().map_or_else(
+                    || "off".to_string(),
+                    |sampler| format!("{} up to {}", sampler.sampling(), sampler.max_level().to_str()),
+                ),
+            ),
+            (
+                "shutdown_summary",
+                self.shutdown_summary
+                    .map_or_else(|| "off".to_string(), |last_errors| format!("last {} errors", last_errors)),
+            ),
+            ("integrity", self.integrity.as_ref().map_or("off", |_| "hmac-sha256").to_string()),
+            ("redaction_rules", self.redactor.as_ref().map_or(0, |redactor| redactor.rules().len()).to_string()),
+        ]
+    }
 }
 
 impl Default for LogConfig {
@@ -59,190 +390,324 @@ impl Default for LogConfig {
             log_dir,
             log_level: LogLevel::LogLevelTrace,
             file_save_days_max: 7,
+            write_policy: LogWritePolicy::LogWritePolicyUnbuffered,
+            output_format: LogOutputFormat::LogOutputFormatText,
+            write_file_header: false,
+            app_version: None,
+            redactor: None,
+            sinks: Vec::new(),
+            show_logger_name: false,
+            file_layout: LogFileLayout::default(),
+            text_template: None,
+            record_thread_name: false,
+            record_thread_id: false,
+            record_process_id: false,
+            record_host_name: false,
+            clock: Arc::new(LogSystemClock),
+            process_lock: false,
+            sampler: None,
+            shutdown_summary: None,
+            integrity: None,
         }
     }
 }
 
-pub struct LoggerManager {
+/// A single log event as it flows from the logging call to the output.
+#[derive(Clone, Debug)]
+pub struct LogRecord {
+    pub time: chrono::DateTime<chrono::Utc>,
+    pub level: LogLevel,
+    pub logger_name: String,
+    /// Caller info for DEBUG/TRACE records, empty otherwise
+    pub location: String,
+    /// Source file and line of the caller, for DEBUG/TRACE records when debug info is available
+    pub code_file: Option<String>,
+    pub code_line: Option<u32>,
+    /// Sampling that let this record through, if its level is sampled
+    pub sampling: Option<LogSampling>,
+    pub message: String,
+    pub thread_name: Option<String>,
+    pub thread_id: Option<u64>,
+    pub pid: Option<u32>,
+    pub host: Option<String>,
+    /// Structured key/value fields, in call order
+    pub fields: Vec<(String, LogFieldValue)>,
+}
+
+/// An additional destination for log records, e.g. a network collector.
+pub trait LogSink: Send + Sync {
+    fn write_record(&self, record: &LogRecord);
+
+    fn flush(&self) {}
+}
+
+impl fmt::Debug for dyn LogSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LogSink")
+    }
+}
+
+/// Where a logger takes its config from
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum LogConfigSource {
+    /// The global config as is, named after the global project (the default logger)
+    Global,
+    /// The global config resolved for the logger's dotted name
+    GlobalNamed,
+    /// The config given at construction, never reloaded
+    Fixed,
+}
+
+/// A record between `build_record` and `write_record`
+pub(crate) struct PendingRecord {
+    record: LogRecord,
+    /// Record time in the config clock's offset, which decides the file it goes to
+    now: DateTime<FixedOffset>,
+    /// Unresolved backtrace for the caller location of DEBUG/TRACE records
+    caller: Option<backtrace::Backtrace>,
+}
+
+impl PendingRecord {
+    fn resolve_caller(&mut self) {
+        if let Some(caller) = self.caller.take() {
+            let (location, code_file, code_line) = LoggerManager::get_caller_info(caller);
+            self.record.location = location;
+            self.record.code_file = code_file;
+            self.record.code_line = code_line;
+        }
+    }
+}
+
+/// Everything a logger derives from its config; swapped as a whole on reconfiguration
+struct LoggerState {
+    generation: u64,
+    name: String,
     config: Arc<LogConfig>,
-    file: Option<Mutex<File>>,
-    current_log_path: Mutex<PathBuf>,
+    file: Option<Arc<LogFileHandle>>,
+    counters: Arc<LogLevelCounters>,
+}
+
+pub struct LoggerManager {
+    source: LogConfigSource,
+    module_name: String,
+    state: RwLock<LoggerState>,
+    /// Held while a new state is built, so one caller opens files and the rest keep logging
+    refreshing: Mutex<()>,
+}
+
+impl Default for LoggerManager {
+    fn default() -> Self {
+        LoggerManager::following_global(LogConfigSource::Global, "")
+    }
 }
 
 impl LoggerManager {
-    pub fn default() -> Self {
-        let config = GLOBAL_LOG_CONFIG.lock().unwrap().clone();
-        LoggerManager::initialize_logger(config)
+    /// Creates a logger for `config`. When its log file cannot be opened, or is already open
+    /// with settings it cannot share (see `LogConfig::with_integrity`), the error is printed
+    /// and the logger writes to the console only; `try_with_config` returns the error instead.
+    pub fn with_config(config: LogConfig) -> Self {
+        let name = config.project_name.clone();
+        LoggerManager::with_fixed_config(&name, config)
     }
 
+    /// Like `with_config`, but fails when the log file cannot be opened or shared
+    pub fn try_with_config(config: LogConfig) -> std::io::Result<Self> {
+        let name = config.project_name.clone();
+        Ok(LoggerManager {
+            source: LogConfigSource::Fixed,
+            module_name: name.clone(),
+            state: RwLock::new(LoggerState::try_open(name, Arc::new(config), 0, None)?),
+            refreshing: Mutex::new(()),
+        })
+    }
+
+    /// Creates the logger `module_name` from the global config. Dotted names such as
+    /// `pool.stratum.eth` inherit the settings given to their ancestors via `configure_logger`.
+    /// The logger follows later changes made by `reconfigure_log` and `configure_logger`.
     pub fn new(module_name: &str) -> Self {
-        let global_config = GLOBAL_LOG_CONFIG.lock().unwrap().clone();
-        let mut new_config = (*global_config).clone();
-        new_config.project_name = module_name.to_string();
-        LoggerManager::initialize_logger(Arc::new(new_config))
-    }
-
-    fn initialize_logger(config: Arc<LogConfig>) -> Self {
-        let (file, log_path) = if config.enable_save_log_file {
-            let file_path = LoggerManager::get_log_file_path(&config);
-            let log_dir = Path::new(&file_path).parent().unwrap();
-            if let Err(e) = fs::create_dir_all(log_dir) {
-                log_error(&format!("Failed to create log directory {}: {}", log_dir.display(), e));
-                (None, PathBuf::new())
-            } else {
-                match OpenOptions::new()
-                    .create(true)
-                    .write(true)
-                    .append(true)
-                    .open(&file_path)
-                {
-                    Ok(file) => {
-                        LoggerManager::create_symlink(&file_path, &config);
-                        (Some(Mutex::new(file)), PathBuf::from(file_path))
-                    }
-                    Err(e) => {
-                        log_error(&format!("Failed to open log file {}: {}", file_path, e));
-                        (None, PathBuf::new())
-                    }
-                }
-            }
-        } else {
-            (None, PathBuf::new())
-        };
+        LoggerManager::following_global(LogConfigSource::GlobalNamed, module_name)
+    }
+
+    /// Same as `new`, but resolved once on top of `root` instead of following the global config.
+    pub fn with_root_config(module_name: &str, root: &LogConfig) -> Self {
+        LoggerManager::with_fixed_config(module_name, resolve_logger_config(root, module_name))
+    }
+
+    /// Returns the shared logger registered under `module_name`, creating it on first use.
+    pub fn get(module_name: &str) -> Arc<LoggerManager> {
+        let mut registry = LOGGER_REGISTRY.lock().unwrap();
+        registry
+            .entry(module_name.to_string())
+            .or_insert_with(|| Arc::new(LoggerManager::new(module_name)))
+            .clone()
+    }
 
+    fn with_fixed_config(name: &str, config: LogConfig) -> Self {
         LoggerManager {
-            config,
-            file,
-            current_log_path: Mutex::new(log_path),
+            source: LogConfigSource::Fixed,
+            module_name: name.to_string(),
+            state: RwLock::new(LoggerState::open(name.to_string(), Arc::new(config), 0, None)),
+            refreshing: Mutex::new(()),
         }
     }
 
-    fn get_log_file_path(config: &LogConfig) -> String {
-        let now = Local::now();
-        let date_folder = now.format("%Y-%m-%d").to_string();
-        let hour = now.format("%H").to_string();
-        format!(
-            "{}/{}/{}/{}_{}.log",
-            config.log_dir, config.project_name, date_folder, date_folder, hour
-        )
+    fn following_global(source: LogConfigSource, module_name: &str) -> Self {
+        let (global, generation) = global_log_config();
+        let (name, config) = LoggerManager::resolve_config(source, module_name, &global);
+        LoggerManager {
+            source,
+            module_name: module_name.to_string(),
+            state: RwLock::new(LoggerState::open(name, config, generation, None)),
+            refreshing: Mutex::new(()),
+        }
     }
 
-    fn create_symlink(target: &str, config: &LogConfig) {
-        let log_dir = format!("{}/{}/run.log", config.log_dir, config.project_name);
-        let link_path = Path::new(&log_dir);
-        let target_path = Path::new(target);
+    fn resolve_config(source: LogConfigSource, module_name: &str, global: &Arc<LogConfig>) -> (String, Arc<LogConfig>) {
+        match source {
+            LogConfigSource::Global | LogConfigSource::Fixed => {
+                (global.project_name.clone(), global.clone())
+            }
+            LogConfigSource::GlobalNamed => {
+                (module_name.to_string(), Arc::new(resolve_logger_config(global, module_name)))
+            }
+        }
+    }
 
-        if !target_path.exists() {
-            log_warning(&format!("Target log file {} does not exist", target));
+    /// Picks up a newer global config, if this logger follows it and one was published.
+    /// The new state, which may open files, is built without holding `state`, and callers
+    /// arriving while another thread builds it keep using the current state.
+    pub(crate) fn refresh(&self) {
+        if self.source == LogConfigSource::Fixed
+            || self.state.read().unwrap().generation == LOG_CONFIG_GENERATION.load(Ordering::SeqCst)
+        {
             return;
         }
+        let Ok(_refreshing) = self.refreshing.try_lock() else {
+            return;
+        };
+        let (global, generation) = global_log_config();
+        let previous_file = {
+            let state = self.state.read().unwrap();
+            if state.generation == generation {
+                return; // Another thread reloaded first
+            }
+            state.file.clone()
+        };
+        let (name, config) = LoggerManager::resolve_config(self.source, &self.module_name, &global);
+        let next = LoggerState::open(name, config, generation, previous_file);
+        *self.state.write().unwrap() = next;
+    }
 
-        let relative_target = diff_paths(target_path, link_path.parent().unwrap()).unwrap();
+    fn current_state(&self) -> RwLockReadGuard<'_, LoggerState> {
+        self.refresh();
+        self.state.read().unwrap()
+    }
 
-        if link_path.exists() {
-            if let Ok(existing_target) = fs::read_link(link_path) {
-                if existing_target == relative_target {
-                    return; // Symlink already points to the correct target
-                }
-                if let Err(e) = fs::remove_file(link_path) {
-                    log_warning(&format!("Failed to remove old symlink {}: {}", link_path.display(), e));
-                }
+    /// Path of the file to write right now. With a `{seq}` pattern this is the first
+    /// sequence number not yet written by an earlier run (or the one already open here).
+    fn get_log_file_path(config: &LogConfig, now: NaiveDateTime) -> PathBuf {
+        let layout = &config.file_layout;
+        let mut seq = 0;
+        loop {
+            let path = layout.file_path(&config.log_dir, &config.project_name, now, seq);
+            if !layout.has_seq() || LogFileHandle::is_open(&path) || !path.exists() {
+                return path;
             }
+            seq += 1;
         }
+    }
 
-        #[cfg(target_family = "unix")]
-        if let Err(e) = std::os::unix::fs::symlink(&relative_target, link_path) {
-            log_warning(&format!("Failed to create symlink {}: {}", link_path.display(), e));
-        }
+    /// The file of this logger (and process, for `{pid}` patterns) written before `file_path`
+    fn previous_log_file(config: &LogConfig, file_path: &Path) -> Option<PathBuf> {
+        let layout = &config.file_layout;
+        let current = layout.parse_path(&config.log_dir, &config.project_name, file_path)?;
+        let current_key = (current.period_start, current.seq, &current.path);
+        config
+            .log_files()
+            .into_iter()
+            .rev()
+            .find(|entry| entry.pid == current.pid && (entry.period_start, entry.seq, &entry.path) < current_key)
+            .map(|entry| entry.path)
+    }
 
-        #[cfg(target_family = "windows")]
-        if let Err(e) = std::os::windows::fs::symlink_file(&relative_target, link_path) {
-            log_warning(&format!("Failed to create symlink {}: {}", link_path.display(), e));
-        }
-    }
-
-    fn clean_old_logs(&self) {
-        let log_base_dir = Path::new(&self.config.log_dir).join(&self.config.project_name);
-        if let Ok(entries) = fs::read_dir(log_base_dir) {
-            let now = Local::now();
-            for entry in entries.flatten() {
-                if let Ok(metadata) = entry.metadata() {
-                    if let Ok(modified) = metadata.modified() {
-                        let age = now
-                            .signed_duration_since(chrono::DateTime::<Local>::from(modified))
-                            .num_days();
-                        if age > self.config.file_save_days_max as i64 {
-                            if let Err(e) = fs::remove_dir_all(entry.path()) {
-                                log_warning(&format!(
-                                    "Failed to remove old log directory {}: {}",
-                                    entry.path().display(),
-                                    e
-                                ));
-                            }
-                        }
-                    }
-                }
+    fn open_log_file(file_path: &Path) -> Option<std::fs::File> {
+        let log_dir = file_path.parent().unwrap();
+        if let Err(e) = fs::create_dir_all(log_dir) {
+            eprintln!("Failed to create log directory {}: {}", log_dir.display(), e);
+            return None;
+        }
+        match LogFileHandle::open_file(file_path) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                eprintln!("Failed to open log file {}: {}", file_path.display(), e);
+                None
             }
         }
     }
 
-    fn rotate_files(&self) {
-        if !self.config.enable_save_log_file {
+    fn write_file_header(handle: &LogFileHandle, config: &LogConfig) {
+        if !config.write_file_header {
             return;
         }
+        if let Err(e) = handle.write_record(render_file_header(config).as_bytes(), LogLevel::LogLevelInfo) {
+            eprintln!("Failed to write log file header {}: {}", handle.path().display(), e);
+        }
+    }
 
-        let now = Local::now();
-        let date_folder = now.format("%Y-%m-%d").to_string();
-        let hour = now.format("%H").to_string();
-        let log_dir = Path::new(&self.config.log_dir)
-            .join(&self.config.project_name)
-            .join(&date_folder);
-        let current_log_path = format!("{}/{}_{}.log", log_dir.display(), date_folder, hour);
+    fn create_symlink(target_path: &Path, config: &LogConfig) {
+        let Some(link_path) = config.file_layout.symlink_path(&config.log_dir, &config.project_name) else {
+            return;
+        };
+        let link_path = link_path.as_path();
 
-        let mut log_path = self.current_log_path.lock().unwrap();
-        if *log_path != PathBuf::from(&current_log_path) {
-            if let Err(e) = fs::create_dir_all(&log_dir) {
-                log_error(&format!("Failed to create log directory {}: {}", log_dir.display(), e));
-                return;
-            }
+        if !target_path.exists() {
+            eprintln!("Target log file {} does not exist", target_path.display());
+            return;
+        }
 
-            let file = match OpenOptions::new()
-                .create(true)
-                .write(true)
-                .append(true)
-                .open(&current_log_path)
-            {
-                Ok(file) => file,
-                Err(e) => {
-                    log_error(&format!("Failed to open log file {}: {}", current_log_path, e));
-                    return;
-                }
-            };
+        let link_dir = link_path.parent().unwrap();
+        if let Err(e) = fs::create_dir_all(link_dir) {
+            eprintln!("Failed to create log directory {}: {}", link_dir.display(), e);
+            return;
+        }
+        let relative_target = diff_paths(target_path, link_dir).unwrap();
 
-            if let Some(ref file_lock) = self.file {
-                let mut file_guard = file_lock.lock().unwrap();
-                *file_guard = file;
-            }
+        if let Ok(existing_target) = fs::read_link(link_path)
+            && existing_target == relative_target
+        {
+            return; // Symlink already points to the correct target
+        }
+
+        // Build the new link next to the old one and rename it over, so other processes
+        // never see a missing link and never fail on one created in between
+        let file_name = link_path.file_name().unwrap().to_string_lossy();
+        let temp_path = link_dir.join(format!(".{}.{}.tmp", file_name, std::process::id()));
+        let _ = fs::remove_file(&temp_path);
+
+        #[cfg(target_family = "unix")]
+        let created = std::os::unix::fs::symlink(&relative_target, &temp_path);
 
-            *log_path = PathBuf::from(&current_log_path);
-            LoggerManager::create_symlink(&current_log_path, &self.config);
+        #[cfg(target_family = "windows")]
+        let created = std::os::windows::fs::symlink_file(&relative_target, &temp_path);
 
-            self.clean_old_logs();
+        if let Err(e) = created.and_then(|()| fs::rename(&temp_path, link_path)) {
+            let _ = fs::remove_file(&temp_path);
+            eprintln!("Failed to create symlink {}: {}", link_path.display(), e);
         }
     }
 
-    fn should_rotate(&self) -> bool {
-        let now = Local::now();
-        let log_path = self.current_log_path.lock().unwrap();
-        let log_dir = Path::new(&self.config.log_dir)
-            .join(&self.config.project_name)
-            .join(now.format("%Y-%m-%d").to_string());
-
-        !log_path.starts_with(&log_dir)
+    fn remove_empty_dirs(mut dir: Option<&Path>, log_dir: &Path) {
+        while let Some(current) = dir {
+            if current == log_dir || !current.starts_with(log_dir) || fs::remove_dir(current).is_err() {
+                return;
+            }
+            dir = current.parent();
+        }
     }
 
-    fn get_caller_info() -> String {
-        let backtrace = backtrace::Backtrace::new();
+    /// `[package:.. method:.. line:..]` of the first frame outside the logger, plus its source file and line
+    fn get_caller_info(mut backtrace: backtrace::Backtrace) -> (String, Option<String>, Option<u32>) {
+        backtrace.resolve();
         let exclude_list = ["rs_box_log.rs", "backtrace::", "rs_box::rs_box_log::"];
         for frame in backtrace.frames().iter().skip(3) {
             for symbol in frame.symbols() {
@@ -253,101 +718,379 @@ impl LoggerManager {
                         if parts.len() > 2 {
                             let method_name = parts[parts.len() - 2];
                             let package_name = parts[..parts.len() - 2].join("::");
-                            return format!(
+                            let location = format!(
                                 "[package:{} method:{} line:{}]",
                                 package_name,
                                 method_name,
                                 symbol.lineno().unwrap_or(0)
                             );
+                            let file = symbol.filename().map(|path| path.display().to_string());
+                            return (location, file, symbol.lineno());
                         }
                     }
                 }
             }
         }
-        "unknown".to_string()
+        ("unknown".to_string(), None, None)
     }
 
-    fn log_format(&self, level: LogLevel, message: &str) {
-        if level as u8 > self.config.log_level as u8 {
+    /// Whether a record of `level` would be written; the macros check this before formatting.
+    pub fn enabled(&self, level: LogLevel) -> bool {
+        level_enabled(level, self.current_state().config.log_level)
+    }
+
+    /// Logs `message` with structured fields; used by the `log_info!` family of macros.
+    pub fn log_with_fields(&self, level: LogLevel, message: &str, fields: Vec<(String, LogFieldValue)>) {
+        self.log_format(level, message, fields);
+    }
+
+    fn log_format(&self, level: LogLevel, message: &str, fields: Vec<(String, LogFieldValue)>) {
+        let state = self.current_state();
+        let Some(mut pending) = LoggerManager::build_record(&state, level, message, fields) else {
             return;
+        };
+        pending.resolve_caller();
+        LoggerManager::write_record(&state, &pending);
+        // Hooks may log through this logger, which can need the state lock again
+        drop(state);
+        run_record_hooks(&pending.record);
+    }
+
+    /// Applies level, sampling and redaction and captures the record; `None` if it is filtered out.
+    /// Touches no files, so it is safe on an async executor thread.
+    fn build_record(
+        state: &LoggerState,
+        level: LogLevel,
+        message: &str,
+        fields: Vec<(String, LogFieldValue)>,
+    ) -> Option<PendingRecord> {
+        if !level_enabled(level, state.config.log_level) {
+            return None;
         }
 
-        let now = chrono::Utc::now();
-        let color_code = match level {
-            LogLevel::LogLevelInfo => "\x1b[32m",    // Green
-            LogLevel::LogLevelWarning => "\x1b[33m", // Yellow
-            LogLevel::LogLevelError => "\x1b[31m",   // Red
-            LogLevel::LogLevelDebug => "\x1b[36m",   // Cyan
-            LogLevel::LogLevelTrace => "\x1b[34m",   // Blue
+        let now = state.config.clock.now();
+        let sampling = match state.config.sampler {
+            Some(ref sampler) => match sampler.decide(level, now.timestamp()) {
+                Some(true) => Some(sampler.sampling()),
+                Some(false) => {
+                    record_sampled_out();
+                    return None;
+                }
+                None => None,
+            },
+            None => None,
         };
-        let reset_code = "\x1b[0m";
 
-        let location_info = if level == LogLevel::LogLevelDebug || level == LogLevel::LogLevelTrace {
-            LoggerManager::get_caller_info()
-        } else {
-            "".to_string()
-        };
+        record_emitted(level);
+        state.counters.record(level);
 
-        let log_message = if location_info.is_empty() {
-            format!(
-                "[{}] {}[{}]{} [{}]\n",
-                now.format("%Y-%m-%d %H:%M:%S %:z"),
-                color_code,
-                level.to_str(),
-                reset_code,
-                message
-            )
-        } else {
-            format!(
-                "[{}] {}[{}]{} {} [{}]\n",
-                now.format("%Y-%m-%d %H:%M:%S %:z"),
-                color_code,
-                level.to_str(),
-                reset_code,
-                location_info,
-                message
-            )
+        // Symbols are resolved later, as that may read debug info from disk
+        let caller = (level == LogLevel::LogLevelDebug || level == LogLevel::LogLevelTrace)
+            .then(backtrace::Backtrace::new_unresolved);
+
+        let record = LogRecord {
+            time: now.to_utc(),
+            level,
+            logger_name: state.name.clone(),
+            location: String::new(),
+            code_file: None,
+            code_line: None,
+            sampling,
+            message: match state.config.redactor {
+                Some(ref redactor) => redactor.redact(message).into_owned(),
+                None => message.to_string(),
+            },
+            thread_name: state
+                .config
+                .record_thread_name
+                .then(|| std::thread::current().name().unwrap_or("unnamed").to_string()),
+            thread_id: state.config.record_thread_id.then(current_thread_id),
+            pid: state.config.record_process_id.then(std::process::id),
+            host: state.config.record_host_name.then(|| host_name().to_string()),
+            fields: match state.config.redactor {
+                Some(ref redactor) => fields
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let value = redactor.redact_field(&key, value);
+                        (key, value)
+                    })
+                    .collect(),
+                None => fields,
+            },
         };
+        if level == LogLevel::LogLevelError {
+            record_error(&record);
+        }
+        Some(PendingRecord { record, now, caller })
+    }
+
+    /// Writes a built record to the file (rotating first if due) and the sinks; hooks are
+    /// run by the caller once it lets go of the state
+    fn write_record(state: &LoggerState, pending: &PendingRecord) {
+        let record = &pending.record;
+        let log_message = render_record(record, &state.config);
 
-        if let Some(ref file) = self.file {
-            if self.should_rotate() {
-                self.rotate_files();
+        if let Some(ref file) = state.file {
+            if state.should_rotate(pending.now.naive_local()) {
+                state.rotate_files(pending.now.naive_local());
             }
-            if let Ok(mut file) = file.lock() {
-                if let Err(e) = file.write_all(log_message.as_bytes()) {
-                    eprintln!("Failed to write to log file: {}", e);
-                }
+            if let Err(e) = file.write_record(log_message.as_bytes(), record.level) {
+                record_dropped();
+                eprintln!("Failed to write to log file: {}", e);
             }
         } else {
             print!("{}", log_message);
+            record_bytes_written(log_message.len());
+        }
+
+        for sink in &state.config.sinks {
+            sink.write_record(record);
+        }
+    }
+
+    /// Writes text that is not a record, rendered for the current config, to the file or stdout
+    pub(crate) fn write_raw(&self, render: impl FnOnce(&LogConfig) -> String) {
+        let state = self.current_state();
+        let text = render(&state.config);
+        match state.file {
+            Some(ref file) => {
+                if let Err(e) = file.write_record(text.as_bytes(), LogLevel::LogLevelInfo) {
+                    eprintln!("Failed to write to log file {}: {}", file.path().display(), e);
+                }
+            }
+            None => {
+                print!("{}", text);
+                record_bytes_written(text.len());
+            }
         }
     }
 
+    /// `build_record` against the state as it is, without picking up a newer config
+    #[cfg(feature = "tokio")]
+    pub(crate) fn build_pending(
+        &self,
+        level: LogLevel,
+        message: &str,
+        fields: Vec<(String, LogFieldValue)>,
+    ) -> Option<PendingRecord> {
+        LoggerManager::build_record(&self.state.read().unwrap(), level, message, fields)
+    }
+
+    /// Resolves the caller and writes a record from `build_pending`
+    #[cfg(feature = "tokio")]
+    pub(crate) fn write_pending(&self, mut pending: PendingRecord) {
+        pending.resolve_caller();
+        LoggerManager::write_record(&self.current_state(), &pending);
+        run_record_hooks(&pending.record);
+    }
+
+    /// `enabled` without picking up a newer config
+    #[cfg(feature = "tokio")]
+    pub(crate) fn enabled_now(&self, level: LogLevel) -> bool {
+        level_enabled(level, self.state.read().unwrap().config.log_level)
+    }
+
+    /// True if a newer global config waits to be picked up by `refresh`
+    #[cfg(feature = "tokio")]
+    pub(crate) fn is_stale(&self) -> bool {
+        self.source != LogConfigSource::Fixed
+            && self.state.read().unwrap().generation != LOG_CONFIG_GENERATION.load(Ordering::SeqCst)
+    }
+
     pub fn log_info_f(&self, message: &str) {
-        self.log_format(LogLevel::LogLevelInfo, message);
+        self.log_format(LogLevel::LogLevelInfo, message, Vec::new());
     }
 
     pub fn log_warning_f(&self, message: &str) {
-        self.log_format(LogLevel::LogLevelWarning, message);
+        self.log_format(LogLevel::LogLevelWarning, message, Vec::new());
     }
 
     pub fn log_error_f(&self, message: &str) {
-        self.log_format(LogLevel::LogLevelError, message);
+        self.log_format(LogLevel::LogLevelError, message, Vec::new());
     }
 
     pub fn log_debug_f(&self, message: &str) {
-        self.log_format(LogLevel::LogLevelDebug, message);
+        self.log_format(LogLevel::LogLevelDebug, message, Vec::new());
     }
 
     pub fn log_trace_f(&self, message: &str) {
-        self.log_format(LogLevel::LogLevelTrace, message);
+        self.log_format(LogLevel::LogLevelTrace, message, Vec::new());
+    }
+}
+
+impl LoggerState {
+    /// Builds the state for `config`, keeping `previous_file` when the path is unchanged.
+    /// A log file that cannot be opened or shared is reported, and the logger writes to the
+    /// console only; `try_open` returns the error instead.
+    fn open(name: String, config: Arc<LogConfig>, generation: u64, previous_file: Option<Arc<LogFileHandle>>) -> Self {
+        match LoggerState::try_open(name.clone(), config.clone(), generation, previous_file) {
+            Ok(state) => state,
+            Err(e) => {
+                eprintln!("Failed to open log file of {}, logging to the console only: {}", name, e);
+                LoggerState {
+                    generation,
+                    counters: logger_counters(&name),
+                    name,
+                    config,
+                    file: None,
+                }
+            }
+        }
+    }
+
+    fn try_open(
+        name: String,
+        config: Arc<LogConfig>,
+        generation: u64,
+        previous_file: Option<Arc<LogFileHandle>>,
+    ) -> std::io::Result<Self> {
+        let now = config.clock.now().naive_local();
+        let mut newly_opened = false;
+        let file = if config.enable_save_log_file {
+            let file_path = LoggerManager::get_log_file_path(&config, now);
+            let log_dir = file_path.parent().unwrap();
+            let kept = previous_file.filter(|handle| handle.path() == file_path);
+            if kept.is_some() {
+                kept
+            } else {
+                fs::create_dir_all(log_dir).map_err(|e| {
+                    std::io::Error::new(e.kind(), format!("cannot create log directory {}: {}", log_dir.display(), e))
+                })?;
+                let chain = config.integrity.as_ref().map(|integrity| LogChainSettings {
+                    integrity,
+                    json: config.output_format == LogOutputFormat::LogOutputFormatJson,
+                    log_dir: Path::new(&config.log_dir),
+                    previous_file: LoggerManager::previous_log_file(&config, &file_path),
+                    per_process: config.file_layout.has_pid(),
+                });
+                let (handle, opened) = LogFileHandle::open_shared(&file_path, config.write_policy, config.process_lock, chain)
+                    .map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", file_path.display(), e)))?;
+                if opened {
+                    LoggerManager::create_symlink(&file_path, &config);
+                    LoggerManager::write_file_header(&handle, &config);
+                }
+                newly_opened = opened;
+                Some(handle)
+            }
+        } else {
+            None
+        };
+
+        let state = LoggerState {
+            generation,
+            counters: logger_counters(&name),
+            name,
+            config,
+            file,
+        };
+        if newly_opened {
+            // A restarted service cleans up right away instead of waiting for the next rotation
+            state.clean_old_logs(now);
+        }
+        Ok(state)
+    }
+
+    /// Deletes files of this logger whose period (parsed from the name, or the
+    /// modification time if the pattern has no date) is past `file_save_days_max`.
+    fn clean_old_logs(&self, now: NaiveDateTime) {
+        let config = &self.config;
+        let current_path = self.file.as_ref().map(|handle| handle.path());
+
+        for entry in config.file_layout.list_files(&config.log_dir, &config.project_name) {
+            if Some(&entry.path) == current_path.as_ref() {
+                continue;
+            }
+            let period_start = entry.period_start.or_else(|| {
+                let modified = fs::metadata(&entry.path).and_then(|metadata| metadata.modified()).ok()?;
+                Some(chrono::DateTime::<Local>::from(modified).naive_local())
+            });
+            let Some(period_start) = period_start else {
+                continue;
+            };
+            if now.signed_duration_since(period_start).num_days() <= config.file_save_days_max as i64 {
+                continue;
+            }
+            if let Err(e) = fs::remove_file(&entry.path) {
+                // Another process sharing the directory may have removed it first
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    eprintln!("Failed to remove old log file {}: {}", entry.path.display(), e);
+                }
+                continue;
+            }
+            record_deleted_file();
+            LoggerManager::remove_empty_dirs(entry.path.parent(), Path::new(&config.log_dir));
+        }
+    }
+
+    fn rotate_files(&self, now: NaiveDateTime) {
+        if !self.config.enable_save_log_file {
+            return;
+        }
+        let Some(ref handle) = self.file else {
+            return;
+        };
+        let _rotation = handle.lock_rotation();
+        if !self.should_rotate(now) {
+            return; // Another logger sharing the handle rotated first
+        }
+
+        let current_log_path = LoggerManager::get_log_file_path(&self.config, now);
+        let Some(file) = LoggerManager::open_log_file(&current_log_path) else {
+            return;
+        };
+
+        handle.replace_file(file, current_log_path.clone());
+        record_rotation();
+        LoggerManager::write_file_header(handle, &self.config);
+        LoggerManager::create_symlink(&current_log_path, &self.config);
+
+        self.clean_old_logs(now);
+    }
+
+    /// True once the current time renders to a different file than the open one
+    fn should_rotate(&self, now: NaiveDateTime) -> bool {
+        let Some(ref handle) = self.file else {
+            return false;
+        };
+        let config = &self.config;
+        let log_path = handle.path();
+        let seq = if config.file_layout.has_seq() {
+            config
+                .file_layout
+                .parse_path(&config.log_dir, &config.project_name, &log_path)
+                .map_or(0, |entry| entry.seq)
+        } else {
+            0
+        };
+        let expected = config
+            .file_layout
+            .file_path(&config.log_dir, &config.project_name, now, seq);
+
+        log_path != expected
     }
 }
 
 static GLOBAL_LOG_CONFIG: Lazy<Mutex<Arc<LogConfig>>> =
     Lazy::new(|| Mutex::new(Arc::new(LogConfig::default())));
-static DEFAULT_LOGGER: Lazy<Mutex<LoggerManager>> =
-    Lazy::new(|| Mutex::new(LoggerManager::default()));
+/// Bumped on every change that live loggers must pick up
+static LOG_CONFIG_GENERATION: AtomicU64 = AtomicU64::new(0);
+static DEFAULT_LOGGER: Lazy<LoggerManager> = Lazy::new(LoggerManager::default);
+static LOGGER_REGISTRY: Lazy<Mutex<HashMap<String, Arc<LoggerManager>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Flushes all buffered log files when dropped; keep it alive in `main`.
+#[must_use = "dropping the guard flushes and closes the logs right away; bind it, e.g. `let _guard = ...`"]
+pub struct LogGuard {
+    _private: (),
+}
+
+impl Drop for LogGuard {
+    fn drop(&mut self) {
+        write_shutdown_summary(&global_log_config().0);
+        seal_all_file_handles();
+        flush();
+    }
+}
 
 pub fn setup_log_tools(
     project_name: &str,
@@ -355,43 +1098,87 @@ pub fn setup_log_tools(
     log_dir: &str,
     log_level: LogLevel,
     file_save_days_max: u64,
-) {
-    let log_dir = if log_dir.is_empty() {
-        if cfg!(target_os = "linux") {
-            format!("/var/log/{}", project_name)
-        } else {
-            "./logs".to_string()
-        }
-    } else {
-        log_dir.to_string()
-    };
-
-    let new_config = Arc::new(LogConfig {
-        project_name: project_name.to_string(),
+) -> LogGuard {
+    setup_log_tools_with_config(LogConfig::new(
+        project_name,
         enable_save_log_file,
         log_dir,
         log_level,
         file_save_days_max,
-    });
+    ))
+}
+
+pub fn setup_log_tools_with_config(config: LogConfig) -> LogGuard {
+    mark_log_shutdown(false);
+    rearm_shutdown_summary();
+    reconfigure_log(config);
+    LogGuard { _private: () }
+}
+
+/// Like `setup_log_tools_with_config`, but fails without touching the global config when
+/// the log file cannot be opened or shared, instead of logging to the console only.
+pub fn try_setup_log_tools_with_config(config: LogConfig) -> std::io::Result<LogGuard> {
+    let config = Arc::new(config);
+    // Keeps the file open until the default logger picks it up
+    let _opened = LoggerState::try_open(config.project_name.clone(), config.clone(), 0, None)?;
+    Ok(setup_log_tools_with_config(Arc::unwrap_or_clone(config)))
+}
 
+/// Replaces the global config. The default logger and every logger from `LoggerManager::new`
+/// or `LoggerManager::get` switch to it before their next record; their log files stay open
+/// unless the new config moves them to another path.
+pub fn reconfigure_log(config: LogConfig) {
+    if let Some(last_errors) = config.shutdown_summary {
+        set_recent_errors_limit(last_errors);
+    }
     {
-        let mut config = GLOBAL_LOG_CONFIG.lock().unwrap();
-        *config = new_config.clone();
+        let mut global = GLOBAL_LOG_CONFIG.lock().unwrap();
+        *global = Arc::new(config);
+        bump_log_config_generation();
     }
+    DEFAULT_LOGGER.refresh();
+}
 
-    {
-        let mut logger = DEFAULT_LOGGER.lock().unwrap();
-        *logger = LoggerManager::initialize_logger(new_config);
+fn global_log_config() -> (Arc<LogConfig>, u64) {
+    let config = GLOBAL_LOG_CONFIG.lock().unwrap();
+    (config.clone(), LOG_CONFIG_GENERATION.load(Ordering::SeqCst))
+}
+
+/// Makes loggers following the global config resolve it again
+pub(crate) fn bump_log_config_generation() {
+    LOG_CONFIG_GENERATION.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Writes out every buffered log record without waiting for the flush interval.
+pub fn flush() {
+    flush_all_file_handles(false);
+    flush_sinks();
+}
+
+/// Flushes and fsyncs all log files; records logged afterwards are written unbuffered.
+/// Writes the shutdown summary first if the global config asks for one.
+pub fn shutdown() {
+    write_shutdown_summary(&global_log_config().0);
+    seal_all_file_handles();
+    mark_log_shutdown(true);
+    flush_all_file_handles(true);
+    flush_sinks();
+}
+
+fn flush_sinks() {
+    let global_sinks = GLOBAL_LOG_CONFIG.lock().unwrap().sinks.clone();
+    for sink in global_sinks.iter().chain(configured_sinks().iter()) {
+        sink.flush();
     }
 }
 
+/// Changes the given fields of the global config; `reconfigure_log` replaces all of them.
 pub fn update_log_config(
     log_level: Option<LogLevel>,
     log_dir: Option<&str>,
     file_save_days_max: Option<u64>,
 ) {
-    let mut config = GLOBAL_LOG_CONFIG.lock().unwrap();
-    let mut new_config = (**config).clone();
+    let mut new_config = (*global_log_config().0).clone();
     if let Some(level) = log_level {
         new_config.log_level = level;
     }
@@ -401,17 +1188,14 @@ pub fn update_log_config(
     if let Some(days) = file_save_days_max {
         new_config.file_save_days_max = days;
     }
-    *config = Arc::new(new_config);
-    let mut logger = DEFAULT_LOGGER.lock().unwrap();
-    *logger = LoggerManager::initialize_logger(config.clone());
+    reconfigure_log(new_config);
 }
 
 pub fn with_default_logger<F>(log_function: F)
 where
     F: FnOnce(&LoggerManager),
 {
-    let logger = DEFAULT_LOGGER.lock().unwrap();
-    log_function(&*logger);
+    log_function(&DEFAULT_LOGGER);
 }
 
 pub fn log_info(message: &str) {
@@ -437,44 +1221,137 @@ pub fn log_trace(message: &str) {
 #[macro_export]
 macro_rules! log_infof {
     ($($arg:tt)*) => {
-        $crate::with_default_logger(|logger| {
-            logger.log_info_f(&format!($($arg)*));
-        })
+        if $crate::LogLevel::LogLevelInfo.severity() <= $crate::STATIC_MAX_LEVEL_SEVERITY {
+            $crate::with_default_logger(|logger| {
+                if logger.enabled($crate::LogLevel::LogLevelInfo) {
+                    logger.log_info_f(&format!($($arg)*));
+                }
+            })
+        }
     };
 }
 
 #[macro_export]
 macro_rules! log_warningf {
     ($($arg:tt)*) => {
-        $crate::with_default_logger(|logger| {
-            logger.log_warning_f(&format!($($arg)*));
-        })
+        if $crate::LogLevel::LogLevelWarning.severity() <= $crate::STATIC_MAX_LEVEL_SEVERITY {
+            $crate::with_default_logger(|logger| {
+                if logger.enabled($crate::LogLevel::LogLevelWarning) {
+                    logger.log_warning_f(&format!($($arg)*));
+                }
+            })
+        }
     };
 }
 
 #[macro_export]
 macro_rules! log_errorf {
     ($($arg:tt)*) => {
-        $crate::with_default_logger(|logger| {
-            logger.log_error_f(&format!($($arg)*));
-        })
+        if $crate::LogLevel::LogLevelError.severity() <= $crate::STATIC_MAX_LEVEL_SEVERITY {
+            $crate::with_default_logger(|logger| {
+                if logger.enabled($crate::LogLevel::LogLevelError) {
+                    logger.log_error_f(&format!($($arg)*));
+                }
+            })
+        }
     };
 }
 
 #[macro_export]
 macro_rules! log_debugf {
     ($($arg:tt)*) => {
-        $crate::with_default_logger(|logger| {
-            logger.log_debug_f(&format!($($arg)*));
-        })
+        if $crate::LogLevel::LogLevelDebug.severity() <= $crate::STATIC_MAX_LEVEL_SEVERITY {
+            $crate::with_default_logger(|logger| {
+                if logger.enabled($crate::LogLevel::LogLevelDebug) {
+                    logger.log_debug_f(&format!($($arg)*));
+                }
+            })
+        }
     };
 }
 
 #[macro_export]
 macro_rules! log_tracef {
     ($($arg:tt)*) => {
-        $crate::with_default_logger(|logger| {
-            logger.log_trace_f(&format!($($arg)*));
-        })
+        if $crate::LogLevel::LogLevelTrace.severity() <= $crate::STATIC_MAX_LEVEL_SEVERITY {
+            $crate::with_default_logger(|logger| {
+                if logger.enabled($crate::LogLevel::LogLevelTrace) {
+                    logger.log_trace_f(&format!($($arg)*));
+                }
+            })
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log_kv {
+    ($logger:ident, $level:expr, $fields:ident; $key:ident = % $value:expr, $($rest:tt)+) => {
+        $fields.push((stringify!($key).to_string(), $crate::LogFieldValue::from(format!("{}", $value))));
+        $crate::__log_kv!($logger, $level, $fields; $($rest)+)
+    };
+    ($logger:ident, $level:expr, $fields:ident; $key:ident = ? $value:expr, $($rest:tt)+) => {
+        $fields.push((stringify!($key).to_string(), $crate::LogFieldValue::from(format!("{:?}", $value))));
+        $crate::__log_kv!($logger, $level, $fields; $($rest)+)
+    };
+    ($logger:ident, $level:expr, $fields:ident; $key:ident = $value:expr, $($rest:tt)+) => {
+        $fields.push((stringify!($key).to_string(), $crate::LogFieldValue::from($value)));
+        $crate::__log_kv!($logger, $level, $fields; $($rest)+)
+    };
+    ($logger:ident, $level:expr, $fields:ident; $($message:tt)+) => {
+        $logger.log_with_fields($level, &format!($($message)+), $fields)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log_kv_entry {
+    ($level:expr, $($arg:tt)+) => {
+        if $level.severity() <= $crate::STATIC_MAX_LEVEL_SEVERITY {
+            #[allow(unused_mut, clippy::vec_init_then_push)]
+            $crate::with_default_logger(|logger| {
+                if logger.enabled($level) {
+                    let mut fields: Vec<(String, $crate::LogFieldValue)> = Vec::new();
+                    $crate::__log_kv!(logger, $level, fields; $($arg)+);
+                }
+            })
+        }
+    };
+}
+
+/// `log_info!(worker = %name, diff = 65536, "share accepted from {}", addr)`: fields are
+/// `key = value` (typed), `key = %value` (Display) or `key = ?value` (Debug), then the message.
+#[macro_export]
+macro_rules! log_info {
+    ($($arg:tt)+) => {
+        $crate::__log_kv_entry!($crate::LogLevel::LogLevelInfo, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! log_warning {
+    ($($arg:tt)+) => {
+        $crate::__log_kv_entry!($crate::LogLevel::LogLevelWarning, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! log_error {
+    ($($arg:tt)+) => {
+        $crate::__log_kv_entry!($crate::LogLevel::LogLevelError, $($arg)+)
+    };
+}
+
+#[macro_export]
+macro_rules! log_debug {
+    ($($arg:tt)+) => {
+        $crate::__log_kv_entry!($crate::LogLevel::LogLevelDebug, $($arg)+)
     };
-}
\ No newline at end of file
+}
+
+#[macro_export]
+macro_rules! log_trace {
+    ($($arg:tt)+) => {
+        $crate::__log_kv_entry!($crate::LogLevel::LogLevelTrace, $($arg)+)
+    };
+}
@@ -0,0 +1,154 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot};
+use crate::rs_box_log::rs_box_log::{with_default_logger, LogFieldValue, LogLevel, LoggerManager, PendingRecord};
+use super::rs_box_log_metrics::record_dropped;
+
+const DEFAULT_QUEUE_CAPACITY: usize = 8192;
+
+enum AsyncLogMessage {
+    Record(Box<PendingRecord>),
+    /// A newer global config was published; load it before the next record
+    Refresh,
+    Flush(oneshot::Sender<()>),
+}
+
+/// The logger the worker writes through
+#[derive(Clone)]
+enum AsyncTarget {
+    Default,
+    Logger(Arc<LoggerManager>),
+}
+
+impl AsyncTarget {
+    fn with<R>(&self, f: impl FnOnce(&LoggerManager) -> R) -> R {
+        match self {
+            AsyncTarget::Default => {
+                let mut result = None;
+                with_default_logger(|logger| result = Some(f(logger)));
+                result.unwrap()
+            }
+            AsyncTarget::Logger(logger) => f(logger),
+        }
+    }
+}
+
+/// Front end of a `LoggerManager` for async code. Records are built on the calling task
+/// (level check, sampling, redaction, timestamp) and handed to a dedicated thread that does
+/// all file work: writing, rotation, cleanup of old files and reopening on config changes.
+/// No method blocks the calling thread on I/O; when the queue is full, records are dropped
+/// and counted in `dropped_records`.
+///
+/// Create it at startup: the first use of a logger opens its file on the creating thread.
+/// Dropping the front end lets the thread finish the queue in the background; call
+/// `flush().await` before shutting down to be sure everything is written.
+pub struct AsyncLoggerManager {
+    target: AsyncTarget,
+    sender: mpsc::Sender<AsyncLogMessage>,
+    /// Set while a `Refresh` is queued, so a burst of records sends only one
+    refresh_queued: Arc<AtomicBool>,
+}
+
+impl AsyncLoggerManager {
+    /// Front end for `logger`, e.g. `LoggerManager::get("pool.stratum")`
+    pub fn new(logger: Arc<LoggerManager>) -> io::Result<Self> {
+        AsyncLoggerManager::start(AsyncTarget::Logger(logger), DEFAULT_QUEUE_CAPACITY)
+    }
+
+    /// Front end for the default logger used by `log_info` and friends
+    pub fn for_default_logger() -> io::Result<Self> {
+        AsyncLoggerManager::start(AsyncTarget::Default, DEFAULT_QUEUE_CAPACITY)
+    }
+
+    /// Like `new`, with room for `queue_capacity` records waiting to be written (default 8192)
+    pub fn with_queue_capacity(logger: Arc<LoggerManager>, queue_capacity: usize) -> io::Result<Self> {
+        AsyncLoggerManager::start(AsyncTarget::Logger(logger), queue_capacity.max(1))
+    }
+
+    fn start(target: AsyncTarget, queue_capacity: usize) -> io::Result<Self> {
+        let (sender, receiver) = mpsc::channel(queue_capacity);
+        let refresh_queued = Arc::new(AtomicBool::new(false));
+        let worker_target = target.clone();
+        let worker_refresh_queued = refresh_queued.clone();
+        std::thread::Builder::new()
+            .name("rs_box_log_async".to_string())
+            .spawn(move || run_async_writer(worker_target, receiver, worker_refresh_queued))?;
+        Ok(AsyncLoggerManager {
+            target,
+            sender,
+            refresh_queued,
+        })
+    }
+
+    /// Whether a record of `level` would be written
+    pub fn enabled(&self, level: LogLevel) -> bool {
+        self.nudge_if_stale();
+        self.target.with(|logger| logger.enabled_now(level))
+    }
+
+    pub fn log_with_fields(&self, level: LogLevel, message: &str, fields: Vec<(String, LogFieldValue)>) {
+        self.nudge_if_stale();
+        let Some(pending) = self.target.with(|logger| logger.build_pending(level, message, fields)) else {
+            return;
+        };
+        if self.sender.try_send(AsyncLogMessage::Record(Box::new(pending))).is_err() {
+            record_dropped();
+        }
+    }
+
+    pub fn log_info(&self, message: &str) {
+        self.log_with_fields(LogLevel::LogLevelInfo, message, Vec::new());
+    }
+
+    pub fn log_warning(&self, message: &str) {
+        self.log_with_fields(LogLevel::LogLevelWarning, message, Vec::new());
+    }
+
+    pub fn log_error(&self, message: &str) {
+        self.log_with_fields(LogLevel::LogLevelError, message, Vec::new());
+    }
+
+    pub fn log_debug(&self, message: &str) {
+        self.log_with_fields(LogLevel::LogLevelDebug, message, Vec::new());
+    }
+
+    pub fn log_trace(&self, message: &str) {
+        self.log_with_fields(LogLevel::LogLevelTrace, message, Vec::new());
+    }
+
+    /// Resolves once every record logged before the call is written and the log files
+    /// and sinks are flushed
+    pub async fn flush(&self) {
+        let (done, finished) = oneshot::channel();
+        if self.sender.send(AsyncLogMessage::Flush(done)).await.is_ok() {
+            let _ = finished.await;
+        }
+    }
+
+    /// Lets the worker load a newer config; until it has, records use the previous one
+    fn nudge_if_stale(&self) {
+        if self.target.with(|logger| logger.is_stale())
+            && !self.refresh_queued.swap(true, Ordering::AcqRel)
+            && self.sender.try_send(AsyncLogMessage::Refresh).is_err()
+        {
+            self.refresh_queued.store(false, Ordering::Release);
+        }
+    }
+}
+
+fn run_async_writer(target: AsyncTarget, mut receiver: mpsc::Receiver<AsyncLogMessage>, refresh_queued: Arc<AtomicBool>) {
+    while let Some(message) = receiver.blocking_recv() {
+        match message {
+            AsyncLogMessage::Record(pending) => target.with(|logger| logger.write_pending(*pending)),
+            AsyncLogMessage::Refresh => {
+                refresh_queued.store(false, Ordering::Release);
+                target.with(|logger| logger.refresh());
+            }
+            AsyncLogMessage::Flush(done) => {
+                crate::rs_box_log::rs_box_log::flush();
+                let _ = done.send(());
+            }
+        }
+    }
+}
@@ -0,0 +1,86 @@
+#[cfg(test)]
+use super::rs_box_log;
+#[cfg(test)]
+use super::rs_box_log_test::{lock_global_config, test_log_dir};
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+
+/// Remembers which thread wrote each record
+#[cfg(test)]
+#[derive(Default)]
+struct ThreadSink {
+    writes: Mutex<Vec<(String, String)>>,
+}
+
+#[cfg(test)]
+impl rs_box_log::LogSink for ThreadSink {
+    fn write_record(&self, record: &rs_box_log::LogRecord) {
+        let thread = std::thread::current().name().unwrap_or("").to_string();
+        self.writes.lock().unwrap().push((record.message.clone(), thread));
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_async_logger_writes_on_its_own_thread() {
+    let log_dir = test_log_dir("async_writer");
+    let sink = Arc::new(ThreadSink::default());
+    let config = rs_box_log::LogConfig::new("async_writer", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_thread_name(true)
+        .with_sink(sink.clone());
+    let logger = rs_box_log::AsyncLoggerManager::new(Arc::new(rs_box_log::LoggerManager::with_config(config))).unwrap();
+
+    assert!(logger.enabled(rs_box_log::LogLevel::LogLevelDebug));
+    assert!(!logger.enabled(rs_box_log::LogLevel::LogLevelTrace));
+    for index in 0..50 {
+        logger.log_info(&format!("share {}", index));
+    }
+    logger.log_with_fields(
+        rs_box_log::LogLevel::LogLevelWarning,
+        "stale job",
+        vec![("job_id".to_string(), rs_box_log::LogFieldValue::from("7f"))],
+    );
+    logger.log_debug("with caller");
+    logger.log_trace("hidden");
+    logger.flush().await;
+
+    let writes = sink.writes.lock().unwrap().clone();
+    assert_eq!(writes.len(), 52);
+    assert!(writes.iter().all(|(_, thread)| thread == "rs_box_log_async"));
+    let messages: Vec<&str> = writes.iter().map(|(message, _)| message.as_str()).collect();
+    assert_eq!(messages[..2], ["share 0", "share 1"]);
+    assert_eq!(messages[50..], ["stale job", "with caller"]);
+
+    // The record keeps the name of the runtime thread that logged it, not the writer's
+    let content = std::fs::read_to_string(format!("{}/async_writer/run.log", log_dir)).unwrap();
+    assert!(content.find("[share 0]").unwrap() < content.find("[share 49]").unwrap());
+    assert!(content.contains("[stale job] job_id=7f"));
+    assert!(!content.contains("thread:rs_box_log_async"));
+}
+
+#[test]
+fn test_async_logger_follows_config_updates() {
+    let _global = lock_global_config();
+    let log_dir = test_log_dir("async_follow");
+    let config = rs_box_log::LogConfig::new("async_follow", true, &log_dir, rs_box_log::LogLevel::LogLevelInfo, 7);
+    let _guard = rs_box_log::setup_log_tools_with_config(config);
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let logger = rs_box_log::AsyncLoggerManager::for_default_logger().unwrap();
+
+    runtime.block_on(async {
+        logger.log_debug("debug hidden");
+        logger.flush().await;
+    });
+    rs_box_log::update_log_config(Some(rs_box_log::LogLevel::LogLevelDebug), None, None);
+    runtime.block_on(async {
+        // The first call only asks the writer to reload the config
+        while !logger.enabled(rs_box_log::LogLevel::LogLevelDebug) {
+            logger.flush().await;
+        }
+        logger.log_debug("debug shown");
+        logger.flush().await;
+    });
+
+    let content = std::fs::read_to_string(format!("{}/async_follow/run.log", log_dir)).unwrap();
+    assert!(content.contains("[debug shown]"));
+    assert!(!content.contains("[debug hidden]"));
+}
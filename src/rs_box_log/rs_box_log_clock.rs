@@ -0,0 +1,54 @@
+use std::fmt;
+use std::sync::Mutex;
+use chrono::{DateTime, Duration, FixedOffset, Local};
+
+/// Source of the current time for record timestamps, file naming, rotation and retention.
+/// The offset of the returned time decides the local hour a record belongs to.
+pub trait LogClock: Send + Sync {
+    fn now(&self) -> DateTime<FixedOffset>;
+}
+
+impl fmt::Debug for dyn LogClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LogClock")
+    }
+}
+
+/// The local wall clock of the machine (default)
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LogSystemClock;
+
+impl LogClock for LogSystemClock {
+    fn now(&self) -> DateTime<FixedOffset> {
+        Local::now().fixed_offset()
+    }
+}
+
+/// A clock that only moves when told to. Changing the offset with `set` simulates a
+/// DST switch, e.g. `01:59:59+01:00` followed by `03:00:00+02:00`.
+#[derive(Debug)]
+pub struct LogManualClock {
+    now: Mutex<DateTime<FixedOffset>>,
+}
+
+impl LogManualClock {
+    pub fn new(now: DateTime<FixedOffset>) -> Self {
+        LogManualClock { now: Mutex::new(now) }
+    }
+
+    pub fn set(&self, now: DateTime<FixedOffset>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    /// Moves the clock forward keeping the current offset
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now += duration;
+    }
+}
+
+impl LogClock for LogManualClock {
+    fn now(&self) -> DateTime<FixedOffset> {
+        *self.now.lock().unwrap()
+    }
+}
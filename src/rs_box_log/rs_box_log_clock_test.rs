@@ -0,0 +1,140 @@
+#[cfg(test)]
+use super::rs_box_log;
+#[cfg(test)]
+use super::rs_box_log_hierarchy_test::MemorySink;
+#[cfg(test)]
+use super::rs_box_log_test::test_log_dir;
+#[cfg(test)]
+use chrono::{DateTime, Duration, FixedOffset};
+#[cfg(test)]
+use std::sync::Arc;
+
+#[cfg(test)]
+fn at(time: &str) -> DateTime<FixedOffset> {
+    DateTime::parse_from_rfc3339(time).unwrap()
+}
+
+#[cfg(test)]
+fn clocked_config(project: &str, log_dir: &str, clock: &Arc<rs_box_log::LogManualClock>, days: u64) -> rs_box_log::LogConfig {
+    rs_box_log::LogConfig::new(project, true, log_dir, rs_box_log::LogLevel::LogLevelDebug, days).with_clock(clock.clone())
+}
+
+#[cfg(test)]
+fn file_names(config: &rs_box_log::LogConfig, log_dir: &str) -> Vec<String> {
+    config
+        .log_files()
+        .iter()
+        .map(|entry| entry.path.strip_prefix(log_dir).unwrap().to_string_lossy().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+fn symlink_target(log_dir: &str, project: &str) -> String {
+    std::fs::read_link(format!("{}/{}/run.log", log_dir, project))
+        .unwrap()
+        .to_string_lossy()
+        .to_string()
+}
+
+#[test]
+fn test_rotates_on_hour_boundary() {
+    let log_dir = test_log_dir("clock_hour");
+    let clock = Arc::new(rs_box_log::LogManualClock::new(at("2024-05-17T09:59:59+00:00")));
+    let sink = Arc::new(MemorySink::default());
+    let config = clocked_config("hourly", &log_dir, &clock, 7).with_sink(sink.clone());
+    let logger = rs_box_log::LoggerManager::with_config(config.clone());
+
+    logger.log_info_f("before the hour");
+    assert_eq!(symlink_target(&log_dir, "hourly"), "2024-05-17/2024-05-17_09.log");
+    clock.advance(Duration::seconds(1));
+    logger.log_info_f("after the hour");
+
+    assert_eq!(
+        file_names(&config, &log_dir),
+        vec!["hourly/2024-05-17/2024-05-17_09.log", "hourly/2024-05-17/2024-05-17_10.log"]
+    );
+    let previous = std::fs::read_to_string(format!("{}/hourly/2024-05-17/2024-05-17_09.log", log_dir)).unwrap();
+    assert!(previous.contains("before the hour") && !previous.contains("after the hour"));
+    assert_eq!(symlink_target(&log_dir, "hourly"), "2024-05-17/2024-05-17_10.log");
+
+    let times: Vec<String> = sink.records.lock().unwrap().iter().map(|record| record.time.to_rfc3339()).collect();
+    assert_eq!(times, vec!["2024-05-17T09:59:59+00:00", "2024-05-17T10:00:00+00:00"]);
+}
+
+#[test]
+fn test_day_boundary_and_retention() {
+    let log_dir = test_log_dir("clock_day");
+    let clock = Arc::new(rs_box_log::LogManualClock::new(at("2024-05-17T23:30:00+00:00")));
+    let config = clocked_config("daily", &log_dir, &clock, 1);
+    let logger = rs_box_log::LoggerManager::with_config(config.clone());
+
+    logger.log_info_f("day one");
+    clock.set(at("2024-05-18T00:10:00+00:00"));
+    logger.log_info_f("day two");
+    assert_eq!(
+        file_names(&config, &log_dir),
+        vec!["daily/2024-05-17/2024-05-17_23.log", "daily/2024-05-18/2024-05-18_00.log"]
+    );
+
+    // 1 day 23 hours after the first file started: still within file_save_days_max
+    clock.set(at("2024-05-19T22:10:00+00:00"));
+    logger.log_info_f("day three");
+    assert_eq!(file_names(&config, &log_dir).len(), 3);
+
+    // Two full days past 2024-05-17 23:00, so only that file and its directory go
+    let deleted_before = rs_box_log::log_metrics_snapshot().deleted_files;
+    clock.set(at("2024-05-19T23:10:00+00:00"));
+    logger.log_info_f("day three, later");
+    assert!(rs_box_log::log_metrics_snapshot().deleted_files > deleted_before);
+    assert_eq!(
+        file_names(&config, &log_dir),
+        vec![
+            "daily/2024-05-18/2024-05-18_00.log",
+            "daily/2024-05-19/2024-05-19_22.log",
+            "daily/2024-05-19/2024-05-19_23.log"
+        ]
+    );
+    assert!(!std::path::Path::new(&format!("{}/daily/2024-05-17", log_dir)).exists());
+    assert_eq!(symlink_target(&log_dir, "daily"), "2024-05-19/2024-05-19_23.log");
+}
+
+#[test]
+fn test_dst_spring_forward_skips_missing_hour() {
+    let log_dir = test_log_dir("clock_dst_spring");
+    let clock = Arc::new(rs_box_log::LogManualClock::new(at("2024-03-31T01:59:59+01:00")));
+    let config = clocked_config("spring", &log_dir, &clock, 7);
+    let logger = rs_box_log::LoggerManager::with_config(config.clone());
+
+    logger.log_info_f("winter time");
+    clock.set(at("2024-03-31T03:00:00+02:00"));
+    logger.log_info_f("summer time");
+
+    assert_eq!(
+        file_names(&config, &log_dir),
+        vec!["spring/2024-03-31/2024-03-31_01.log", "spring/2024-03-31/2024-03-31_03.log"]
+    );
+    assert_eq!(symlink_target(&log_dir, "spring"), "2024-03-31/2024-03-31_03.log");
+}
+
+#[test]
+fn test_dst_fall_back_appends_repeated_hour() {
+    let log_dir = test_log_dir("clock_dst_fall");
+    let clock = Arc::new(rs_box_log::LogManualClock::new(at("2024-10-27T02:50:00+02:00")));
+    let config = clocked_config("fall", &log_dir, &clock, 7);
+    let logger = rs_box_log::LoggerManager::with_config(config.clone());
+
+    logger.log_info_f("first pass");
+    // The clock goes back to 02:00; the second 02 hour lands in the same file
+    clock.set(at("2024-10-27T02:10:00+01:00"));
+    logger.log_info_f("second pass");
+    clock.set(at("2024-10-27T03:00:00+01:00"));
+    logger.log_info_f("after the switch");
+
+    assert_eq!(
+        file_names(&config, &log_dir),
+        vec!["fall/2024-10-27/2024-10-27_02.log", "fall/2024-10-27/2024-10-27_03.log"]
+    );
+    let repeated = std::fs::read_to_string(format!("{}/fall/2024-10-27/2024-10-27_02.log", log_dir)).unwrap();
+    assert!(repeated.contains("first pass") && repeated.contains("second pass"));
+    assert_eq!(symlink_target(&log_dir, "fall"), "2024-10-27/2024-10-27_03.log");
+}
@@ -0,0 +1,104 @@
+use std::fmt;
+use super::rs_box_log_format::json_escape;
+
+/// Typed value of a structured field attached to a record, e.g. by `log_info!(diff = 65536, "...")`
+#[derive(Clone, Debug, PartialEq)]
+pub enum LogFieldValue {
+    LogFieldValueStr(String),
+    LogFieldValueI64(i64),
+    LogFieldValueU64(u64),
+    LogFieldValueF64(f64),
+    LogFieldValueBool(bool),
+}
+
+impl LogFieldValue {
+    /// Native JSON form: numbers and booleans unquoted, non-finite floats as strings
+    pub fn to_json(&self) -> String {
+        match self {
+            LogFieldValue::LogFieldValueStr(value) => json_escape(value),
+            LogFieldValue::LogFieldValueF64(value) if !value.is_finite() => json_escape(&value.to_string()),
+            other => other.to_string(),
+        }
+    }
+
+    /// Form used in text lines: strings with spaces, quotes or `=` are quoted
+    pub fn to_text(&self) -> String {
+        match self {
+            LogFieldValue::LogFieldValueStr(value)
+                if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == '"' || c == '=') =>
+            {
+                json_escape(value)
+            }
+            other => other.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for LogFieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogFieldValue::LogFieldValueStr(value) => write!(f, "{}", value),
+            LogFieldValue::LogFieldValueI64(value) => write!(f, "{}", value),
+            LogFieldValue::LogFieldValueU64(value) => write!(f, "{}", value),
+            LogFieldValue::LogFieldValueF64(value) => write!(f, "{}", value),
+            LogFieldValue::LogFieldValueBool(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+macro_rules! impl_field_value_from {
+    ($variant:ident, $target:ty, $($source:ty),+) => {
+        $(
+            impl From<$source> for LogFieldValue {
+                fn from(value: $source) -> Self {
+                    LogFieldValue::$variant(value as $target)
+                }
+            }
+        )+
+    };
+}
+
+impl_field_value_from!(LogFieldValueI64, i64, i8, i16, i32, i64, isize);
+impl_field_value_from!(LogFieldValueU64, u64, u8, u16, u32, u64, usize);
+impl_field_value_from!(LogFieldValueF64, f64, f32, f64);
+
+impl From<bool> for LogFieldValue {
+    fn from(value: bool) -> Self {
+        LogFieldValue::LogFieldValueBool(value)
+    }
+}
+
+impl From<&str> for LogFieldValue {
+    fn from(value: &str) -> Self {
+        LogFieldValue::LogFieldValueStr(value.to_string())
+    }
+}
+
+impl From<String> for LogFieldValue {
+    fn from(value: String) -> Self {
+        LogFieldValue::LogFieldValueStr(value)
+    }
+}
+
+impl From<&String> for LogFieldValue {
+    fn from(value: &String) -> Self {
+        LogFieldValue::LogFieldValueStr(value.clone())
+    }
+}
+
+/// ` key=value key2=value2` for text lines, empty without fields
+pub(crate) fn render_text_fields(fields: &[(String, LogFieldValue)]) -> String {
+    fields
+        .iter()
+        .map(|(key, value)| format!(" {}={}", key, value.to_text()))
+        .collect()
+}
+
+/// `{"key":value,...}` with native JSON values
+pub(crate) fn render_json_fields(fields: &[(String, LogFieldValue)]) -> String {
+    let body: Vec<String> = fields
+        .iter()
+        .map(|(key, value)| format!("{}:{}", json_escape(key), value.to_json()))
+        .collect();
+    format!("{{{}}}", body.join(","))
+}
@@ -0,0 +1,58 @@
+#[cfg(test)]
+use super::rs_box_log;
+#[cfg(test)]
+use super::rs_box_log_test::{lock_global_config, test_log_dir};
+
+#[test]
+fn test_field_values() {
+    assert_eq!(rs_box_log::LogFieldValue::from(65536), rs_box_log::LogFieldValue::LogFieldValueI64(65536));
+    assert_eq!(rs_box_log::LogFieldValue::from(7u8), rs_box_log::LogFieldValue::LogFieldValueU64(7));
+    assert_eq!(rs_box_log::LogFieldValue::from("eth").to_json(), "\"eth\"");
+    assert_eq!(rs_box_log::LogFieldValue::from(1.5).to_json(), "1.5");
+    assert_eq!(rs_box_log::LogFieldValue::from(f64::NAN).to_json(), "\"NaN\"");
+    assert_eq!(rs_box_log::LogFieldValue::from("two words").to_text(), "\"two words\"");
+    assert_eq!(rs_box_log::LogFieldValue::from(true).to_text(), "true");
+}
+
+#[test]
+fn test_text_fields_are_redacted() {
+    let log_dir = test_log_dir("fields_text");
+    let config = rs_box_log::LogConfig::new("fields_text", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_redactor(rs_box_log::LogRedactor::with_default_preset());
+    let logger = rs_box_log::LoggerManager::with_config(config.clone());
+    logger.log_with_fields(
+        rs_box_log::LogLevel::LogLevelInfo,
+        "login",
+        vec![
+            ("worker".to_string(), "rig 01".into()),
+            ("password".to_string(), 1234.into()),
+            ("url".to_string(), "stratum+tcp://pool?api_key=abc".into()),
+        ],
+    );
+
+    let content = std::fs::read_to_string(&config.log_files()[0].path).unwrap();
+    assert!(content.contains("[login] worker=\"rig 01\" password=****** url=\"stratum+tcp://pool?api_key=******\"\n"));
+}
+
+#[test]
+fn test_macros_write_native_json_fields() {
+    let _global = lock_global_config();
+    let log_dir = test_log_dir("fields_json");
+    let config = rs_box_log::LogConfig::new("fields_json", true, &log_dir, rs_box_log::LogLevel::LogLevelInfo, 7)
+        .with_output_format(rs_box_log::LogOutputFormat::LogOutputFormatJson);
+    let _guard = rs_box_log::setup_log_tools_with_config(config.clone());
+
+    let name = String::from("alice");
+    let addr = std::net::Ipv4Addr::LOCALHOST;
+    crate::log_info!(worker = %name, diff = 65536, stale = false, peer = ?addr, "share accepted from {}", name);
+    crate::log_warning!("plain {}", 1);
+    crate::log_debug!(hidden = 1, "below level");
+
+    let content = std::fs::read_to_string(&config.log_files()[0].path).unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].contains(
+        r#","fields":{"worker":"alice","diff":65536,"stale":false,"peer":"127.0.0.1"},"message":"share accepted from alice"}"#
+    ));
+    assert!(!lines[1].contains("\"fields\"") && lines[1].contains("\"message\":\"plain 1\""));
+}
@@ -1 +1,343 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+use once_cell::sync::Lazy;
+use crate::rs_box_log::rs_box_log::LogLevel;
+use super::rs_box_log_integrity::{LogChain, LogChainSettings};
+use super::rs_box_log_metrics::{record_bytes_written, record_write_failure};
 
+/// Buffered data is written out as soon as it grows past this size
+const LOG_BUFFER_FLUSH_BYTES: usize = 64 * 1024;
+/// How often the background flusher looks for due buffers
+const LOG_FLUSHER_TICK: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogWritePolicy {
+    /// Every record is written straight to the file (default)
+    LogWritePolicyUnbuffered,
+    /// Records are buffered in memory and flushed at least once per interval
+    LogWritePolicyBuffered(Duration),
+    /// Like `LogWritePolicyBuffered`, but ERROR records are flushed and fsync'd immediately
+    LogWritePolicySyncOnError(Duration),
+}
+
+impl LogWritePolicy {
+    fn flush_interval(&self) -> Option<Duration> {
+        match *self {
+            LogWritePolicy::LogWritePolicyUnbuffered => None,
+            LogWritePolicy::LogWritePolicyBuffered(interval) => Some(interval),
+            LogWritePolicy::LogWritePolicySyncOnError(interval) => Some(interval),
+        }
+    }
+}
+
+struct LogFileState {
+    file: File,
+    process_lock: bool,
+    path: PathBuf,
+    buffer: Vec<u8>,
+    last_flush: Instant,
+    /// Tags records with an HMAC chain, see `LogIntegrity`
+    chain: Option<LogChain>,
+    /// `chain` as of the last tag that reached the file. A failed write falls back to it, so
+    /// records that never got written do not break the chain of the ones after them.
+    written_chain: Option<LogChain>,
+}
+
+impl LogFileState {
+    /// Queues the seal tag of the chain, if the file has unsealed records
+    fn seal(&mut self) {
+        if let Some(seal) = self.chain.as_mut().and_then(LogChain::seal) {
+            self.buffer.extend_from_slice(seal.as_bytes());
+        }
+    }
+
+    fn flush_buffer(&mut self) -> io::Result<()> {
+        self.last_flush = Instant::now();
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        // The buffer only ever holds whole records, so one write never splits a line
+        let result = write_counted(&mut self.file, &self.buffer, self.process_lock);
+        self.buffer.clear();
+        if result.is_ok() {
+            self.written_chain.clone_from(&self.chain);
+        } else {
+            self.chain.clone_from(&self.written_chain);
+        }
+        result
+    }
+}
+
+/// Writes `data` in one go. With `process_lock` an exclusive advisory lock is held for
+/// the write, so processes appending to the same file never interleave within a record.
+fn write_counted(file: &mut File, data: &[u8], process_lock: bool) -> io::Result<()> {
+    let result = if process_lock {
+        file.lock().and_then(|()| {
+            let written = file.write_all(data);
+            file.unlock().and(written)
+        })
+    } else {
+        file.write_all(data)
+    };
+    match result {
+        Ok(()) => {
+            record_bytes_written(data.len());
+            Ok(())
+        }
+        Err(e) => {
+            record_write_failure();
+            Err(e)
+        }
+    }
+}
+
+fn shared_chain_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "cannot chain a log file shared by processes: put {pid} in the file layout or drop the process lock",
+    )
+}
+
+/// An open log file plus its write policy; rotation swaps the underlying file in place.
+/// Loggers writing to the same path share one handle.
+pub(crate) struct LogFileHandle {
+    policy: LogWritePolicy,
+    state: Mutex<LogFileState>,
+    rotation: Mutex<()>,
+}
+
+static LIVE_FILE_HANDLES: Lazy<Mutex<Vec<Weak<LogFileHandle>>>> = Lazy::new(|| Mutex::new(Vec::new()));
+static LOG_FLUSHER_STARTED: AtomicBool = AtomicBool::new(false);
+static LOG_SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+impl LogFileHandle {
+    pub(crate) fn open_file(path: &Path) -> io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(path)
+    }
+
+    /// Returns the live handle already writing `path`, or opens a new one, chaining its
+    /// records as `chain` says. The flag returned is true when the file was opened by this call.
+    ///
+    /// A live handle takes on a requested process lock; it is refused when it cannot give the
+    /// requested integrity chain, and keeps its own write policy with a warning. A chained
+    /// file is refused a process lock unless its name holds the process id: every process
+    /// would keep its own chain position and break the chain of the others.
+    pub(crate) fn open_shared(
+        path: &Path,
+        policy: LogWritePolicy,
+        process_lock: bool,
+        chain: Option<LogChainSettings>,
+    ) -> io::Result<(Arc<Self>, bool)> {
+        let mut handles = LIVE_FILE_HANDLES.lock().unwrap();
+        handles.retain(|weak| weak.strong_count() > 0);
+        if let Some(handle) = handles.iter().filter_map(Weak::upgrade).find(|handle| handle.path() == path) {
+            handle.join_settings(policy, process_lock, chain.as_ref())?;
+            return Ok((handle, false));
+        }
+        if process_lock && chain.as_ref().is_some_and(|settings| !settings.per_process) {
+            return Err(shared_chain_error());
+        }
+
+        let file = LogFileHandle::open_file(path)?;
+        let chain = chain.map(|settings| LogChain::resume(&settings, path));
+        let handle = Arc::new(LogFileHandle {
+            policy,
+            state: Mutex::new(LogFileState {
+                file,
+                process_lock,
+                path: path.to_path_buf(),
+                buffer: Vec::new(),
+                last_flush: Instant::now(),
+                written_chain: chain.clone(),
+                chain,
+            }),
+            rotation: Mutex::new(()),
+        });
+        handles.push(Arc::downgrade(&handle));
+        drop(handles);
+
+        if policy.flush_interval().is_some() {
+            start_log_flusher();
+        }
+        Ok((handle, true))
+    }
+
+    /// Reconciles the settings of another config writing to this handle's file
+    fn join_settings(&self, policy: LogWritePolicy, process_lock: bool, chain: Option<&LogChainSettings>) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let refused = match (&state.chain, chain) {
+            (None, Some(_)) => Some("it is already written without an integrity chain"),
+            (Some(chain), Some(settings)) if !chain.continues(settings.integrity, settings.json) => {
+                Some("it is already chained with another integrity key or format")
+            }
+            _ => None,
+        };
+        if let Some(reason) = refused {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("cannot share log file: {}", reason)));
+        }
+        if (state.process_lock || process_lock) && state.chain.as_ref().is_some_and(|chain| !chain.is_per_process()) {
+            return Err(shared_chain_error());
+        }
+        state.process_lock |= process_lock;
+        if policy != self.policy {
+            eprintln!(
+                "Log file {} is already open with write policy {:?}, ignoring {:?}",
+                state.path.display(),
+                self.policy,
+                policy
+            );
+        }
+        Ok(())
+    }
+
+    pub(crate) fn is_open(path: &Path) -> bool {
+        let handles = LIVE_FILE_HANDLES.lock().unwrap();
+        handles.iter().filter_map(Weak::upgrade).any(|handle| handle.path() == path)
+    }
+
+    /// Held while deciding on and performing a rotation, so loggers sharing the
+    /// handle rotate (and run retention) once.
+    pub(crate) fn lock_rotation(&self) -> MutexGuard<'_, ()> {
+        self.rotation.lock().unwrap()
+    }
+
+    pub(crate) fn path(&self) -> PathBuf {
+        self.state.lock().unwrap().path.clone()
+    }
+
+    pub(crate) fn write_record(&self, data: &[u8], level: LogLevel) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let tagged;
+        let data = match state.chain {
+            Some(ref mut chain) => {
+                let start = chain.start().unwrap_or_default();
+                tagged = [start.as_bytes(), data, chain.tag(data).as_bytes()].concat();
+                tagged.as_slice()
+            }
+            None => data,
+        };
+        state.buffer.extend_from_slice(data);
+        if self.policy.flush_interval().is_none() || LOG_SHUTDOWN.load(Ordering::SeqCst) {
+            return state.flush_buffer();
+        }
+        if level == LogLevel::LogLevelError
+            && let LogWritePolicy::LogWritePolicySyncOnError(_) = self.policy
+        {
+            state.flush_buffer()?;
+            return state.file.sync_data();
+        }
+        if state.buffer.len() >= LOG_BUFFER_FLUSH_BYTES {
+            state.flush_buffer()?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn replace_file(&self, file: File, path: PathBuf) {
+        let mut state = self.state.lock().unwrap();
+        state.seal();
+        if let Err(e) = state.flush_buffer() {
+            eprintln!("Failed to flush log file {}: {}", state.path.display(), e);
+        }
+        state.file = file;
+        state.path = path;
+        let state = &mut *state;
+        if let Some(ref mut chain) = state.chain {
+            chain.restart(&state.path);
+        }
+        state.written_chain.clone_from(&state.chain);
+    }
+
+    /// Writes the seal tag if the file is chained and has records since the last seal
+    pub(crate) fn seal(&self) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.seal();
+        state.flush_buffer()
+    }
+
+    pub(crate) fn flush(&self, sync: bool) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.flush_buffer()?;
+        state.file.flush()?;
+        if sync {
+            state.file.sync_all()?;
+        }
+        Ok(())
+    }
+
+    fn flush_if_due(&self) {
+        let Some(interval) = self.policy.flush_interval() else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap();
+        if !state.buffer.is_empty()
+            && state.last_flush.elapsed() >= interval
+            && let Err(e) = state.flush_buffer()
+        {
+            eprintln!("Failed to flush log file {}: {}", state.path.display(), e);
+        }
+    }
+}
+
+impl Drop for LogFileHandle {
+    fn drop(&mut self) {
+        if let Ok(state) = self.state.get_mut() {
+            state.seal();
+            if let Err(e) = state.flush_buffer() {
+                eprintln!("Failed to flush log file {}: {}", state.path.display(), e);
+            }
+        }
+    }
+}
+
+fn live_file_handles() -> Vec<Arc<LogFileHandle>> {
+    let mut handles = LIVE_FILE_HANDLES.lock().unwrap();
+    handles.retain(|weak| weak.strong_count() > 0);
+    handles.iter().filter_map(Weak::upgrade).collect()
+}
+
+fn start_log_flusher() {
+    if LOG_FLUSHER_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let spawned = std::thread::Builder::new()
+        .name("rs_box_log_flusher".to_string())
+        .spawn(|| {
+            loop {
+                std::thread::sleep(LOG_FLUSHER_TICK);
+                for handle in live_file_handles() {
+                    handle.flush_if_due();
+                }
+            }
+        });
+    if let Err(e) = spawned {
+        LOG_FLUSHER_STARTED.store(false, Ordering::SeqCst);
+        eprintln!("Failed to start log flusher thread: {}", e);
+    }
+}
+
+/// Flushes every open log file, optionally followed by an fsync
+pub(crate) fn flush_all_file_handles(sync: bool) {
+    for handle in live_file_handles() {
+        if let Err(e) = handle.flush(sync) {
+            eprintln!("Failed to flush log file {}: {}", handle.path().display(), e);
+        }
+    }
+}
+
+/// Seals every chained log file, on a graceful exit
+pub(crate) fn seal_all_file_handles() {
+    for handle in live_file_handles() {
+        if let Err(e) = handle.seal() {
+            eprintln!("Failed to seal log file {}: {}", handle.path().display(), e);
+        }
+    }
+}
+
+/// Once shut down, records bypass the buffer and go straight to disk
+pub(crate) fn mark_log_shutdown(shutdown: bool) {
+    LOG_SHUTDOWN.store(shutdown, Ordering::SeqCst);
+}
@@ -0,0 +1,299 @@
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+use once_cell::sync::Lazy;
+use crate::rs_box_log::rs_box_log::{LogConfig, LogLevel, LogRecord};
+use super::rs_box_log_fields::{render_json_fields, render_text_fields};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogOutputFormat {
+    /// `[time] [LEVEL] [message]` lines, colored by level (default)
+    LogOutputFormatText,
+    /// One JSON object per line
+    LogOutputFormatJson,
+}
+
+impl LogOutputFormat {
+    pub fn to_str(&self) -> &'static str {
+        match self {
+            LogOutputFormat::LogOutputFormatText => "text",
+            LogOutputFormat::LogOutputFormatJson => "json",
+        }
+    }
+}
+
+static HOST_NAME: Lazy<String> = Lazy::new(|| {
+    #[cfg(target_os = "linux")]
+    if let Ok(name) = std::fs::read_to_string("/proc/sys/kernel/hostname") {
+        let name = name.trim();
+        if !name.is_empty() {
+            return name.to_string();
+        }
+    }
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+});
+
+pub(crate) fn host_name() -> &'static str {
+    HOST_NAME.as_str()
+}
+
+pub(crate) fn level_color_code(level: LogLevel) -> &'static str {
+    match level {
+        LogLevel::LogLevelInfo => "\x1b[32m",    // Green
+        LogLevel::LogLevelWarning => "\x1b[33m", // Yellow
+        LogLevel::LogLevelError => "\x1b[31m",   // Red
+        LogLevel::LogLevelDebug => "\x1b[36m",   // Cyan
+        LogLevel::LogLevelTrace => "\x1b[34m",   // Blue
+    }
+}
+
+pub(crate) fn json_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TemplatePart {
+    Literal(String),
+    Time,
+    Level,
+    Logger,
+    Location,
+    Message,
+    ThreadName,
+    ThreadId,
+    ProcessId,
+    Host,
+    Fields,
+    Sampled,
+}
+
+/// Custom text line layout, e.g. `"{time} {level} [{thread_name}#{thread_id}] {message}"`.
+/// Tokens: `{time}`, `{level}`, `{logger}`, `{location}`, `{message}`, `{thread_name}`,
+/// `{thread_id}`, `{pid}`, `{host}`, `{fields}` (` key=value` pairs) and `{sampled}` (the
+/// sampling that kept the record); values that are not recorded render as `-`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogTextTemplate {
+    template: String,
+    parts: Vec<TemplatePart>,
+}
+
+impl LogTextTemplate {
+    pub fn new(template: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(TemplatePart::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed token in log template {:?}", template))?;
+            let part = match &rest[start + 1..start + end] {
+                "time" => TemplatePart::Time,
+                "level" => TemplatePart::Level,
+                "logger" => TemplatePart::Logger,
+                "location" => TemplatePart::Location,
+                "message" => TemplatePart::Message,
+                "thread_name" => TemplatePart::ThreadName,
+                "thread_id" => TemplatePart::ThreadId,
+                "pid" => TemplatePart::ProcessId,
+                "host" => TemplatePart::Host,
+                "fields" => TemplatePart::Fields,
+                "sampled" => TemplatePart::Sampled,
+                other => return Err(format!("unknown token {{{}}} in log template {:?}", other, template)),
+            };
+            parts.push(part);
+            rest = &rest[start + end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(TemplatePart::Literal(rest.to_string()));
+        }
+        Ok(LogTextTemplate {
+            template: template.to_string(),
+            parts,
+        })
+    }
+
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    fn render(&self, record: &LogRecord) -> String {
+        let mut line = String::new();
+        for part in &self.parts {
+            let value = match part {
+                TemplatePart::Literal(text) => text.clone(),
+                TemplatePart::Time => record.time.format("%Y-%m-%d %H:%M:%S %:z").to_string(),
+                TemplatePart::Level => record.level.to_str().to_string(),
+                TemplatePart::Logger => record.logger_name.clone(),
+                TemplatePart::Location => record.location.clone(),
+                TemplatePart::Message => record.message.clone(),
+                TemplatePart::ThreadName => record.thread_name.clone().unwrap_or_else(|| "-".to_string()),
+                TemplatePart::ThreadId => record.thread_id.map_or_else(|| "-".to_string(), |id| id.to_string()),
+                TemplatePart::ProcessId => record.pid.map_or_else(|| "-".to_string(), |pid| pid.to_string()),
+                TemplatePart::Host => record.host.clone().unwrap_or_else(|| "-".to_string()),
+                TemplatePart::Fields => render_text_fields(&record.fields),
+                TemplatePart::Sampled => record.sampling.map_or_else(|| "-".to_string(), |sampling| sampling.to_string()),
+            };
+            line.push_str(&value);
+        }
+        line.push('\n');
+        line
+    }
+}
+
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static THREAD_ID: u64 = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Number of the current thread, unique within the process; threads are numbered from 1
+/// in the order they first log. `ThreadId` has no stable integer accessor.
+pub(crate) fn current_thread_id() -> u64 {
+    THREAD_ID.with(|id| *id)
+}
+
+fn render_text(record: &LogRecord, show_logger_name: bool) -> String {
+    let reset_code = "\x1b[0m";
+    let mut line = format!(
+        "[{}] {}[{}]{}",
+        record.time.format("%Y-%m-%d %H:%M:%S %:z"),
+        level_color_code(record.level),
+        record.level.to_str(),
+        reset_code
+    );
+    if let Some(sampling) = record.sampling {
+        let _ = write!(line, " [sampled:{}]", sampling);
+    }
+    if show_logger_name {
+        let _ = write!(line, " [logger:{}]", record.logger_name);
+    }
+
+    let mut identity = Vec::new();
+    if let Some(ref thread_name) = record.thread_name {
+        identity.push(format!("thread:{}", thread_name));
+    }
+    if let Some(thread_id) = record.thread_id {
+        identity.push(format!("tid:{}", thread_id));
+    }
+    if let Some(pid) = record.pid {
+        identity.push(format!("pid:{}", pid));
+    }
+    if let Some(ref host) = record.host {
+        identity.push(format!("host:{}", host));
+    }
+    if !identity.is_empty() {
+        let _ = write!(line, " [{}]", identity.join(" "));
+    }
+
+    if !record.location.is_empty() {
+        let _ = write!(line, " {}", record.location);
+    }
+    let _ = writeln!(line, " [{}]{}", record.message, render_text_fields(&record.fields));
+    line
+}
+
+pub(crate) fn render_json(record: &LogRecord) -> String {
+    let mut line = format!(
+        "{{\"time\":{},\"level\":{},\"logger\":{}",
+        json_escape(&record.time.to_rfc3339()),
+        json_escape(record.level.to_str()),
+        json_escape(&record.logger_name)
+    );
+    if let Some(sampling) = record.sampling {
+        let _ = write!(line, ",\"sampled\":{}", json_escape(&sampling.to_string()));
+    }
+    if !record.location.is_empty() {
+        let _ = write!(line, ",\"location\":{}", json_escape(&record.location));
+    }
+    if let Some(ref thread_name) = record.thread_name {
+        let _ = write!(line, ",\"thread_name\":{}", json_escape(thread_name));
+    }
+    if let Some(thread_id) = record.thread_id {
+        let _ = write!(line, ",\"thread_id\":{}", thread_id);
+    }
+    if let Some(pid) = record.pid {
+        let _ = write!(line, ",\"pid\":{}", pid);
+    }
+    if let Some(ref host) = record.host {
+        let _ = write!(line, ",\"host\":{}", json_escape(host));
+    }
+    if !record.fields.is_empty() {
+        let _ = write!(line, ",\"fields\":{}", render_json_fields(&record.fields));
+    }
+    let _ = writeln!(line, ",\"message\":{}}}", json_escape(&record.message));
+    line
+}
+
+pub(crate) fn render_record(record: &LogRecord, config: &LogConfig) -> String {
+    match config.output_format {
+        LogOutputFormat::LogOutputFormatText => match config.text_template {
+            Some(ref template) => template.render(record),
+            None => render_text(record, config.show_logger_name),
+        },
+        LogOutputFormat::LogOutputFormatJson => render_json(record),
+    }
+}
+
+/// Header block written at the top of every newly opened log file, so a file
+/// pulled off a machine tells which binary and settings produced it.
+pub(crate) fn render_file_header(config: &LogConfig) -> String {
+    let fields: Vec<(&str, String)> = vec![
+        ("project", config.project_name().to_string()),
+        ("rs_box_version", crate::LIB_VERSION.to_string()),
+        ("app_version", config.app_version().unwrap_or("unknown").to_string()),
+        ("pid", std::process::id().to_string()),
+        ("host", host_name().to_string()),
+        ("run_mode", crate::get_current_run_mode().to_str().to_string()),
+        ("os", std::env::consts::OS.to_string()),
+        ("arch", std::env::consts::ARCH.to_string()),
+        ("opened_at", config.clock.now().to_rfc3339()),
+    ];
+    let config_fields = config.describe();
+
+    match config.output_format() {
+        LogOutputFormat::LogOutputFormatText => {
+            let mut header = String::from("# ==================== rs_box log header ====================\n");
+            for (key, value) in &fields {
+                let _ = writeln!(header, "# {:<24} = {}", key, value);
+            }
+            for (key, value) in &config_fields {
+                let _ = writeln!(header, "# {:<24} = {}", format!("config.{}", key), value);
+            }
+            header.push_str("# ============================================================\n");
+            header
+        }
+        LogOutputFormat::LogOutputFormatJson => {
+            let render_object = |pairs: &[(&str, String)]| {
+                pairs
+                    .iter()
+                    .map(|(key, value)| format!("{}:{}", json_escape(key), json_escape(value)))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            };
+            format!(
+                "{{\"header\":{{{},\"config\":{{{}}}}}}}\n",
+                render_object(&fields),
+                render_object(&config_fields)
+            )
+        }
+    }
+}
@@ -0,0 +1,106 @@
+#[cfg(test)]
+use super::rs_box_log;
+#[cfg(test)]
+use super::rs_box_log_test::test_log_dir;
+
+#[test]
+fn test_text_file_header() {
+    let log_dir = test_log_dir("text_header");
+    let config = rs_box_log::LogConfig::new("text_header", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_file_header(true)
+        .with_app_version("1.2.3");
+    let logger = rs_box_log::LoggerManager::with_config(config);
+    logger.log_info_f("first record after header");
+
+    let content = std::fs::read_to_string(format!("{}/text_header/run.log", log_dir)).unwrap();
+    assert!(content.starts_with("# ==================== rs_box log header"));
+    assert!(content.contains(&format!("rs_box_version           = {}", crate::LIB_VERSION)));
+    assert!(content.contains("app_version              = 1.2.3"));
+    assert!(content.contains(&format!("pid                      = {}", std::process::id())));
+    assert!(content.contains("config.log_level         = DEBUG"));
+    assert!(content.contains("first record after header"));
+}
+
+#[test]
+fn test_json_output_with_header() {
+    let log_dir = test_log_dir("json_header");
+    let config = rs_box_log::LogConfig::new("json_header", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_output_format(rs_box_log::LogOutputFormat::LogOutputFormatJson)
+        .with_file_header(true);
+    let logger = rs_box_log::LoggerManager::with_config(config);
+    logger.log_warning_f("quoted \"value\"\nnext line");
+
+    let content = std::fs::read_to_string(format!("{}/json_header/run.log", log_dir)).unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("{\"header\":{\"project\":\"json_header\""));
+    assert!(lines[0].contains("\"config\":{\"enable_save_log_file\":\"true\""));
+    assert!(lines[1].starts_with("{\"time\":"));
+    assert!(lines[1].contains("\"level\":\"WARNING\",\"logger\":\"json_header\""));
+    assert!(lines[1].ends_with("\"message\":\"quoted \\\"value\\\"\\nnext line\"}"));
+}
+
+#[test]
+fn test_no_header_by_default() {
+    let log_dir = test_log_dir("no_header");
+    let config = rs_box_log::LogConfig::new("no_header", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7);
+    let logger = rs_box_log::LoggerManager::with_config(config);
+    logger.log_info_f("plain record");
+
+    let content = std::fs::read_to_string(format!("{}/no_header/run.log", log_dir)).unwrap();
+    assert!(content.starts_with('['));
+}
+
+#[test]
+fn test_identification_fields_in_text_and_json() {
+    let log_dir = test_log_dir("identity");
+    let text_config = rs_box_log::LogConfig::new("identity_text", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_thread_name(true)
+        .with_process_id(true);
+    let json_config = rs_box_log::LogConfig::new("identity_json", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_output_format(rs_box_log::LogOutputFormat::LogOutputFormatJson)
+        .with_thread_name(true)
+        .with_thread_id(true)
+        .with_host_name(true);
+
+    std::thread::Builder::new()
+        .name("share-worker".to_string())
+        .spawn(move || {
+            rs_box_log::LoggerManager::with_config(text_config).log_info_f("text identity");
+            rs_box_log::LoggerManager::with_config(json_config).log_info_f("json identity");
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+
+    let text = std::fs::read_to_string(format!("{}/identity_text/run.log", log_dir)).unwrap();
+    assert!(text.contains(&format!("[thread:share-worker pid:{}] [text identity]", std::process::id())));
+
+    let json = std::fs::read_to_string(format!("{}/identity_json/run.log", log_dir)).unwrap();
+    assert!(json.contains("\"thread_name\":\"share-worker\",\"thread_id\":"));
+    assert!(json.contains("\"host\":"));
+    assert!(!json.contains("\"pid\""));
+}
+
+#[test]
+fn test_text_template() {
+    let log_dir = test_log_dir("template");
+    let template = rs_box_log::LogTextTemplate::new("{level}|{logger}|{thread_name}|{pid}|{message}").unwrap();
+    let config = rs_box_log::LogConfig::new("template", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_text_template(template)
+        .with_process_id(true);
+    rs_box_log::LoggerManager::with_config(config).log_error_f("templated");
+
+    let content = std::fs::read_to_string(format!("{}/template/run.log", log_dir)).unwrap();
+    assert_eq!(content, format!("ERROR|template|-|{}|templated\n", std::process::id()));
+    assert!(rs_box_log::LogTextTemplate::new("{time} {severity}").is_err());
+}
+
+#[test]
+fn test_thread_ids_are_stable_and_distinct() {
+    let main_id = super::rs_box_log_format::current_thread_id();
+    assert!(main_id > 0);
+    assert_eq!(super::rs_box_log_format::current_thread_id(), main_id);
+    let other_id = std::thread::spawn(super::rs_box_log_format::current_thread_id).join().unwrap();
+    assert!(other_id > 0 && other_id != main_id);
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use once_cell::sync::Lazy;
+use crate::rs_box_log::rs_box_log::{bump_log_config_generation, LogConfig, LogLevel, LogOutputFormat, LogSampler, LogSink};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogChildFileMode {
+    /// Records go into the file of the nearest configured ancestor, tagged with the logger name
+    LogChildFileModeParentFile,
+    /// Records go into a subdirectory of the nearest configured ancestor's directory (default)
+    LogChildFileModeSubdirectory,
+}
+
+/// Overrides for a dotted logger name such as `pool.stratum`. Every field left unset
+/// is inherited from the nearest configured ancestor, and finally from the global config.
+#[derive(Clone, Debug, Default)]
+pub struct LoggerSettings {
+    log_level: Option<LogLevel>,
+    output_format: Option<LogOutputFormat>,
+    enable_save_log_file: Option<bool>,
+    sinks: Option<Vec<Arc<dyn LogSink>>>,
+    file_mode: Option<LogChildFileMode>,
+    sampler: Option<LogSampler>,
+}
+
+impl LoggerSettings {
+    pub fn new() -> Self {
+        LoggerSettings::default()
+    }
+
+    pub fn with_log_level(mut self, log_level: LogLevel) -> Self {
+        self.log_level = Some(log_level);
+        self
+    }
+
+    pub fn with_output_format(mut self, output_format: LogOutputFormat) -> Self {
+        self.output_format = Some(output_format);
+        self
+    }
+
+    pub fn with_save_log_file(mut self, enable_save_log_file: bool) -> Self {
+        self.enable_save_log_file = Some(enable_save_log_file);
+        self
+    }
+
+    /// Replaces the inherited extra sinks for this logger and its children
+    pub fn with_sinks(mut self, sinks: Vec<Arc<dyn LogSink>>) -> Self {
+        self.sinks = Some(sinks);
+        self
+    }
+
+    /// Samples this logger and its children; they share the sampler's counters
+    pub fn with_sampler(mut self, sampler: LogSampler) -> Self {
+        self.sampler = Some(sampler);
+        self
+    }
+
+    /// Where the children of this logger write; the logger itself keeps its own file
+    pub fn with_file_mode(mut self, file_mode: LogChildFileMode) -> Self {
+        self.file_mode = Some(file_mode);
+        self
+    }
+
+    fn apply(&self, config: &mut LogConfig) {
+        if let Some(log_level) = self.log_level {
+            config.log_level = log_level;
+        }
+        if let Some(output_format) = self.output_format {
+            config.output_format = output_format;
+        }
+        if let Some(enable_save_log_file) = self.enable_save_log_file {
+            config.enable_save_log_file = enable_save_log_file;
+        }
+        if let Some(ref sinks) = self.sinks {
+            config.sinks = sinks.clone();
+        }
+        if let Some(ref sampler) = self.sampler {
+            config.sampler = Some(sampler.clone());
+        }
+    }
+}
+
+static LOGGER_SETTINGS: Lazy<Mutex<HashMap<String, LoggerSettings>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Configures the logger `name` and, through inheritance, all loggers below it.
+/// Live loggers following the global config pick the change up with their next record.
+pub fn configure_logger(name: &str, settings: LoggerSettings) {
+    LOGGER_SETTINGS.lock().unwrap().insert(name.to_string(), settings);
+    bump_log_config_generation();
+}
+
+pub(crate) fn configured_sinks() -> Vec<Arc<dyn LogSink>> {
+    let settings = LOGGER_SETTINGS.lock().unwrap();
+    settings.values().filter_map(|s| s.sinks.clone()).flatten().collect()
+}
+
+/// Builds the effective config of the dotted logger `name` on top of `root`.
+pub(crate) fn resolve_logger_config(root: &LogConfig, name: &str) -> LogConfig {
+    let settings = LOGGER_SETTINGS.lock().unwrap();
+    let segments: Vec<&str> = name.split('.').collect();
+
+    let mut config = root.clone();
+    for depth in 1..=segments.len() {
+        if let Some(s) = settings.get(&segments[..depth].join(".")) {
+            s.apply(&mut config);
+        }
+    }
+
+    let file_mode = file_mode_of(&settings, &segments);
+    config.project_name = file_dir_of(&settings, &segments);
+    config.show_logger_name = file_mode == LogChildFileMode::LogChildFileModeParentFile;
+    config
+}
+
+/// How the logger made of `segments` places its files, as set on its nearest ancestor that
+/// has a file mode. A mode set on the logger itself only applies to its children.
+fn file_mode_of(settings: &HashMap<String, LoggerSettings>, segments: &[&str]) -> LogChildFileMode {
+    (1..segments.len())
+        .rev()
+        .find_map(|depth| settings.get(&segments[..depth].join(".")).and_then(|s| s.file_mode))
+        .unwrap_or(LogChildFileMode::LogChildFileModeSubdirectory)
+}
+
+/// Directory (relative to `log_dir`) holding the files of the logger made of `segments`
+fn file_dir_of(settings: &HashMap<String, LoggerSettings>, segments: &[&str]) -> String {
+    let ancestor = (1..segments.len())
+        .rev()
+        .find(|&depth| settings.contains_key(&segments[..depth].join(".")));
+
+    match (ancestor, file_mode_of(settings, segments)) {
+        (Some(depth), LogChildFileMode::LogChildFileModeParentFile) => file_dir_of(settings, &segments[..depth]),
+        (Some(depth), LogChildFileMode::LogChildFileModeSubdirectory) => {
+            format!("{}/{}", file_dir_of(settings, &segments[..depth]), segments[depth..].join("/"))
+        }
+        // A child file mode is always set on a configured ancestor
+        (None, _) => segments.join("."),
+    }
+}
@@ -0,0 +1,94 @@
+#[cfg(test)]
+use super::rs_box_log;
+#[cfg(test)]
+use super::rs_box_log_test::{level_compiled_in, test_log_dir};
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct MemorySink {
+    pub(crate) records: Mutex<Vec<rs_box_log::LogRecord>>,
+}
+
+#[cfg(test)]
+impl rs_box_log::LogSink for MemorySink {
+    fn write_record(&self, record: &rs_box_log::LogRecord) {
+        self.records.lock().unwrap().push(record.clone());
+    }
+}
+
+#[test]
+fn test_children_inherit_from_nearest_configured_ancestor() {
+    let log_dir = test_log_dir("hierarchy_inherit");
+    let root = rs_box_log::LogConfig::new("hierarchy_root", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7);
+    let sink = Arc::new(MemorySink::default());
+    rs_box_log::configure_logger(
+        "hinherit",
+        rs_box_log::LoggerSettings::new()
+            .with_log_level(rs_box_log::LogLevel::LogLevelInfo)
+            .with_output_format(rs_box_log::LogOutputFormat::LogOutputFormatJson)
+            .with_sinks(vec![sink.clone()]),
+    );
+    rs_box_log::configure_logger(
+        "hinherit.rpc",
+        rs_box_log::LoggerSettings::new().with_log_level(rs_box_log::LogLevel::LogLevelDebug),
+    );
+
+    let stratum = rs_box_log::LoggerManager::with_root_config("hinherit.stratum.eth", &root);
+    stratum.log_info_f("stratum info");
+    stratum.log_debug_f("stratum debug is filtered by the inherited level");
+    let rpc = rs_box_log::LoggerManager::with_root_config("hinherit.rpc.btc", &root);
+    rpc.log_debug_f("rpc debug is allowed by the override");
+
+    let names: Vec<(String, String)> = sink
+        .records
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|record| (record.logger_name.clone(), record.message.clone()))
+        .collect();
+    let mut expected = vec![("hinherit.stratum.eth".to_string(), "stratum info".to_string())];
+    if level_compiled_in(rs_box_log::LogLevel::LogLevelDebug) {
+        expected.push(("hinherit.rpc.btc".to_string(), "rpc debug is allowed by the override".to_string()));
+    }
+    assert_eq!(names, expected);
+
+    // Children get their own subdirectory below the configured ancestor and inherit its JSON format
+    let content = std::fs::read_to_string(format!("{}/hinherit/stratum/eth/run.log", log_dir)).unwrap();
+    assert!(content.starts_with("{\"time\":"));
+    assert!(content.contains("\"logger\":\"hinherit.stratum.eth\""));
+    if level_compiled_in(rs_box_log::LogLevel::LogLevelDebug) {
+        assert!(std::path::Path::new(&format!("{}/hinherit/rpc/btc/run.log", log_dir)).exists());
+    }
+}
+
+#[test]
+fn test_children_write_into_parent_file() {
+    let log_dir = test_log_dir("hierarchy_parent");
+    let root = rs_box_log::LogConfig::new("hierarchy_root", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7);
+    rs_box_log::configure_logger(
+        "hparent",
+        rs_box_log::LoggerSettings::new().with_file_mode(rs_box_log::LogChildFileMode::LogChildFileModeParentFile),
+    );
+
+    let parent = rs_box_log::LoggerManager::with_root_config("hparent", &root);
+    let child = rs_box_log::LoggerManager::with_root_config("hparent.worker", &root);
+    parent.log_info_f("from parent");
+    child.log_info_f("from child");
+
+    // The file mode applies to the children; "hparent" keeps its own file and shares it with them
+    let content = std::fs::read_to_string(format!("{}/hparent/run.log", log_dir)).unwrap();
+    assert!(content.contains("[from parent]") && !content.contains("[logger:hparent]"));
+    assert!(content.contains("[logger:hparent.worker] [from child]"));
+    assert!(!std::path::Path::new(&format!("{}/hierarchy_root", log_dir)).exists());
+}
+
+#[test]
+fn test_unconfigured_dotted_name_keeps_flat_directory() {
+    let log_dir = test_log_dir("hierarchy_flat");
+    let root = rs_box_log::LogConfig::new("hierarchy_root", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7);
+    let logger = rs_box_log::LoggerManager::with_root_config("hflat.module", &root);
+    logger.log_info_f("flat");
+    assert!(std::path::Path::new(&format!("{}/hflat.module/run.log", log_dir)).exists());
+}
@@ -0,0 +1,102 @@
+use std::cell::Cell;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use once_cell::sync::Lazy;
+use crate::rs_box_log::rs_box_log::{LogLevel, LogRecord};
+
+/// Selects the records a hook is called for. An empty filter matches everything.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LogRecordFilter {
+    levels: Option<Vec<LogLevel>>,
+    logger: Option<String>,
+}
+
+impl LogRecordFilter {
+    pub fn new() -> Self {
+        LogRecordFilter::default()
+    }
+
+    pub fn with_levels(mut self, levels: &[LogLevel]) -> Self {
+        self.levels = Some(levels.to_vec());
+        self
+    }
+
+    /// Matches the logger `name` and its dotted children, e.g. `pool.stratum` matches `pool.stratum.eth`
+    pub fn with_logger(mut self, name: &str) -> Self {
+        self.logger = Some(name.to_string());
+        self
+    }
+
+    pub fn matches(&self, record: &LogRecord) -> bool {
+        if let Some(ref levels) = self.levels
+            && !levels.contains(&record.level)
+        {
+            return false;
+        }
+        match self.logger {
+            Some(ref name) => {
+                record.logger_name == *name
+                    || record.logger_name.strip_prefix(name.as_str()).is_some_and(|rest| rest.starts_with('.'))
+            }
+            None => true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LogHookId(u64);
+
+type LogHook = Arc<dyn Fn(&LogRecord) + Send + Sync>;
+
+static LOG_HOOKS: Lazy<RwLock<Vec<(LogHookId, LogRecordFilter, LogHook)>>> = Lazy::new(|| RwLock::new(Vec::new()));
+static NEXT_HOOK_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static IN_LOG_HOOK: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Registers `hook` to run for every record matching `filter`, after level
+/// filtering and redaction. A panicking hook is caught and reported on stderr.
+///
+/// Hooks run after the logging call has released its logger, so they may log through
+/// any logger; records logged from inside a hook do not trigger hooks again.
+pub fn on_record<F>(filter: LogRecordFilter, hook: F) -> LogHookId
+where
+    F: Fn(&LogRecord) + Send + Sync + 'static,
+{
+    let id = LogHookId(NEXT_HOOK_ID.fetch_add(1, Ordering::Relaxed));
+    LOG_HOOKS.write().unwrap().push((id, filter, Arc::new(hook)));
+    id
+}
+
+pub fn remove_record_hook(id: LogHookId) -> bool {
+    let mut hooks = LOG_HOOKS.write().unwrap();
+    let before = hooks.len();
+    hooks.retain(|(hook_id, _, _)| *hook_id != id);
+    hooks.len() != before
+}
+
+pub(crate) fn run_record_hooks(record: &LogRecord) {
+    if IN_LOG_HOOK.with(Cell::get) {
+        return;
+    }
+    let hooks: Vec<LogHook> = LOG_HOOKS
+        .read()
+        .unwrap()
+        .iter()
+        .filter(|(_, filter, _)| filter.matches(record))
+        .map(|(_, _, hook)| hook.clone())
+        .collect();
+    if hooks.is_empty() {
+        return;
+    }
+
+    IN_LOG_HOOK.with(|in_hook| in_hook.set(true));
+    for hook in hooks {
+        if catch_unwind(AssertUnwindSafe(|| hook(record))).is_err() {
+            eprintln!("Log record hook panicked on record from {}", record.logger_name);
+        }
+    }
+    IN_LOG_HOOK.with(|in_hook| in_hook.set(false));
+}
@@ -0,0 +1,80 @@
+#[cfg(test)]
+use super::rs_box_log;
+#[cfg(test)]
+use super::rs_box_log_test::{lock_global_config, test_log_dir};
+#[cfg(test)]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn test_hook_runs_for_matching_records_after_redaction() {
+    let seen = Arc::new(Mutex::new(Vec::new()));
+    let hook_seen = seen.clone();
+    let id = rs_box_log::on_record(
+        rs_box_log::LogRecordFilter::new()
+            .with_levels(&[rs_box_log::LogLevel::LogLevelError])
+            .with_logger("hookpool.stratum"),
+        move |record| hook_seen.lock().unwrap().push((record.logger_name.clone(), record.message.clone())),
+    );
+
+    let config = rs_box_log::LogConfig::new("hookpool", false, "./logs", rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_redactor(rs_box_log::LogRedactor::new().with_key("password"));
+    let stratum = rs_box_log::LoggerManager::with_root_config("hookpool.stratum.eth", &config);
+    let stratum_like = rs_box_log::LoggerManager::with_root_config("hookpool.stratumx", &config);
+    stratum.log_error_f("login failed password=hunter2");
+    stratum.log_warning_f("not an error");
+    stratum_like.log_error_f("different module");
+
+    assert!(rs_box_log::remove_record_hook(id));
+    stratum.log_error_f("after removal");
+    assert!(!rs_box_log::remove_record_hook(id));
+
+    assert_eq!(
+        *seen.lock().unwrap(),
+        vec![("hookpool.stratum.eth".to_string(), "login failed password=******".to_string())]
+    );
+}
+
+#[test]
+fn test_panicking_hook_is_isolated() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let hook_calls = calls.clone();
+    let panicking = rs_box_log::on_record(rs_box_log::LogRecordFilter::new().with_logger("hookpanic"), |_| {
+        panic!("hook failure")
+    });
+    let counting = rs_box_log::on_record(rs_box_log::LogRecordFilter::new().with_logger("hookpanic"), move |_| {
+        hook_calls.fetch_add(1, Ordering::SeqCst);
+    });
+
+    let config = rs_box_log::LogConfig::new("hookpanic", false, "./logs", rs_box_log::LogLevel::LogLevelDebug, 7);
+    let logger = rs_box_log::LoggerManager::with_config(config);
+    logger.log_info_f("first");
+    logger.log_info_f("second");
+
+    rs_box_log::remove_record_hook(panicking);
+    rs_box_log::remove_record_hook(counting);
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_hook_may_log_through_same_logger() {
+    let _global = lock_global_config();
+    let log_dir = test_log_dir("hook_reentrant");
+    let config = rs_box_log::LogConfig::new("hook_reentrant", true, &log_dir, rs_box_log::LogLevel::LogLevelInfo, 7);
+    let _guard = rs_box_log::setup_log_tools_with_config(config);
+    let logger = rs_box_log::LoggerManager::get("hook_reentrant_named");
+
+    let hook_logger = logger.clone();
+    let id = rs_box_log::on_record(rs_box_log::LogRecordFilter::new().with_logger("hook_reentrant_named"), move |record| {
+        // A config change makes the nested call refresh the logger's state
+        rs_box_log::update_log_config(None, None, Some(8));
+        hook_logger.log_info_f(&format!("seen {}", record.message));
+    });
+    logger.log_info_f("share accepted");
+    rs_box_log::remove_record_hook(id);
+
+    let named = rs_box_log::LogConfig::new("hook_reentrant_named", true, &log_dir, rs_box_log::LogLevel::LogLevelInfo, 7);
+    let content = std::fs::read_to_string(&named.log_files()[0].path).unwrap();
+    assert!(content.contains("[share accepted]") && content.contains("[seen share accepted]"));
+}
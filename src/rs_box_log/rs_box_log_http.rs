@@ -0,0 +1,410 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use super::rs_box_log_metrics::record_dropped;
+
+/// `http://host:port/path` target of an exporter. TLS is not built in; put a local
+/// collector or proxy in front of `https` endpoints.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct HttpEndpoint {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl HttpEndpoint {
+    /// Parses `url`, using `default_path` when it has none
+    pub(crate) fn parse(url: &str, default_path: &str) -> Result<Self, String> {
+        let rest = match url.strip_prefix("http://") {
+            Some(rest) => rest,
+            None if url.starts_with("https://") => {
+                return Err(format!("{} needs TLS, which is not supported; use an http:// endpoint", url));
+            }
+            None => return Err(format!("{} is not an http:// url", url)),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(index) if index + 1 < rest.len() => (&rest[..index], rest[index..].to_string()),
+            Some(index) => (&rest[..index], default_path.to_string()),
+            None => (rest, default_path.to_string()),
+        };
+        // IPv6 hosts come in brackets: http://[::1]:4318/
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(bracketed) => {
+                let (host, rest) = bracketed.split_once(']').ok_or_else(|| format!("unclosed '[' in {}", url))?;
+                match rest.strip_prefix(':') {
+                    Some(port) => (host, port),
+                    None if rest.is_empty() => (host, ""),
+                    None => return Err(format!("invalid port in {}", url)),
+                }
+            }
+            None => authority.rsplit_once(':').unwrap_or((authority, "")),
+        };
+        let port = match port {
+            "" => 80,
+            port => port.parse().map_err(|_| format!("invalid port in {}", url))?,
+        };
+        if host.is_empty() {
+            return Err(format!("missing host in {}", url));
+        }
+        if host.contains(':') && !authority.starts_with('[') {
+            return Err(format!("IPv6 address in {} must be put in brackets", url));
+        }
+        Ok(HttpEndpoint {
+            host: host.to_string(),
+            port,
+            path,
+        })
+    }
+
+    /// `host:port` as written in the `Host` header, with IPv6 addresses in brackets
+    pub(crate) fn authority(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+
+    pub(crate) fn socket_addr(&self) -> io::Result<SocketAddr> {
+        (self.host.as_str(), self.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("cannot resolve {}", self.host)))
+    }
+
+    /// Sends a POST with `Connection: close` and returns the response status code
+    pub(crate) fn post(&self, headers: &[(String, String)], body: &[u8], timeout: Duration) -> io::Result<u16> {
+        self.post_with_body(headers, body, timeout).map(|(status, _)| status)
+    }
+
+    /// Like `post`, but also returns the response body, decoded if it came chunked
+    pub(crate) fn post_with_body(
+        &self,
+        headers: &[(String, String)],
+        body: &[u8],
+        timeout: Duration,
+    ) -> io::Result<(u16, String)> {
+        let address = self.socket_addr()?;
+        let mut stream = TcpStream::connect_timeout(&address, timeout)?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+
+        let mut request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+            self.path,
+            self.authority(),
+            body.len()
+        );
+        for (name, value) in headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes())?;
+        stream.write_all(body)?;
+        stream.flush()?;
+
+        let mut reader = BufReader::new(stream);
+        let mut status_line = String::new();
+        reader.read_line(&mut status_line)?;
+        let status = status_line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("bad status line {:?}", status_line)))?;
+        let mut chunked = false;
+        loop {
+            let mut header = Vec::new();
+            if reader.read_until(b'\n', &mut header)? == 0 {
+                break;
+            }
+            let header = String::from_utf8_lossy(&header);
+            if header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':')
+                && name.trim().eq_ignore_ascii_case("transfer-encoding")
+            {
+                chunked = value.to_ascii_lowercase().contains("chunked");
+            }
+        }
+        let mut response = Vec::new();
+        // Read to the end so the server sees a clean close
+        let _ = reader.read_to_end(&mut response);
+        if chunked {
+            response = decode_chunked(&response)?;
+        }
+        Ok((status, String::from_utf8_lossy(&response).into_owned()))
+    }
+}
+
+/// Joins the chunks of a `Transfer-Encoding: chunked` body, ignoring extensions and trailers
+fn decode_chunked(mut data: &[u8]) -> io::Result<Vec<u8>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed chunked response body");
+    let mut body = Vec::new();
+    loop {
+        let line_end = data.windows(2).position(|pair| pair == b"\r\n").ok_or_else(invalid)?;
+        let size_line = std::str::from_utf8(&data[..line_end]).map_err(|_| invalid())?;
+        let size = size_line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16).map_err(|_| invalid())?;
+        data = &data[line_end + 2..];
+        if size == 0 {
+            return Ok(body);
+        }
+        body.extend_from_slice(data.get(..size).ok_or_else(invalid)?);
+        data = data[size..].strip_prefix(b"\r\n").ok_or_else(invalid)?;
+    }
+}
+
+/// How an exporter batches and retries
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct BatchSettings {
+    pub(crate) max_batch: usize,
+    pub(crate) flush_interval: Duration,
+    pub(crate) queue_capacity: usize,
+    pub(crate) max_retries: u32,
+    pub(crate) retry_backoff: Duration,
+}
+
+impl Default for BatchSettings {
+    fn default() -> Self {
+        BatchSettings {
+            max_batch: 512,
+            flush_interval: Duration::from_secs(1),
+            queue_capacity: 8192,
+            max_retries: 3,
+            retry_backoff: Duration::from_millis(200),
+        }
+    }
+}
+
+/// Outcome of one export attempt
+pub(crate) enum ExportResult {
+    Exported,
+    /// Worth another attempt, e.g. a connection error or a 5xx/429 response
+    Retry(String),
+    /// Only the items at these indices of the batch need another attempt, e.g. the
+    /// entries a bulk request rejected; the others are done with
+    RetryItems(Vec<usize>, String),
+    /// Will fail again, e.g. a 4xx response; the batch is dropped
+    Failed(String),
+}
+
+impl ExportResult {
+    /// Maps an HTTP status (or transport error) to an export result
+    pub(crate) fn from_status(result: io::Result<u16>) -> Self {
+        match result {
+            Ok(status) if (200..300).contains(&status) => ExportResult::Exported,
+            Ok(status) if status == 429 || status >= 500 => ExportResult::Retry(format!("HTTP status {}", status)),
+            Ok(status) => ExportResult::Failed(format!("HTTP status {}", status)),
+            Err(e) => ExportResult::Retry(e.to_string()),
+        }
+    }
+}
+
+pub(crate) type BatchFallback<T> = Arc<dyn Fn(Vec<T>) + Send + Sync>;
+
+enum BatchMessage<T> {
+    Item(T),
+    Flush(mpsc::Sender<()>),
+}
+
+/// Queues items for a background thread that exports them in batches, retrying
+/// with exponential backoff. Items that cannot be queued or exported go to the
+/// fallback if there is one, otherwise they count as dropped.
+pub(crate) struct LogBatcher<T: Send + 'static> {
+    sender: Mutex<Option<SyncSender<BatchMessage<T>>>>,
+    worker: Mutex<Option<JoinHandle<()>>>,
+    fallback: Option<BatchFallback<T>>,
+    /// Items that found the queue full, handed to `fallback` a batch at a time
+    overflow: Mutex<Vec<T>>,
+    max_batch: usize,
+}
+
+impl<T: Send + 'static> LogBatcher<T> {
+    /// `export` gets each batch; it is also called with what is pending when the batcher drops
+    pub(crate) fn start<F>(name: &str, settings: BatchSettings, export: F) -> io::Result<Self>
+    where
+        F: FnMut(&[T]) -> ExportResult + Send + 'static,
+    {
+        LogBatcher::start_with_fallback(name, settings, export, None)
+    }
+
+    /// Like `start`, but a batch still failing with `ExportResult::Retry` after the last
+    /// retry goes to `fallback` instead of being dropped, and so do items pushed while
+    /// the queue is full. Those may reach the server out of order.
+    pub(crate) fn start_with_fallback<F>(
+        name: &str,
+        settings: BatchSettings,
+        export: F,
+        fallback: Option<BatchFallback<T>>,
+    ) -> io::Result<Self>
+    where
+        F: FnMut(&[T]) -> ExportResult + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel(settings.queue_capacity);
+        let worker_fallback = fallback.clone();
+        let worker = std::thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || run_batcher(receiver, settings, export, worker_fallback))?;
+        Ok(LogBatcher {
+            sender: Mutex::new(Some(sender)),
+            worker: Mutex::new(Some(worker)),
+            fallback,
+            overflow: Mutex::new(Vec::new()),
+            max_batch: settings.max_batch,
+        })
+    }
+
+    pub(crate) fn push(&self, item: T) {
+        let result = match *self.sender.lock().unwrap() {
+            Some(ref sender) => sender.try_send(BatchMessage::Item(item)),
+            None => {
+                record_dropped();
+                return;
+            }
+        };
+        match result {
+            Ok(()) => {}
+            Err(TrySendError::Full(BatchMessage::Item(item))) if self.fallback.is_some() => {
+                let mut overflow = self.overflow.lock().unwrap();
+                overflow.push(item);
+                if overflow.len() >= self.max_batch {
+                    let items = std::mem::take(&mut *overflow);
+                    drop(overflow);
+                    self.spill(items);
+                }
+            }
+            Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => record_dropped(),
+        }
+    }
+
+    /// Hands the items left over from a full queue to the fallback
+    fn spill_overflow(&self) {
+        let items = std::mem::take(&mut *self.overflow.lock().unwrap());
+        if !items.is_empty() {
+            self.spill(items);
+        }
+    }
+
+    fn spill(&self, items: Vec<T>) {
+        if let Some(ref fallback) = self.fallback {
+            fallback(items);
+        }
+    }
+
+    /// Exports everything queued so far, waiting at most `timeout`
+    pub(crate) fn flush(&self, timeout: Duration) {
+        self.spill_overflow();
+        let (done, finished) = mpsc::channel();
+        {
+            let sender = self.sender.lock().unwrap();
+            let Some(ref sender) = *sender else {
+                return;
+            };
+            if sender.send(BatchMessage::Flush(done)).is_err() {
+                return;
+            }
+        }
+        let _ = finished.recv_timeout(timeout);
+    }
+}
+
+impl<T: Send + 'static> Drop for LogBatcher<T> {
+    fn drop(&mut self) {
+        // Closing the channel makes the worker export what is left and exit
+        self.sender.lock().unwrap().take();
+        self.spill_overflow();
+        if let Some(worker) = self.worker.lock().unwrap().take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn run_batcher<T, F>(
+    receiver: Receiver<BatchMessage<T>>,
+    settings: BatchSettings,
+    mut export: F,
+    fallback: Option<BatchFallback<T>>,
+) where
+    F: FnMut(&[T]) -> ExportResult,
+{
+    let mut batch = Vec::new();
+    let mut deadline = Instant::now() + settings.flush_interval;
+    loop {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match receiver.recv_timeout(timeout) {
+            Ok(BatchMessage::Item(item)) => {
+                batch.push(item);
+                if batch.len() >= settings.max_batch {
+                    export_with_retry(&mut batch, &settings, &mut export, &fallback);
+                }
+            }
+            Ok(BatchMessage::Flush(done)) => {
+                export_with_retry(&mut batch, &settings, &mut export, &fallback);
+                let _ = done.send(());
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                export_with_retry(&mut batch, &settings, &mut export, &fallback);
+                deadline = Instant::now() + settings.flush_interval;
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                export_with_retry(&mut batch, &settings, &mut export, &fallback);
+                return;
+            }
+        }
+    }
+}
+
+fn export_with_retry<T, F>(
+    batch: &mut Vec<T>,
+    settings: &BatchSettings,
+    export: &mut F,
+    fallback: &Option<BatchFallback<T>>,
+) where
+    F: FnMut(&[T]) -> ExportResult,
+{
+    if batch.is_empty() {
+        return;
+    }
+    let mut backoff = settings.retry_backoff;
+    let mut attempt = 0;
+    let error = loop {
+        let error = match export(batch) {
+            ExportResult::Exported => {
+                batch.clear();
+                return;
+            }
+            ExportResult::Failed(e) => break e,
+            ExportResult::Retry(e) => e,
+            ExportResult::RetryItems(indices, e) => {
+                let mut index = 0;
+                batch.retain(|_| {
+                    index += 1;
+                    indices.contains(&(index - 1))
+                });
+                if batch.is_empty() {
+                    return;
+                }
+                e
+            }
+        };
+        if attempt < settings.max_retries {
+            attempt += 1;
+            std::thread::sleep(backoff);
+            backoff *= 2;
+            continue;
+        }
+        if let Some(fallback) = fallback {
+            fallback(std::mem::take(batch));
+            return;
+        }
+        break error;
+    };
+    eprintln!("Failed to export {} log records: {}", batch.len(), error);
+    for _ in batch.drain(..) {
+        record_dropped();
+    }
+}
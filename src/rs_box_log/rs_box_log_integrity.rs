@@ -0,0 +1,507 @@
+use std::fmt;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use super::rs_box_log_format::json_escape;
+use super::rs_box_log_layout::LogFileEntry;
+use super::rs_box_log_view::json_unescape;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const MAC_LEN: usize = 32;
+const TEXT_TAG_PREFIX: &str = "# chain seq=";
+const JSON_TAG_PREFIX: &str = "{\"chain\":{\"seq\":";
+
+/// Key for the tamper evident mode of log files, see `LogConfig::with_integrity`.
+///
+/// Each run writing a file begins with a start tag naming the file (relative to the log
+/// directory) and the last tag of the file written before it. After every record the file
+/// gets a tag line holding an HMAC-SHA256 over the record and the tag before it; a graceful
+/// exit or a rotation adds a seal tag. Without the key, no record can be changed, removed,
+/// reordered or appended, and no file renamed, in a way `verify_log_file` accepts;
+/// `verify_log_files` also catches removed, swapped or cut off files.
+#[derive(Clone)]
+pub struct LogIntegrity {
+    key: Arc<Vec<u8>>,
+}
+
+impl LogIntegrity {
+    pub fn new(key: &[u8]) -> Self {
+        LogIntegrity { key: Arc::new(key.to_vec()) }
+    }
+
+    /// Reads the key from a file, ignoring surrounding whitespace
+    pub fn from_key_file(path: &Path) -> Result<Self, String> {
+        let key = fs::read(path).map_err(|e| format!("failed to read key file {}: {}", path.display(), e))?;
+        let key = key.trim_ascii();
+        if key.is_empty() {
+            return Err(format!("key file {} is empty", path.display()));
+        }
+        Ok(LogIntegrity::new(key))
+    }
+
+    fn mac(&self, previous: &[u8; MAC_LEN], seq: u64, kind: &TagKind, data: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(previous);
+        mac.update(&seq.to_be_bytes());
+        match kind {
+            TagKind::Record => mac.update(b"r"),
+            TagKind::Seal => mac.update(b"s"),
+            TagKind::Start { link, file } => {
+                mac.update(b"b");
+                mac.update(link);
+                mac.update(file.as_bytes());
+            }
+        }
+        mac.update(data);
+        mac
+    }
+}
+
+impl fmt::Debug for LogIntegrity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LogIntegrity(hmac-sha256)")
+    }
+}
+
+/// What a log file handle needs to chain the records of its file
+pub(crate) struct LogChainSettings<'a> {
+    pub(crate) integrity: &'a LogIntegrity,
+    /// Tags as JSON lines
+    pub(crate) json: bool,
+    /// Start tags name files relative to this directory
+    pub(crate) log_dir: &'a Path,
+    /// The file written before, whose last tag a new file links to
+    pub(crate) previous_file: Option<PathBuf>,
+    /// The file name holds the process id, so no other process writes to it
+    pub(crate) per_process: bool,
+}
+
+/// Chain position of an open log file
+#[derive(Clone)]
+pub(crate) struct LogChain {
+    integrity: LogIntegrity,
+    json: bool,
+    per_process: bool,
+    log_dir: PathBuf,
+    /// The file as named in start tags
+    file: String,
+    seq: u64,
+    previous: [u8; MAC_LEN],
+    /// Last tag of the file before, put in the start tag of a new file
+    link: [u8; MAC_LEN],
+    /// Whether this run wrote its start tag yet
+    started: bool,
+    sealed: bool,
+}
+
+impl LogChain {
+    /// Continues the chain already in `path`, or starts a new one for an empty or new file
+    pub(crate) fn resume(settings: &LogChainSettings, path: &Path) -> Self {
+        let link = settings
+            .previous_file
+            .as_deref()
+            .and_then(last_tag)
+            .map_or([0; MAC_LEN], |tag| tag.mac);
+        let mut chain = LogChain {
+            integrity: settings.integrity.clone(),
+            json: settings.json,
+            per_process: settings.per_process,
+            log_dir: settings.log_dir.to_path_buf(),
+            file: String::new(),
+            seq: 0,
+            previous: [0; MAC_LEN],
+            link,
+            started: false,
+            sealed: false,
+        };
+        chain.resume_file(path);
+        chain
+    }
+
+    /// Whether records chained with `integrity` in this format fit into this chain
+    pub(crate) fn continues(&self, integrity: &LogIntegrity, json: bool) -> bool {
+        self.json == json && self.integrity.key == integrity.key
+    }
+
+    /// Whether only this process writes to the chained file
+    pub(crate) fn is_per_process(&self) -> bool {
+        self.per_process
+    }
+
+    /// Starts over for a newly opened file, linked to the end of this one
+    pub(crate) fn restart(&mut self, path: &Path) {
+        self.link = if self.seq > 0 { self.previous } else { [0; MAC_LEN] };
+        self.resume_file(path);
+    }
+
+    /// Moves the chain to the end of `path`, keeping the link to the file before
+    fn resume_file(&mut self, path: &Path) {
+        self.file = relative_file_name(&self.log_dir, path);
+        self.seq = 0;
+        self.previous = [0; MAC_LEN];
+        self.started = false;
+        self.sealed = false;
+        if let Some(tag) = last_tag(path) {
+            self.seq = tag.seq + 1;
+            self.previous = tag.mac;
+            self.sealed = tag.kind == TagKind::Seal;
+        }
+    }
+
+    /// The start tag to write ahead of the first record of this run
+    pub(crate) fn start(&mut self) -> Option<String> {
+        if self.started {
+            return None;
+        }
+        self.started = true;
+        self.sealed = false;
+        // A resumed file continues its own chain; only a new one links to the file before
+        let link = if self.seq == 0 { self.link } else { self.previous };
+        let file = self.file.clone();
+        Some(self.next_tag(TagKind::Start { link, file }, &[]))
+    }
+
+    /// The tag line to write right after `data`
+    pub(crate) fn tag(&mut self, data: &[u8]) -> String {
+        self.sealed = false;
+        self.next_tag(TagKind::Record, data)
+    }
+
+    /// The seal tag, unless this run wrote nothing or the file is sealed already
+    pub(crate) fn seal(&mut self) -> Option<String> {
+        if self.sealed || !self.started {
+            return None;
+        }
+        self.sealed = true;
+        Some(self.next_tag(TagKind::Seal, &[]))
+    }
+
+    fn next_tag(&mut self, kind: TagKind, data: &[u8]) -> String {
+        let mac_bytes: [u8; MAC_LEN] = self.integrity.mac(&self.previous, self.seq, &kind, data).finalize().into_bytes().into();
+        let mac = to_hex(&mac_bytes);
+        let line = match (self.json, &kind) {
+            (false, TagKind::Record) => format!("{}{} mac={}\n", TEXT_TAG_PREFIX, self.seq, mac),
+            (false, TagKind::Seal) => format!("{}{} seal mac={}\n", TEXT_TAG_PREFIX, self.seq, mac),
+            (false, TagKind::Start { link, file }) => {
+                format!("{}{} start prev={} mac={} file={}\n", TEXT_TAG_PREFIX, self.seq, to_hex(link), mac, file)
+            }
+            (true, TagKind::Record) => format!("{}{},\"mac\":\"{}\"}}}}\n", JSON_TAG_PREFIX, self.seq, mac),
+            (true, TagKind::Seal) => format!("{}{},\"seal\":true,\"mac\":\"{}\"}}}}\n", JSON_TAG_PREFIX, self.seq, mac),
+            (true, TagKind::Start { link, file }) => format!(
+                "{}{},\"start\":\"{}\",\"mac\":\"{}\",\"file\":{}}}}}\n",
+                JSON_TAG_PREFIX,
+                self.seq,
+                to_hex(link),
+                mac,
+                json_escape(file)
+            ),
+        };
+        self.seq += 1;
+        self.previous = mac_bytes;
+        line
+    }
+}
+
+/// `path` below `log_dir` with `/` separators, as named in start tags
+fn relative_file_name(log_dir: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(log_dir).unwrap_or(path);
+    let parts: Vec<String> = relative
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect();
+    parts.join("/")
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TagKind {
+    Record,
+    Seal,
+    /// First tag of a run: the file it belongs to and the tag the run continues from
+    Start { link: [u8; MAC_LEN], file: String },
+}
+
+struct ChainTag {
+    seq: u64,
+    kind: TagKind,
+    mac: [u8; MAC_LEN],
+}
+
+/// Bytes `last_tag` reads at a time; a line longer than this is no tag and is not kept
+const TAG_SCAN_CHUNK: u64 = 64 * 1024;
+
+/// The last tag in `path`, if it has any. The file is read backwards in chunks, so
+/// resuming a large file costs about one chunk rather than the whole file.
+fn last_tag(path: &Path) -> Option<ChainTag> {
+    let mut file = fs::File::open(path).ok()?;
+    let mut end = file.metadata().ok()?.len();
+    // Start of the line that the chunk read before began in, up to its line break
+    let mut carry = Vec::new();
+    // That line is longer than a chunk, so its start is not kept
+    let mut overlong = false;
+    while end > 0 {
+        let start = end.saturating_sub(TAG_SCAN_CHUNK);
+        let mut chunk = vec![0; (end - start) as usize];
+        file.seek(SeekFrom::Start(start)).ok()?;
+        file.read_exact(&mut chunk).ok()?;
+        end = start;
+        let first_line = if start == 0 {
+            0
+        } else if let Some(position) = chunk.iter().position(|b| *b == b'\n') {
+            position + 1
+        } else {
+            carry.clear();
+            overlong = true;
+            continue;
+        };
+
+        let mut lines = chunk.split_off(first_line);
+        if !overlong {
+            lines.extend_from_slice(&carry);
+        }
+        let mut lines = lines.split(|b| *b == b'\n').rev();
+        if overlong {
+            lines.next();
+        }
+        if let Some(tag) = lines.find_map(parse_tag) {
+            return Some(tag);
+        }
+        carry = chunk;
+        overlong = false;
+    }
+    None
+}
+
+/// Reads a tag line in either output format, with or without its line end
+fn parse_tag(line: &[u8]) -> Option<ChainTag> {
+    let line = std::str::from_utf8(line).ok()?.trim_end_matches(['\n', '\r']);
+    let (seq, kind, mac) = if let Some(rest) = line.strip_prefix(TEXT_TAG_PREFIX) {
+        let (seq, rest) = rest.split_once(' ')?;
+        if let Some(mac) = rest.strip_prefix("seal mac=") {
+            (seq, TagKind::Seal, mac)
+        } else if let Some(rest) = rest.strip_prefix("start prev=") {
+            let (link, rest) = rest.split_once(" mac=")?;
+            let (mac, file) = rest.split_once(" file=")?;
+            let kind = TagKind::Start {
+                link: from_hex(link)?,
+                file: file.to_string(),
+            };
+            (seq, kind, mac)
+        } else {
+            (seq, TagKind::Record, rest.strip_prefix("mac=")?)
+        }
+    } else {
+        let rest = line.strip_prefix(JSON_TAG_PREFIX)?;
+        let (seq, rest) = rest.split_once(',')?;
+        if let Some(rest) = rest.strip_prefix("\"seal\":true,\"mac\":\"") {
+            (seq, TagKind::Seal, rest.strip_suffix("\"}}")?)
+        } else if let Some(rest) = rest.strip_prefix("\"start\":\"") {
+            let (link, rest) = rest.split_once("\",\"mac\":\"")?;
+            let (mac, file) = rest.split_once("\",\"file\":")?;
+            let kind = TagKind::Start {
+                link: from_hex(link)?,
+                file: json_unescape(file.strip_suffix("}}")?)?,
+            };
+            (seq, kind, mac)
+        } else {
+            (seq, TagKind::Record, rest.strip_prefix("\"mac\":\"")?.strip_suffix("\"}}")?)
+        }
+    };
+    Some(ChainTag {
+        seq: seq.parse().ok()?,
+        kind,
+        mac: from_hex(mac)?,
+    })
+}
+
+/// Whether `line` is a well-formed tag written by the integrity mode
+pub(crate) fn is_chain_line(line: &str) -> bool {
+    (line.starts_with(TEXT_TAG_PREFIX) || line.starts_with(JSON_TAG_PREFIX)) && parse_tag(line.as_bytes()).is_some()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    hex
+}
+
+fn from_hex(hex: &str) -> Option<[u8; MAC_LEN]> {
+    if hex.len() != MAC_LEN * 2 {
+        return None;
+    }
+    let mut bytes = [0; MAC_LEN];
+    for (index, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(index * 2..index * 2 + 2)?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+/// First problem `verify_log_file` found; lines count from 1
+#[derive(Clone, Debug, PartialEq)]
+pub enum LogIntegrityIssue {
+    /// The record starting at `line` does not match its tag
+    LogIntegrityIssueModified { line: usize },
+    /// The tag at `line` is not the next one: records were removed, reordered or copied in
+    LogIntegrityIssueOutOfSequence { line: usize, expected_seq: u64, found_seq: u64 },
+    /// Data from `line` to the end has no tag: appended without the key, or a torn write
+    LogIntegrityIssueUnsigned { line: usize },
+    /// The file does not end with a seal: cut off, or the writer is still running or crashed
+    LogIntegrityIssueUnsealed,
+    /// The start tag at `line` was written for `file`: renamed, moved or copied over another file
+    LogIntegrityIssueWrongFile { line: usize, file: String },
+    /// The file does not start where the one before it ends: a file in between was removed,
+    /// or the one before was cut off or swapped (only reported by `verify_log_files`)
+    LogIntegrityIssueNotLinked,
+}
+
+impl fmt::Display for LogIntegrityIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogIntegrityIssue::LogIntegrityIssueModified { line } => write!(f, "record at line {} was modified", line),
+            LogIntegrityIssue::LogIntegrityIssueOutOfSequence { line, expected_seq, found_seq } => write!(
+                f,
+                "records missing or out of order at line {}: expected seq {}, found {}",
+                line, expected_seq, found_seq
+            ),
+            LogIntegrityIssue::LogIntegrityIssueUnsigned { line } => write!(f, "unsigned data from line {}", line),
+            LogIntegrityIssue::LogIntegrityIssueUnsealed => write!(f, "file is not sealed, it may be truncated"),
+            LogIntegrityIssue::LogIntegrityIssueWrongFile { line, file } => {
+                write!(f, "run starting at line {} was written to {}, the file was renamed or replaced", line, file)
+            }
+            LogIntegrityIssue::LogIntegrityIssueNotLinked => {
+                write!(f, "does not continue the previous file, which was removed, cut off or replaced")
+            }
+        }
+    }
+}
+
+/// Result of `verify_log_file`
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogIntegrityReport {
+    pub path: PathBuf,
+    /// Records verified before the first issue
+    pub records: u64,
+    /// Runs (process starts or reopenings) that wrote to the file, from its start tags
+    pub runs: u64,
+    pub sealed: bool,
+    /// Hex MAC of the previous file's last tag this file links to, `None` for a first file
+    pub link: Option<String>,
+    /// Hex MAC of the last verified tag, which the next file links to
+    pub last_mac: Option<String>,
+    pub issue: Option<LogIntegrityIssue>,
+}
+
+impl LogIntegrityReport {
+    pub fn is_intact(&self) -> bool {
+        self.issue.is_none()
+    }
+}
+
+/// Checks the chain of a log file written with `LogConfig::with_integrity` and `integrity`'s key.
+/// The file currently written to is reported as unsealed until a graceful exit or rotation.
+///
+/// A file cut back to the seal of an earlier run still verifies on its own; only fewer `runs`
+/// or the link of the next file (see `verify_log_files`) give it away.
+pub fn verify_log_file(path: &Path, integrity: &LogIntegrity) -> io::Result<LogIntegrityReport> {
+    let content = fs::read(path)?;
+    let mut report = LogIntegrityReport {
+        path: path.to_path_buf(),
+        records: 0,
+        runs: 0,
+        sealed: false,
+        link: None,
+        last_mac: None,
+        issue: None,
+    };
+    let mut previous = [0; MAC_LEN];
+    let mut seq = 0;
+    let mut data: Vec<u8> = Vec::new();
+    let mut data_line = 0;
+    // A failed tag may be a message line that only looks like one; it is confirmed
+    // once no later tag makes up for it
+    let mut failed: Option<LogIntegrityIssue> = None;
+
+    for (index, line) in content.split_inclusive(|b| *b == b'\n').enumerate() {
+        if let Some(tag) = parse_tag(line) {
+            if tag.seq == seq && integrity.mac(&previous, seq, &tag.kind, &data).verify_slice(&tag.mac).is_ok() {
+                previous = tag.mac;
+                seq += 1;
+                report.sealed = tag.kind == TagKind::Seal;
+                match tag.kind {
+                    TagKind::Record => report.records += 1,
+                    TagKind::Seal => {}
+                    TagKind::Start { link, file } => {
+                        if !path.ends_with(&file) {
+                            report.issue = Some(LogIntegrityIssue::LogIntegrityIssueWrongFile { line: index + 1, file });
+                            return Ok(report);
+                        }
+                        if tag.seq == 0 && link != [0; MAC_LEN] {
+                            report.link = Some(to_hex(&link));
+                        }
+                        report.runs += 1;
+                    }
+                }
+                report.last_mac = Some(to_hex(&previous));
+                data.clear();
+                failed = None;
+                continue;
+            }
+            failed.get_or_insert(if tag.seq == seq {
+                LogIntegrityIssue::LogIntegrityIssueModified {
+                    line: if data.is_empty() { index + 1 } else { data_line },
+                }
+            } else {
+                LogIntegrityIssue::LogIntegrityIssueOutOfSequence {
+                    line: index + 1,
+                    expected_seq: seq,
+                    found_seq: tag.seq,
+                }
+            });
+        }
+        if data.is_empty() {
+            data_line = index + 1;
+        }
+        data.extend_from_slice(line);
+        report.sealed = false;
+    }
+
+    report.issue = if failed.is_some() {
+        failed
+    } else if !data.is_empty() {
+        Some(LogIntegrityIssue::LogIntegrityIssueUnsigned { line: data_line })
+    } else if !report.sealed {
+        Some(LogIntegrityIssue::LogIntegrityIssueUnsealed)
+    } else {
+        None
+    };
+    Ok(report)
+}
+
+/// `verify_log_file` for each of a logger's files, oldest first as `LogConfig::log_files`
+/// lists them, also checking that every file starts where the one before it ends (the one
+/// before written by the same process for `{pid}` patterns). The oldest file is not checked
+/// against anything, as retention may have removed the ones before it.
+pub fn verify_log_files(files: &[LogFileEntry], integrity: &LogIntegrity) -> io::Result<Vec<LogIntegrityReport>> {
+    let mut reports: Vec<LogIntegrityReport> = Vec::with_capacity(files.len());
+    for (index, file) in files.iter().enumerate() {
+        let mut report = verify_log_file(&file.path, integrity)?;
+        let before = files[..index].iter().rposition(|other| other.pid == file.pid);
+        if let Some(before) = before
+            && report.link != reports[before].last_mac
+            && matches!(report.issue, None | Some(LogIntegrityIssue::LogIntegrityIssueUnsealed))
+        {
+            report.issue = Some(LogIntegrityIssue::LogIntegrityIssueNotLinked);
+        }
+        reports.push(report);
+    }
+    Ok(reports)
+}
@@ -0,0 +1,287 @@
+#[cfg(test)]
+use super::rs_box_log;
+#[cfg(test)]
+use super::rs_box_log_integrity::{LogChain, LogChainSettings};
+#[cfg(test)]
+use super::rs_box_log_test::test_log_dir;
+#[cfg(test)]
+use chrono::{DateTime, Duration};
+#[cfg(test)]
+use std::path::PathBuf;
+#[cfg(test)]
+use std::sync::Arc;
+
+#[cfg(test)]
+fn chained_config(project: &str, log_dir: &str, key: &[u8]) -> rs_box_log::LogConfig {
+    rs_box_log::LogConfig::new(project, true, log_dir, rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_file_header(true)
+        .with_integrity(rs_box_log::LogIntegrity::new(key))
+}
+
+/// Writes a sealed file of four records and returns its path and lines
+#[cfg(test)]
+fn sealed_file(project: &str, log_dir: &str) -> (PathBuf, Vec<String>) {
+    let config = chained_config(project, log_dir, b"payout key");
+    let logger = rs_box_log::LoggerManager::with_config(config.clone());
+    for index in 1..=4 {
+        logger.log_info_f(&format!("payout {}", index));
+    }
+    drop(logger);
+    let path = config.log_files()[0].path.clone();
+    let lines = std::fs::read_to_string(&path).unwrap().lines().map(str::to_string).collect();
+    (path, lines)
+}
+
+#[cfg(test)]
+fn verify_lines(path: &PathBuf, lines: &[String], key: &[u8]) -> rs_box_log::LogIntegrityReport {
+    std::fs::write(path, lines.iter().map(|line| format!("{}\n", line)).collect::<String>()).unwrap();
+    rs_box_log::verify_log_file(path, &rs_box_log::LogIntegrity::new(key)).unwrap()
+}
+
+#[test]
+fn test_chain_survives_restart_rotation_and_json() {
+    let log_dir = test_log_dir("integrity_chain");
+    let key = b"payout key";
+    let integrity = rs_box_log::LogIntegrity::new(key);
+    let clock = Arc::new(rs_box_log::LogManualClock::new(DateTime::parse_from_rfc3339("2024-05-17T10:30:00+00:00").unwrap()));
+    let config = chained_config("chain", &log_dir, key).with_clock(clock.clone());
+
+    let logger = rs_box_log::LoggerManager::with_config(config.clone());
+    logger.log_info_f("share accepted");
+    // Message lines that look like tags do not break the chain
+    logger.log_warning_f("multi\n# chain seq=2 mac=00\nline");
+    let path = config.log_files()[0].path.clone();
+    let report = rs_box_log::verify_log_file(&path, &integrity).unwrap();
+    // header + 2 records, the file is still open
+    assert_eq!((report.records, report.sealed), (3, false));
+    assert_eq!(report.issue, Some(rs_box_log::LogIntegrityIssue::LogIntegrityIssueUnsealed));
+
+    // A restarted process continues the chain of the same file
+    drop(logger);
+    let logger = rs_box_log::LoggerManager::with_config(config.clone());
+    logger.log_error_f("payout failed");
+    clock.advance(Duration::hours(1));
+    logger.log_info_f("next hour");
+    let report = rs_box_log::verify_log_file(&path, &integrity).unwrap();
+    assert!(report.is_intact() && report.sealed, "{:?}", report);
+    // The reopened file got a second header and a second start tag
+    assert_eq!((report.records, report.runs), (5, 2));
+    drop(logger);
+    assert!(rs_box_log::verify_log_file(&config.log_files()[1].path, &integrity).unwrap().is_intact());
+
+    // The viewer leaves the tags out
+    let entries = rs_box_log::read_log_entries(
+        &log_dir,
+        &rs_box_log::LogFileLayout::default(),
+        &["chain".to_string()],
+        &rs_box_log::LogViewFilter::new(),
+    );
+    assert_eq!(entries.len(), 4);
+    assert!(entries.iter().all(|entry| entry.line.ends_with(']')), "{:?}", entries);
+    assert!(entries[1].line.ends_with("\n# chain seq=2 mac=00\nline]"));
+
+    let json_config = chained_config("chain_json", &log_dir, key).with_output_format(rs_box_log::LogOutputFormat::LogOutputFormatJson);
+    let logger = rs_box_log::LoggerManager::with_config(json_config.clone());
+    logger.log_info_f("json record");
+    drop(logger);
+    let content = std::fs::read_to_string(&json_config.log_files()[0].path).unwrap();
+    assert!(content.starts_with("{\"chain\":{\"seq\":0,\"start\":\""));
+    assert!(content.contains("\n{\"chain\":{\"seq\":3,\"seal\":true,\"mac\":\""));
+    assert!(rs_box_log::verify_log_file(&json_config.log_files()[0].path, &integrity).unwrap().is_intact());
+}
+
+#[test]
+fn test_verifier_detects_tampering() {
+    let log_dir = test_log_dir("integrity_tamper");
+    let (path, lines) = sealed_file("tamper", &log_dir);
+    let key = b"payout key";
+    let record_line = |message: &str| lines.iter().position(|line| line.contains(message)).unwrap();
+    assert!(verify_lines(&path, &lines, key).is_intact());
+
+    let mut modified = lines.clone();
+    let payout_2 = record_line("[payout 2]");
+    modified[payout_2] = modified[payout_2].replace("payout 2", "payout 9");
+    assert_eq!(
+        verify_lines(&path, &modified, key).issue,
+        Some(rs_box_log::LogIntegrityIssue::LogIntegrityIssueModified { line: payout_2 + 1 })
+    );
+
+    let mut removed = lines.clone();
+    removed.drain(payout_2..payout_2 + 2);
+    let report = verify_lines(&path, &removed, key);
+    assert!(matches!(
+        report.issue,
+        Some(rs_box_log::LogIntegrityIssue::LogIntegrityIssueOutOfSequence { expected_seq: 3, found_seq: 4, .. })
+    ));
+    assert_eq!(report.records, 2);
+
+    let mut reordered = lines.clone();
+    reordered.swap(payout_2, payout_2 + 2);
+    reordered.swap(payout_2 + 1, payout_2 + 3);
+    assert!(matches!(
+        verify_lines(&path, &reordered, key).issue,
+        Some(rs_box_log::LogIntegrityIssue::LogIntegrityIssueOutOfSequence { .. })
+    ));
+
+    let truncated = lines[..lines.len() - 1].to_vec();
+    assert_eq!(
+        verify_lines(&path, &truncated, key).issue,
+        Some(rs_box_log::LogIntegrityIssue::LogIntegrityIssueUnsealed)
+    );
+
+    let mut appended = lines.clone();
+    appended.push(lines[payout_2].clone());
+    assert_eq!(
+        verify_lines(&path, &appended, key).issue,
+        Some(rs_box_log::LogIntegrityIssue::LogIntegrityIssueUnsigned { line: lines.len() + 1 })
+    );
+
+    assert_eq!(
+        verify_lines(&path, &lines, b"other key").issue,
+        Some(rs_box_log::LogIntegrityIssue::LogIntegrityIssueModified { line: 1 })
+    );
+}
+
+/// Writes one file per hour, the first in two runs, and returns the config and files
+#[cfg(test)]
+fn linked_files(project: &str, log_dir: &str) -> (rs_box_log::LogConfig, Vec<rs_box_log::LogFileEntry>) {
+    let clock = Arc::new(rs_box_log::LogManualClock::new(DateTime::parse_from_rfc3339("2024-05-17T10:30:00+00:00").unwrap()));
+    let config = chained_config(project, log_dir, b"payout key").with_clock(clock.clone());
+    rs_box_log::LoggerManager::with_config(config.clone()).log_info_f("first run");
+    let logger = rs_box_log::LoggerManager::with_config(config.clone());
+    logger.log_info_f("second run");
+    clock.advance(Duration::hours(1));
+    logger.log_info_f("next hour");
+    clock.advance(Duration::hours(1));
+    logger.log_info_f("third hour");
+    drop(logger);
+    let files = config.log_files();
+    assert_eq!(files.len(), 3);
+    (config, files)
+}
+
+#[test]
+fn test_files_are_linked_in_order() {
+    let log_dir = test_log_dir("integrity_links");
+    let integrity = rs_box_log::LogIntegrity::new(b"payout key");
+    let (_, files) = linked_files("links", &log_dir);
+    let reports = rs_box_log::verify_log_files(&files, &integrity).unwrap();
+    assert!(reports.iter().all(rs_box_log::LogIntegrityReport::is_intact), "{:?}", reports);
+    assert_eq!(reports[0].runs, 2);
+    assert_eq!(reports[0].link, None);
+    assert_eq!(reports[1].link, reports[0].last_mac);
+    assert_eq!(reports[2].link, reports[1].last_mac);
+    let first = std::fs::read_to_string(&files[0].path).unwrap();
+    assert!(first.starts_with("# chain seq=0 start prev="));
+    let relative = files[0].path.strip_prefix(&log_dir).unwrap();
+    assert!(first.lines().next().unwrap().ends_with(&format!(" file={}", relative.display())));
+}
+
+#[test]
+fn test_cut_back_to_an_earlier_seal_breaks_the_link() {
+    let log_dir = test_log_dir("integrity_cut");
+    let integrity = rs_box_log::LogIntegrity::new(b"payout key");
+    let (_, files) = linked_files("cut", &log_dir);
+
+    // Drop the second run of the first file, keeping the seal of the first run
+    let content = std::fs::read_to_string(&files[0].path).unwrap();
+    let first_seal = content.find(" seal mac=").unwrap();
+    let cut = first_seal + content[first_seal..].find('\n').unwrap() + 1;
+    std::fs::write(&files[0].path, &content[..cut]).unwrap();
+
+    // On its own the file still looks fine, only with one run less
+    let report = rs_box_log::verify_log_file(&files[0].path, &integrity).unwrap();
+    assert!(report.is_intact() && report.sealed);
+    assert_eq!(report.runs, 1);
+    let reports = rs_box_log::verify_log_files(&files, &integrity).unwrap();
+    assert!(reports[0].is_intact());
+    assert_eq!(reports[1].issue, Some(rs_box_log::LogIntegrityIssue::LogIntegrityIssueNotLinked));
+    assert!(reports[2].is_intact());
+}
+
+#[test]
+fn test_removed_and_swapped_files_are_detected() {
+    let log_dir = test_log_dir("integrity_swap");
+    let integrity = rs_box_log::LogIntegrity::new(b"payout key");
+    let (config, files) = linked_files("swap", &log_dir);
+    let middle = std::fs::read(&files[1].path).unwrap();
+
+    std::fs::remove_file(&files[1].path).unwrap();
+    let reports = rs_box_log::verify_log_files(&config.log_files(), &integrity).unwrap();
+    assert_eq!(reports.len(), 2);
+    assert_eq!(reports[1].issue, Some(rs_box_log::LogIntegrityIssue::LogIntegrityIssueNotLinked));
+
+    // The last file put in place of the middle one names its real path
+    std::fs::copy(&files[2].path, &files[1].path).unwrap();
+    let reports = rs_box_log::verify_log_files(&files, &integrity).unwrap();
+    assert!(matches!(
+        reports[1].issue,
+        Some(rs_box_log::LogIntegrityIssue::LogIntegrityIssueWrongFile { line: 1, .. })
+    ));
+
+    std::fs::write(&files[1].path, middle).unwrap();
+    let reports = rs_box_log::verify_log_files(&files, &integrity).unwrap();
+    assert!(reports.iter().all(rs_box_log::LogIntegrityReport::is_intact), "{:?}", reports);
+}
+
+#[test]
+fn test_process_lock_is_refused_for_chained_shared_files() {
+    let log_dir = test_log_dir("integrity_process_lock");
+    let integrity = rs_box_log::LogIntegrity::new(b"payout key");
+
+    // Processes sharing a file would each keep their own chain position
+    let locked = chained_config("locked", &log_dir, b"payout key").with_process_lock(true);
+    let logger = rs_box_log::LoggerManager::with_config(locked.clone());
+    logger.log_info_f("never chained");
+    drop(logger);
+    assert!(locked.log_files().is_empty());
+
+    // Nor can a locked config join a file chained by this process
+    let chained = chained_config("joined", &log_dir, b"payout key");
+    let logger = rs_box_log::LoggerManager::with_config(chained.clone());
+    logger.log_info_f("chained");
+    let joining = rs_box_log::LoggerManager::with_config(chained.clone().with_process_lock(true));
+    joining.log_info_f("locked record");
+    drop(joining);
+    drop(logger);
+    let path = &chained.log_files()[0].path;
+    assert!(!std::fs::read_to_string(path).unwrap().contains("locked record"));
+    assert!(rs_box_log::verify_log_file(path, &integrity).unwrap().is_intact());
+
+    // A file per process is never shared, so it may be locked and chained
+    let per_process = chained_config("per_process", &log_dir, b"payout key")
+        .with_process_lock(true)
+        .with_file_layout(rs_box_log::LogFileLayout::new("{project}/{date}_{pid}.log").unwrap());
+    let logger = rs_box_log::LoggerManager::with_config(per_process.clone());
+    logger.log_info_f("chained per process");
+    drop(logger);
+    let files = per_process.log_files();
+    assert_eq!(files.len(), 1);
+    assert!(rs_box_log::verify_log_file(&files[0].path, &integrity).unwrap().is_intact());
+}
+
+#[test]
+fn test_resume_finds_the_last_tag_from_the_end() {
+    let log_dir = test_log_dir("integrity_last_tag");
+    std::fs::create_dir_all(&log_dir).unwrap();
+    let integrity = rs_box_log::LogIntegrity::new(b"payout key");
+    let settings = LogChainSettings {
+        integrity: &integrity,
+        json: false,
+        log_dir: std::path::Path::new(&log_dir),
+        previous_file: None,
+        per_process: false,
+    };
+    let tag = format!("# chain seq=41 mac={}\n", "ab".repeat(32));
+
+    // The tag straddles the start of the last 64 KiB read
+    let path = PathBuf::from(&log_dir).join("straddle.log");
+    let filler = "f".repeat(64 * 1024 - 10);
+    std::fs::write(&path, format!("{}\n{}{}\n", "r".repeat(300_000), tag, filler)).unwrap();
+    assert!(LogChain::resume(&settings, &path).tag(b"next").starts_with("# chain seq=42 "));
+
+    // A line longer than a chunk after the tag, cut off by a crash, is passed over
+    let path = PathBuf::from(&log_dir).join("overlong.log");
+    std::fs::write(&path, format!("{}{}", tag, "x".repeat(200_000))).unwrap();
+    assert!(LogChain::resume(&settings, &path).tag(b"next").starts_with("# chain seq=42 "));
+}
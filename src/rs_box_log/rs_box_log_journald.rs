@@ -0,0 +1,228 @@
+use std::io;
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+use crate::rs_box_log::rs_box_log::{LogLevel, LogRecord, LogSink};
+use super::rs_box_log_metrics::record_dropped;
+
+/// Socket journald listens on for native protocol datagrams
+pub const JOURNALD_SOCKET_PATH: &str = "/run/systemd/journal/socket";
+
+/// Where and how `LogJournaldSink` sends records
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogJournaldConfig {
+    syslog_identifier: String,
+    socket_path: PathBuf,
+    extra_fields: Vec<(String, String)>,
+}
+
+impl LogJournaldConfig {
+    /// `project_name` becomes `SYSLOG_IDENTIFIER`, so `journalctl -t <project_name>` finds the records
+    pub fn new(project_name: &str) -> Self {
+        LogJournaldConfig {
+            syslog_identifier: project_name.to_string(),
+            socket_path: PathBuf::from(JOURNALD_SOCKET_PATH),
+            extra_fields: Vec::new(),
+        }
+    }
+
+    /// Journal socket to send to (default `/run/systemd/journal/socket`)
+    pub fn with_socket_path(mut self, socket_path: &str) -> Self {
+        self.socket_path = PathBuf::from(socket_path);
+        self
+    }
+
+    /// Static field added to every entry, e.g. `COIN_FLAG=btc`; names the sink writes
+    /// itself, such as `PRIORITY`, get a `FIELD_` prefix
+    pub fn with_field(mut self, key: &str, value: &str) -> Self {
+        self.extra_fields.push((user_field_name(key), value.to_string()));
+        self
+    }
+}
+
+/// syslog priority journald stores in `PRIORITY`
+fn journald_priority(level: LogLevel) -> u8 {
+    match level {
+        LogLevel::LogLevelError => 3,
+        LogLevel::LogLevelWarning => 4,
+        LogLevel::LogLevelInfo => 6,
+        LogLevel::LogLevelDebug | LogLevel::LogLevelTrace => 7,
+    }
+}
+
+/// A sink writing each record as one entry to the systemd journal using its native protocol.
+/// Entries larger than a datagram are passed in a sealed memfd, as journald documents;
+/// where that is not available they are reported and counted as dropped.
+pub struct LogJournaldSink {
+    config: LogJournaldConfig,
+    socket: UnixDatagram,
+}
+
+impl LogJournaldSink {
+    pub fn new(config: LogJournaldConfig) -> Result<Self, String> {
+        if !config.socket_path.exists() {
+            return Err(format!("journal socket {} does not exist", config.socket_path.display()));
+        }
+        let socket = UnixDatagram::unbound().map_err(|e| format!("failed to create journal socket: {}", e))?;
+        Ok(LogJournaldSink { config, socket })
+    }
+
+    fn send(&self, entry: &[u8]) -> io::Result<()> {
+        match self.socket.send_to(entry, &self.config.socket_path) {
+            Ok(_) => Ok(()),
+            #[cfg(target_os = "linux")]
+            Err(e) if matches!(e.raw_os_error(), Some(libc::EMSGSIZE) | Some(libc::ENOBUFS)) => {
+                send_memfd(&self.socket, &self.config.socket_path, entry)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl LogSink for LogJournaldSink {
+    fn write_record(&self, record: &LogRecord) {
+        let entry = journal_entry(record, &self.config);
+        if let Err(e) = self.send(&entry) {
+            eprintln!("Failed to write to journal {}: {}", self.config.socket_path.display(), e);
+            record_dropped();
+        }
+    }
+}
+
+/// Sends `entry` the way journald takes entries too large for a datagram: written to a
+/// sealed memfd whose descriptor is the only content of the datagram
+#[cfg(target_os = "linux")]
+fn send_memfd(socket: &UnixDatagram, socket_path: &std::path::Path, entry: &[u8]) -> io::Result<()> {
+    use std::fs::File;
+    use std::io::Write;
+    use std::os::fd::{AsRawFd, FromRawFd};
+    use std::os::unix::ffi::OsStrExt;
+
+    // SAFETY: the name is a valid C string; the returned descriptor is checked below
+    let fd = unsafe { libc::memfd_create(c"rs_box_journal".as_ptr(), libc::MFD_ALLOW_SEALING | libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `fd` was just created and nothing else owns it; the file closes it
+    let mut memfd = unsafe { File::from_raw_fd(fd) };
+    memfd.write_all(entry)?;
+    let seals = libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_WRITE | libc::F_SEAL_SEAL;
+    // SAFETY: plain fcntl on a descriptor owned by `memfd`
+    if unsafe { libc::fcntl(fd, libc::F_ADD_SEALS, seals) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // SAFETY: all-zero is a valid sockaddr_un and msghdr
+    let mut address: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    address.sun_family = libc::AF_UNIX as libc::sa_family_t;
+    let path = socket_path.as_os_str().as_bytes();
+    if path.len() >= address.sun_path.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "journal socket path is too long"));
+    }
+    for (slot, byte) in address.sun_path.iter_mut().zip(path) {
+        *slot = *byte as libc::c_char;
+    }
+    let address_len = std::mem::offset_of!(libc::sockaddr_un, sun_path) + path.len() + 1;
+
+    let fd_len = std::mem::size_of::<libc::c_int>() as u32;
+    // SAFETY: CMSG_SPACE only computes a size
+    let control_len = unsafe { libc::CMSG_SPACE(fd_len) } as usize;
+    // u64 words keep the control buffer aligned for cmsghdr
+    let mut control = vec![0u64; control_len.div_ceil(8)];
+    let mut message: libc::msghdr = unsafe { std::mem::zeroed() };
+    message.msg_name = (&mut address as *mut libc::sockaddr_un).cast();
+    message.msg_namelen = address_len as libc::socklen_t;
+    message.msg_control = control.as_mut_ptr().cast();
+    message.msg_controllen = control_len as _;
+    // SAFETY: the control buffer holds one cmsghdr with room for one descriptor
+    let sent = unsafe {
+        let header = libc::CMSG_FIRSTHDR(&message);
+        (*header).cmsg_level = libc::SOL_SOCKET;
+        (*header).cmsg_type = libc::SCM_RIGHTS;
+        (*header).cmsg_len = libc::CMSG_LEN(fd_len) as _;
+        std::ptr::write_unaligned(libc::CMSG_DATA(header).cast::<libc::c_int>(), memfd.as_raw_fd());
+        libc::sendmsg(socket.as_raw_fd(), &message, libc::MSG_NOSIGNAL)
+    };
+    if sent < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Fields `journal_entry` sets from the record; user fields never take these names
+const RESERVED_FIELD_NAMES: [&str; 9] = [
+    "MESSAGE",
+    "PRIORITY",
+    "SYSLOG_IDENTIFIER",
+    "LOGGER_NAME",
+    "CODE_FILE",
+    "CODE_LINE",
+    "SAMPLED",
+    "THREAD_NAME",
+    "TID",
+];
+
+fn journal_entry(record: &LogRecord, config: &LogJournaldConfig) -> Vec<u8> {
+    let mut entry = Vec::new();
+    push_journal_field(&mut entry, "MESSAGE", &record.message);
+    push_journal_field(&mut entry, "PRIORITY", &journald_priority(record.level).to_string());
+    push_journal_field(&mut entry, "SYSLOG_IDENTIFIER", &config.syslog_identifier);
+    push_journal_field(&mut entry, "LOGGER_NAME", &record.logger_name);
+    if let Some(ref code_file) = record.code_file {
+        push_journal_field(&mut entry, "CODE_FILE", code_file);
+    }
+    if let Some(code_line) = record.code_line {
+        push_journal_field(&mut entry, "CODE_LINE", &code_line.to_string());
+    }
+    if let Some(sampling) = record.sampling {
+        push_journal_field(&mut entry, "SAMPLED", &sampling.to_string());
+    }
+    if let Some(ref thread_name) = record.thread_name {
+        push_journal_field(&mut entry, "THREAD_NAME", thread_name);
+    }
+    if let Some(thread_id) = record.thread_id {
+        push_journal_field(&mut entry, "TID", &thread_id.to_string());
+    }
+    for (key, value) in &config.extra_fields {
+        push_journal_field(&mut entry, key, value);
+    }
+    for (key, value) in &record.fields {
+        push_journal_field(&mut entry, &user_field_name(key), &value.to_string());
+    }
+    entry
+}
+
+/// `KEY=value\n`, or `KEY\n` + little-endian u64 length + value + `\n` when the value has newlines
+fn push_journal_field(entry: &mut Vec<u8>, key: &str, value: &str) {
+    entry.extend_from_slice(key.as_bytes());
+    if value.contains('\n') {
+        entry.push(b'\n');
+        entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        entry.push(b'=');
+    }
+    entry.extend_from_slice(value.as_bytes());
+    entry.push(b'\n');
+}
+
+/// Journal field names are upper case ASCII letters, digits and `_`, not starting with
+/// `_` or a digit and at most 64 characters; `share.diff` becomes `SHARE_DIFF`.
+pub(crate) fn journal_field_name(key: &str) -> String {
+    let name: String = key
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect();
+    let name = name.trim_start_matches(|c: char| c == '_' || c.is_ascii_digit());
+    let name = if name.is_empty() { "FIELD" } else { name };
+    name.chars().take(64).collect()
+}
+
+/// `journal_field_name`, with `FIELD_` put in front of names the sink sets itself so a
+/// `priority` or `message` field cannot pass for the real one
+pub(crate) fn user_field_name(key: &str) -> String {
+    let name = journal_field_name(key);
+    if RESERVED_FIELD_NAMES.contains(&name.as_str()) {
+        format!("FIELD_{}", name)
+    } else {
+        name
+    }
+}
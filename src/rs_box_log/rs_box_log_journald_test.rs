@@ -0,0 +1,193 @@
+#[cfg(test)]
+use super::rs_box_log;
+#[cfg(test)]
+use super::rs_box_log_journald::{journal_field_name, user_field_name};
+#[cfg(test)]
+use super::rs_box_log_test::{level_compiled_in, test_log_dir};
+#[cfg(test)]
+use std::collections::HashMap;
+#[cfg(test)]
+use std::os::unix::net::UnixDatagram;
+#[cfg(test)]
+use std::sync::Arc;
+#[cfg(test)]
+use std::time::Duration;
+
+/// A socket standing in for journald; a missing entry fails the test instead of hanging it
+#[cfg(test)]
+fn bind_journal(socket_path: &str) -> UnixDatagram {
+    let journal = UnixDatagram::bind(socket_path).unwrap();
+    journal.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    journal
+}
+
+/// Decodes one native protocol datagram into its fields
+#[cfg(test)]
+fn parse_journal_entry(mut data: &[u8]) -> HashMap<String, String> {
+    let mut fields = HashMap::new();
+    while !data.is_empty() {
+        let end = data.iter().position(|&b| b == b'\n' || b == b'=').unwrap();
+        let key = String::from_utf8(data[..end].to_vec()).unwrap();
+        let value = if data[end] == b'=' {
+            let rest = &data[end + 1..];
+            let line_end = rest.iter().position(|&b| b == b'\n').unwrap();
+            let value = &rest[..line_end];
+            data = &rest[line_end + 1..];
+            value
+        } else {
+            let length = u64::from_le_bytes(data[end + 1..end + 9].try_into().unwrap()) as usize;
+            let value = &data[end + 9..end + 9 + length];
+            assert_eq!(data[end + 9 + length], b'\n');
+            data = &data[end + 10 + length..];
+            value
+        };
+        fields.insert(key, String::from_utf8(value.to_vec()).unwrap());
+    }
+    fields
+}
+
+#[test]
+fn test_journald_entries_over_local_socket() {
+    let dir = test_log_dir("journald");
+    std::fs::create_dir_all(&dir).unwrap();
+    let socket_path = format!("{}/journal.socket", dir);
+    let journal = bind_journal(&socket_path);
+
+    let sink = rs_box_log::LogJournaldSink::new(
+        rs_box_log::LogJournaldConfig::new("journal_pool")
+            .with_socket_path(&socket_path)
+            .with_field("coin", "btc"),
+    )
+    .unwrap();
+    let config = rs_box_log::LogConfig::new("journal_pool", false, "./logs", rs_box_log::LogLevel::LogLevelTrace, 7)
+        .with_sink(Arc::new(sink));
+    let logger = rs_box_log::LoggerManager::with_config(config);
+
+    logger.log_with_fields(
+        rs_box_log::LogLevel::LogLevelWarning,
+        "share rejected\nstale job",
+        vec![("share.diff".to_string(), 65536.into())],
+    );
+    logger.log_debug_f("job sent");
+
+    let mut buffer = vec![0; 64 * 1024];
+    let length = journal.recv(&mut buffer).unwrap();
+    let warning = parse_journal_entry(&buffer[..length]);
+    assert_eq!(warning["MESSAGE"], "share rejected\nstale job");
+    assert_eq!(warning["PRIORITY"], "4");
+    assert_eq!(warning["SYSLOG_IDENTIFIER"], "journal_pool");
+    assert_eq!(warning["LOGGER_NAME"], "journal_pool");
+    assert_eq!(warning["SHARE_DIFF"], "65536");
+    assert_eq!(warning["COIN"], "btc");
+    assert!(!warning.contains_key("CODE_LINE"));
+
+    if !level_compiled_in(rs_box_log::LogLevel::LogLevelDebug) {
+        return;
+    }
+    let length = journal.recv(&mut buffer).unwrap();
+    let debug = parse_journal_entry(&buffer[..length]);
+    assert_eq!(debug["MESSAGE"], "job sent");
+    assert_eq!(debug["PRIORITY"], "7");
+    // Frames inside this crate are skipped as logger internals, so only check the shape
+    if debug.contains_key("CODE_FILE") {
+        assert!(debug["CODE_LINE"].parse::<u32>().is_ok());
+    }
+}
+
+#[test]
+fn test_journald_field_names_and_missing_socket() {
+    assert_eq!(journal_field_name("worker"), "WORKER");
+    assert_eq!(journal_field_name("share.diff"), "SHARE_DIFF");
+    assert_eq!(journal_field_name("_9lives"), "LIVES");
+    assert_eq!(journal_field_name("__"), "FIELD");
+    assert_eq!(journal_field_name(&"k".repeat(80)).len(), 64);
+
+    let missing = rs_box_log::LogJournaldConfig::new("svc").with_socket_path("/nonexistent/journal.socket");
+    assert!(rs_box_log::LogJournaldSink::new(missing).is_err());
+}
+
+#[test]
+fn test_journald_user_fields_cannot_override_trusted_fields() {
+    assert_eq!(user_field_name("priority"), "FIELD_PRIORITY");
+    assert_eq!(user_field_name("syslog.identifier"), "FIELD_SYSLOG_IDENTIFIER");
+    assert_eq!(user_field_name("worker"), "WORKER");
+
+    let dir = test_log_dir("journald_reserved");
+    std::fs::create_dir_all(&dir).unwrap();
+    let socket_path = format!("{}/journal.socket", dir);
+    let journal = bind_journal(&socket_path);
+
+    let sink = rs_box_log::LogJournaldSink::new(
+        rs_box_log::LogJournaldConfig::new("journal_reserved")
+            .with_socket_path(&socket_path)
+            .with_field("syslog_identifier", "spoofed"),
+    )
+    .unwrap();
+    let config = rs_box_log::LogConfig::new("journal_reserved", false, "./logs", rs_box_log::LogLevel::LogLevelTrace, 7)
+        .with_sink(Arc::new(sink));
+    let logger = rs_box_log::LoggerManager::with_config(config);
+    logger.log_with_fields(
+        rs_box_log::LogLevel::LogLevelInfo,
+        "login attempt",
+        vec![
+            ("priority".to_string(), "0".into()),
+            ("message".to_string(), "all good".into()),
+        ],
+    );
+
+    let mut buffer = vec![0; 64 * 1024];
+    let length = journal.recv(&mut buffer).unwrap();
+    let entry = parse_journal_entry(&buffer[..length]);
+    assert_eq!(entry["PRIORITY"], "6");
+    assert_eq!(entry["MESSAGE"], "login attempt");
+    assert_eq!(entry["SYSLOG_IDENTIFIER"], "journal_reserved");
+    assert_eq!(entry["FIELD_PRIORITY"], "0");
+    assert_eq!(entry["FIELD_MESSAGE"], "all good");
+    assert_eq!(entry["FIELD_SYSLOG_IDENTIFIER"], "spoofed");
+}
+
+/// Receives one datagram carrying a descriptor and reads the entry from it
+#[cfg(all(test, target_os = "linux"))]
+fn recv_memfd_entry(journal: &UnixDatagram) -> Vec<u8> {
+    use std::io::{Read, Seek};
+    use std::os::fd::{AsRawFd, FromRawFd};
+
+    let mut control = [0u64; 8];
+    let mut message: libc::msghdr = unsafe { std::mem::zeroed() };
+    message.msg_control = control.as_mut_ptr().cast();
+    message.msg_controllen = std::mem::size_of_val(&control) as _;
+    let received = unsafe { libc::recvmsg(journal.as_raw_fd(), &mut message, 0) };
+    assert_eq!(received, 0, "{}", std::io::Error::last_os_error());
+    let fd = unsafe {
+        let header = libc::CMSG_FIRSTHDR(&message);
+        assert!(!header.is_null() && (*header).cmsg_type == libc::SCM_RIGHTS);
+        std::ptr::read_unaligned(libc::CMSG_DATA(header).cast::<libc::c_int>())
+    };
+    let mut memfd = unsafe { std::fs::File::from_raw_fd(fd) };
+    memfd.rewind().unwrap();
+    let mut entry = Vec::new();
+    memfd.read_to_end(&mut entry).unwrap();
+    entry
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_journald_large_entries_go_through_memfd() {
+    let dir = test_log_dir("journald_memfd");
+    std::fs::create_dir_all(&dir).unwrap();
+    let socket_path = format!("{}/journal.socket", dir);
+    let journal = bind_journal(&socket_path);
+
+    let sink = rs_box_log::LogJournaldSink::new(rs_box_log::LogJournaldConfig::new("journal_memfd").with_socket_path(&socket_path))
+        .unwrap();
+    let config = rs_box_log::LogConfig::new("journal_memfd", false, "./logs", rs_box_log::LogLevel::LogLevelTrace, 7)
+        .with_sink(Arc::new(sink));
+    let logger = rs_box_log::LoggerManager::with_config(config);
+    // Larger than the default socket send buffer, so too large for one datagram
+    let message = "x".repeat(4 * 1024 * 1024);
+    logger.log_error_f(&message);
+
+    let entry = parse_journal_entry(&recv_memfd_entry(&journal));
+    assert_eq!(entry["MESSAGE"], message);
+    assert_eq!(entry["PRIORITY"], "3");
+}
@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use regex::Regex;
+
+const DEFAULT_FILE_PATTERN: &str = "{project}/{date}/{date}_{hour}.log";
+const DEFAULT_SYMLINK_PATTERN: &str = "{project}/run.log";
+
+#[derive(Clone, Debug, PartialEq)]
+enum LayoutToken {
+    Literal(String),
+    Project,
+    Year,
+    Month,
+    Day,
+    Hour,
+    Date,
+    Seq,
+    Pid,
+}
+
+/// Where log files live below `log_dir`, as a pattern with the tokens
+/// `{project}`, `{YYYY}`, `{MM}`, `{DD}`, `{HH}`, `{date}` (`YYYY-MM-DD`), `{hour}` (`HH`), `{seq}`
+/// and `{pid}` (the current process id, giving every process of a shared directory its own files).
+///
+/// Files rotate whenever the rendered time part changes. `{seq}` numbers the files of one
+/// period: a new run starts a new file instead of appending to one written before.
+#[derive(Clone, Debug)]
+pub struct LogFileLayout {
+    pattern: String,
+    tokens: Vec<LayoutToken>,
+    symlink: Option<Vec<LayoutToken>>,
+    /// Compiled path regex per project, shared by clones of the layout
+    regex_cache: Arc<Mutex<HashMap<String, Regex>>>,
+}
+
+impl PartialEq for LogFileLayout {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern && self.symlink == other.symlink
+    }
+}
+
+impl Default for LogFileLayout {
+    fn default() -> Self {
+        LogFileLayout::new(DEFAULT_FILE_PATTERN)
+            .unwrap()
+            .with_symlink(Some(DEFAULT_SYMLINK_PATTERN))
+            .unwrap()
+    }
+}
+
+/// A log file found on disk, with the period start parsed back from its name
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogFileEntry {
+    pub path: PathBuf,
+    pub period_start: Option<NaiveDateTime>,
+    pub seq: u32,
+    /// Process id of the writer, for patterns with `{pid}`
+    pub pid: Option<u32>,
+}
+
+impl LogFileLayout {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        if pattern.is_empty() || pattern.ends_with('/') {
+            return Err(format!("log file pattern {:?} needs a file name", pattern));
+        }
+        let tokens = parse_pattern(pattern)?;
+        Ok(LogFileLayout {
+            pattern: pattern.to_string(),
+            tokens,
+            symlink: None,
+            regex_cache: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    /// Name of the "current file" symlink below `log_dir` (tokens allowed), or `None` to disable it
+    pub fn with_symlink(mut self, symlink: Option<&str>) -> Result<Self, String> {
+        self.symlink = symlink.map(parse_pattern).transpose()?;
+        Ok(self)
+    }
+
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    pub(crate) fn has_seq(&self) -> bool {
+        self.tokens.contains(&LayoutToken::Seq)
+    }
+
+    pub(crate) fn has_pid(&self) -> bool {
+        self.tokens.contains(&LayoutToken::Pid)
+    }
+
+    pub fn file_path(&self, log_dir: &str, project: &str, time: NaiveDateTime, seq: u32) -> PathBuf {
+        Path::new(log_dir).join(render_tokens(&self.tokens, project, time, seq))
+    }
+
+    pub fn symlink_path(&self, log_dir: &str, project: &str) -> Option<PathBuf> {
+        let tokens = self.symlink.as_ref()?;
+        Some(Path::new(log_dir).join(render_tokens(tokens, project, NaiveDateTime::default(), 0)))
+    }
+
+    /// Parses a path produced by `file_path` back into its period start and sequence number.
+    pub fn parse_path(&self, log_dir: &str, project: &str, path: &Path) -> Option<LogFileEntry> {
+        let relative = path.strip_prefix(log_dir).ok()?;
+        let relative = relative.to_str()?.replace('\\', "/");
+        let regex = self.path_regex(project);
+        let captures = regex.captures(&relative)?;
+
+        let mut year = None;
+        let mut month = 1;
+        let mut day = 1;
+        let mut hour = 0;
+        let mut seq = 0;
+        let mut pid = None;
+        let mut group = 1;
+        for token in &self.tokens {
+            let value = match token {
+                LayoutToken::Literal(_) | LayoutToken::Project => continue,
+                _ => captures.get(group)?.as_str(),
+            };
+            group += 1;
+            match token {
+                LayoutToken::Year => year = value.parse().ok(),
+                LayoutToken::Month => month = value.parse().ok()?,
+                LayoutToken::Day => day = value.parse().ok()?,
+                LayoutToken::Hour => hour = value.parse().ok()?,
+                LayoutToken::Seq => seq = value.parse().ok()?,
+                LayoutToken::Pid => pid = Some(value.parse().ok()?),
+                LayoutToken::Date => {
+                    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?;
+                    year = Some(date.year());
+                    month = date.month();
+                    day = date.day();
+                }
+                LayoutToken::Literal(_) | LayoutToken::Project => {}
+            }
+        }
+
+        let period_start = year
+            .and_then(|year| NaiveDate::from_ymd_opt(year, month, day))
+            .and_then(|date| date.and_hms_opt(hour, 0, 0));
+        Some(LogFileEntry {
+            path: path.to_path_buf(),
+            period_start,
+            seq,
+            pid,
+        })
+    }
+
+    /// All files of `project` below `log_dir` that match the pattern, oldest first.
+    pub fn list_files(&self, log_dir: &str, project: &str) -> Vec<LogFileEntry> {
+        let depth = self.pattern.matches('/').count() + project.matches('/').count() + 1;
+        let mut files = Vec::new();
+        collect_files(Path::new(log_dir), depth, &mut files);
+
+        let symlink = self.symlink_path(log_dir, project);
+        let mut entries: Vec<LogFileEntry> = files
+            .iter()
+            .filter(|path| Some(path.as_path()) != symlink.as_deref())
+            .filter_map(|path| self.parse_path(log_dir, project, path))
+            .collect();
+        entries.sort_by(|a, b| (a.period_start, a.seq, &a.path).cmp(&(b.period_start, b.seq, &b.path)));
+        entries
+    }
+
+    fn path_regex(&self, project: &str) -> Regex {
+        let mut cache = self.regex_cache.lock().unwrap();
+        if let Some(regex) = cache.get(project) {
+            return regex.clone();
+        }
+        let regex = self.build_path_regex(project);
+        cache.insert(project.to_string(), regex.clone());
+        regex
+    }
+
+    fn build_path_regex(&self, project: &str) -> Regex {
+        let mut expr = String::from("^");
+        for token in &self.tokens {
+            match token {
+                LayoutToken::Literal(text) => expr.push_str(&regex::escape(text)),
+                LayoutToken::Project => expr.push_str(&regex::escape(project)),
+                LayoutToken::Year => expr.push_str(r"(\d{4})"),
+                LayoutToken::Month | LayoutToken::Day | LayoutToken::Hour => expr.push_str(r"(\d{2})"),
+                LayoutToken::Date => expr.push_str(r"(\d{4}-\d{2}-\d{2})"),
+                LayoutToken::Seq | LayoutToken::Pid => expr.push_str(r"(\d+)"),
+            }
+        }
+        expr.push('$');
+        Regex::new(&expr).unwrap()
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Result<Vec<LayoutToken>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            tokens.push(LayoutToken::Literal(rest[..start].to_string()));
+        }
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unclosed token in log file pattern {:?}", pattern))?;
+        let token = match &rest[start + 1..start + end] {
+            "project" => LayoutToken::Project,
+            "YYYY" => LayoutToken::Year,
+            "MM" => LayoutToken::Month,
+            "DD" => LayoutToken::Day,
+            "HH" => LayoutToken::Hour,
+            "date" => LayoutToken::Date,
+            "hour" => LayoutToken::Hour,
+            "seq" => LayoutToken::Seq,
+            "pid" => LayoutToken::Pid,
+            other => return Err(format!("unknown token {{{}}} in log file pattern {:?}", other, pattern)),
+        };
+        tokens.push(token);
+        rest = &rest[start + end + 1..];
+    }
+    if !rest.is_empty() {
+        tokens.push(LayoutToken::Literal(rest.to_string()));
+    }
+    Ok(tokens)
+}
+
+fn render_tokens(tokens: &[LayoutToken], project: &str, time: NaiveDateTime, seq: u32) -> String {
+    tokens
+        .iter()
+        .map(|token| match token {
+            LayoutToken::Literal(text) => text.clone(),
+            LayoutToken::Project => project.to_string(),
+            LayoutToken::Year => format!("{:04}", time.year()),
+            LayoutToken::Month => format!("{:02}", time.month()),
+            LayoutToken::Day => format!("{:02}", time.day()),
+            LayoutToken::Hour => format!("{:02}", time.hour()),
+            LayoutToken::Date => time.format("%Y-%m-%d").to_string(),
+            LayoutToken::Seq => seq.to_string(),
+            LayoutToken::Pid => std::process::id().to_string(),
+        })
+        .collect()
+}
+
+fn collect_files(dir: &Path, depth: usize, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => {
+                if depth > 1 {
+                    collect_files(&path, depth - 1, files);
+                }
+            }
+            Ok(_) => files.push(path),
+            Err(_) => {}
+        }
+    }
+}
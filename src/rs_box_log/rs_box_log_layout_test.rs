@@ -0,0 +1,126 @@
+#[cfg(test)]
+use super::rs_box_log;
+#[cfg(test)]
+use super::rs_box_log_test::test_log_dir;
+#[cfg(test)]
+use chrono::NaiveDate;
+
+#[test]
+fn test_layout_render_and_parse() {
+    let layout = rs_box_log::LogFileLayout::new("{project}-{YYYY}{MM}{DD}{HH}.log").unwrap();
+    let time = NaiveDate::from_ymd_opt(2024, 5, 17).unwrap().and_hms_opt(9, 41, 7).unwrap();
+
+    let path = layout.file_path("/data/logs", "pool", time, 0);
+    assert_eq!(path, std::path::Path::new("/data/logs/pool-2024051709.log"));
+
+    let entry = layout.parse_path("/data/logs", "pool", &path).unwrap();
+    assert_eq!(entry.period_start, NaiveDate::from_ymd_opt(2024, 5, 17).unwrap().and_hms_opt(9, 0, 0));
+    assert!(layout.parse_path("/data/logs", "pool", std::path::Path::new("/data/logs/pool.stratum-2024051709.log")).is_none());
+
+    assert!(rs_box_log::LogFileLayout::new("{project}/{minute}.log").is_err());
+    assert!(rs_box_log::LogFileLayout::new("{project}/{date").is_err());
+    assert!(rs_box_log::LogFileLayout::new("{project}/").is_err());
+}
+
+#[test]
+fn test_flat_layout_with_custom_symlink() {
+    let log_dir = test_log_dir("layout_flat");
+    let layout = rs_box_log::LogFileLayout::new("{project}-{YYYY}{MM}{DD}{HH}.log")
+        .unwrap()
+        .with_symlink(Some("{project}.current"))
+        .unwrap();
+    let config = rs_box_log::LogConfig::new("shipper", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_file_layout(layout);
+    let logger = rs_box_log::LoggerManager::with_config(config.clone());
+    logger.log_info_f("flat layout record");
+
+    let expected = format!("{}/shipper-{}.log", log_dir, chrono::Local::now().format("%Y%m%d%H"));
+    let files = config.log_files();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].path, std::path::PathBuf::from(&expected));
+    assert!(std::fs::read_to_string(&expected).unwrap().contains("flat layout record"));
+    assert!(std::fs::read_to_string(format!("{}/shipper.current", log_dir)).unwrap().contains("flat layout record"));
+}
+
+#[test]
+fn test_disabled_symlink() {
+    let log_dir = test_log_dir("layout_no_symlink");
+    let layout = rs_box_log::LogFileLayout::new("{project}/{date}.log").unwrap().with_symlink(None).unwrap();
+    let config = rs_box_log::LogConfig::new("nolink", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_file_layout(layout);
+    rs_box_log::LoggerManager::with_config(config).log_info_f("no symlink");
+
+    let names: Vec<String> = std::fs::read_dir(format!("{}/nolink", log_dir))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(names, vec![format!("{}.log", chrono::Local::now().format("%Y-%m-%d"))]);
+}
+
+#[test]
+fn test_seq_starts_new_file_per_run() {
+    let log_dir = test_log_dir("layout_seq");
+    let layout = rs_box_log::LogFileLayout::new("{project}/{date}_{seq}.log").unwrap();
+    let config = rs_box_log::LogConfig::new("seq", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_file_layout(layout);
+    let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+    std::fs::create_dir_all(format!("{}/seq", log_dir)).unwrap();
+    std::fs::write(format!("{}/seq/{}_0.log", log_dir, today), "earlier run\n").unwrap();
+
+    let first = rs_box_log::LoggerManager::with_config(config.clone());
+    let second = rs_box_log::LoggerManager::with_config(config.clone());
+    first.log_info_f("first");
+    second.log_info_f("second");
+
+    let files = config.log_files();
+    assert_eq!(files.iter().map(|entry| entry.seq).collect::<Vec<_>>(), vec![0, 1]);
+    let content = std::fs::read_to_string(&files[1].path).unwrap();
+    assert!(content.contains("first") && content.contains("second"));
+}
+
+#[test]
+fn test_retention_follows_layout() {
+    let log_dir = test_log_dir("layout_retention");
+    let layout = rs_box_log::LogFileLayout::new("{project}-{YYYY}{MM}{DD}{HH}.log").unwrap();
+    std::fs::create_dir_all(&log_dir).unwrap();
+    let expired = format!("{}/keep-2001010100.log", log_dir);
+    let unrelated = format!("{}/other-2001010100.log", log_dir);
+    std::fs::write(&expired, "old\n").unwrap();
+    std::fs::write(&unrelated, "old\n").unwrap();
+
+    let config = rs_box_log::LogConfig::new("keep", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_file_layout(layout);
+    rs_box_log::LoggerManager::with_config(config).log_info_f("new file");
+
+    assert!(!std::path::Path::new(&expired).exists());
+    assert!(std::path::Path::new(&unrelated).exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_pid_token_and_symlink_replacement() {
+    let log_dir = test_log_dir("layout_pid");
+    let layout = rs_box_log::LogFileLayout::new("{project}/{date}_{pid}.log")
+        .unwrap()
+        .with_symlink(Some("{project}/run.log"))
+        .unwrap();
+    std::fs::create_dir_all(format!("{}/perpid", log_dir)).unwrap();
+    // A run.log left behind by another process is replaced, not appended through
+    std::os::unix::fs::symlink("stale.log", format!("{}/perpid/run.log", log_dir)).unwrap();
+
+    let config = rs_box_log::LogConfig::new("perpid", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_file_layout(layout);
+    rs_box_log::LoggerManager::with_config(config.clone()).log_info_f("per process file");
+
+    let name = format!("{}_{}.log", chrono::Local::now().format("%Y-%m-%d"), std::process::id());
+    let files = config.log_files();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].pid, Some(std::process::id()));
+    assert_eq!(std::fs::read_link(format!("{}/perpid/run.log", log_dir)).unwrap(), std::path::PathBuf::from(&name));
+    let mut names: Vec<String> = std::fs::read_dir(format!("{}/perpid", log_dir))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    assert_eq!(names, vec![name, "run.log".to_string()]);
+}
@@ -0,0 +1,211 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use crate::rs_box_log::rs_box_log::{LogLevel, LogRecord};
+
+pub(crate) const LOG_LEVELS: [LogLevel; 5] = [
+    LogLevel::LogLevelInfo,
+    LogLevel::LogLevelWarning,
+    LogLevel::LogLevelError,
+    LogLevel::LogLevelDebug,
+    LogLevel::LogLevelTrace,
+];
+
+/// Per-level record counters; one set for the whole process and one per logger name.
+#[derive(Default)]
+pub(crate) struct LogLevelCounters {
+    counts: [AtomicU64; 5],
+}
+
+impl LogLevelCounters {
+    pub(crate) fn record(&self, level: LogLevel) {
+        self.counts[level as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn load(&self) -> [u64; 5] {
+        let mut counts = [0; 5];
+        for (index, count) in self.counts.iter().enumerate() {
+            counts[index] = count.load(Ordering::Relaxed);
+        }
+        counts
+    }
+}
+
+#[derive(Default)]
+struct LogMetrics {
+    records: LogLevelCounters,
+    loggers: Mutex<HashMap<String, Arc<LogLevelCounters>>>,
+    bytes_written: AtomicU64,
+    rotations: AtomicU64,
+    write_failures: AtomicU64,
+    dropped_records: AtomicU64,
+    sampled_out_records: AtomicU64,
+    deleted_files: AtomicU64,
+}
+
+static LOG_METRICS: Lazy<LogMetrics> = Lazy::new(LogMetrics::default);
+/// When the first logger was set up, the start of the run a summary reports on
+static LOG_STARTED: Lazy<(Instant, DateTime<Utc>)> = Lazy::new(|| (Instant::now(), Utc::now()));
+/// Latest ERROR records, oldest first, at most `RECENT_ERRORS_LIMIT`
+static RECENT_ERRORS: Lazy<Mutex<VecDeque<LogRecord>>> = Lazy::new(|| Mutex::new(VecDeque::new()));
+static RECENT_ERRORS_LIMIT: AtomicUsize = AtomicUsize::new(10);
+
+/// Counters of a named logger, cached by `LoggerManager` so the hot path never takes the map lock.
+pub(crate) fn logger_counters(logger_name: &str) -> Arc<LogLevelCounters> {
+    Lazy::force(&LOG_STARTED);
+    let mut loggers = LOG_METRICS.loggers.lock().unwrap();
+    loggers.entry(logger_name.to_string()).or_default().clone()
+}
+
+pub(crate) fn record_emitted(level: LogLevel) {
+    LOG_METRICS.records.record(level);
+}
+
+pub(crate) fn record_bytes_written(bytes: usize) {
+    LOG_METRICS.bytes_written.fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+pub(crate) fn record_rotation() {
+    LOG_METRICS.rotations.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn record_write_failure() {
+    LOG_METRICS.write_failures.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn record_dropped() {
+    LOG_METRICS.dropped_records.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn record_sampled_out() {
+    LOG_METRICS.sampled_out_records.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn record_deleted_file() {
+    LOG_METRICS.deleted_files.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Keeps an ERROR record for the shutdown summary
+pub(crate) fn record_error(record: &LogRecord) {
+    let limit = RECENT_ERRORS_LIMIT.load(Ordering::Relaxed);
+    let mut errors = RECENT_ERRORS.lock().unwrap();
+    errors.push_back(record.clone());
+    while errors.len() > limit {
+        errors.pop_front();
+    }
+}
+
+pub(crate) fn set_recent_errors_limit(limit: usize) {
+    RECENT_ERRORS_LIMIT.store(limit, Ordering::Relaxed);
+    let mut errors = RECENT_ERRORS.lock().unwrap();
+    while errors.len() > limit {
+        errors.pop_front();
+    }
+}
+
+pub(crate) fn recent_errors() -> Vec<LogRecord> {
+    RECENT_ERRORS.lock().unwrap().iter().cloned().collect()
+}
+
+pub(crate) fn log_started() -> (Instant, DateTime<Utc>) {
+    *LOG_STARTED
+}
+
+/// Point-in-time copy of the logging subsystem counters.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LogMetricsSnapshot {
+    records: [u64; 5],
+    loggers: BTreeMap<String, [u64; 5]>,
+    pub bytes_written: u64,
+    pub rotations: u64,
+    pub write_failures: u64,
+    pub dropped_records: u64,
+    /// Records skipped by a `LogSampler`
+    pub sampled_out_records: u64,
+    /// Old log files removed by retention
+    pub deleted_files: u64,
+}
+
+impl LogMetricsSnapshot {
+    /// Records emitted at `level` by all loggers
+    pub fn records(&self, level: LogLevel) -> u64 {
+        self.records[level as usize]
+    }
+
+    /// Records emitted at `level` by the logger named `logger_name`
+    pub fn logger_records(&self, logger_name: &str, level: LogLevel) -> u64 {
+        self.loggers.get(logger_name).map(|counts| counts[level as usize]).unwrap_or(0)
+    }
+
+    pub fn logger_names(&self) -> Vec<&str> {
+        self.loggers.keys().map(|name| name.as_str()).collect()
+    }
+
+    /// Renders the snapshot in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# HELP rs_box_log_records_total Log records emitted, by level.\n");
+        out.push_str("# TYPE rs_box_log_records_total counter\n");
+        for level in LOG_LEVELS {
+            let _ = writeln!(out, "rs_box_log_records_total{{level=\"{}\"}} {}", level.to_str(), self.records(level));
+        }
+
+        out.push_str("# HELP rs_box_log_logger_records_total Log records emitted, by logger and level.\n");
+        out.push_str("# TYPE rs_box_log_logger_records_total counter\n");
+        for (name, counts) in &self.loggers {
+            for level in LOG_LEVELS {
+                let _ = writeln!(
+                    out,
+                    "rs_box_log_logger_records_total{{logger=\"{}\",level=\"{}\"}} {}",
+                    escape_label_value(name),
+                    level.to_str(),
+                    counts[level as usize]
+                );
+            }
+        }
+
+        let totals = [
+            ("rs_box_log_bytes_written_total", "Bytes written to log outputs.", self.bytes_written),
+            ("rs_box_log_rotations_total", "Log file rotations performed.", self.rotations),
+            ("rs_box_log_write_failures_total", "Failed writes to log outputs.", self.write_failures),
+            ("rs_box_log_dropped_records_total", "Log records that were lost.", self.dropped_records),
+            ("rs_box_log_sampled_out_records_total", "Log records skipped by sampling.", self.sampled_out_records),
+            ("rs_box_log_deleted_files_total", "Old log files removed by retention.", self.deleted_files),
+        ];
+        for (name, help, value) in totals {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+        out
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+pub fn log_metrics_snapshot() -> LogMetricsSnapshot {
+    let loggers = LOG_METRICS
+        .loggers
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(name, counters)| (name.clone(), counters.load()))
+        .collect();
+
+    LogMetricsSnapshot {
+        records: LOG_METRICS.records.load(),
+        loggers,
+        bytes_written: LOG_METRICS.bytes_written.load(Ordering::Relaxed),
+        rotations: LOG_METRICS.rotations.load(Ordering::Relaxed),
+        write_failures: LOG_METRICS.write_failures.load(Ordering::Relaxed),
+        dropped_records: LOG_METRICS.dropped_records.load(Ordering::Relaxed),
+        sampled_out_records: LOG_METRICS.sampled_out_records.load(Ordering::Relaxed),
+        deleted_files: LOG_METRICS.deleted_files.load(Ordering::Relaxed),
+    }
+}
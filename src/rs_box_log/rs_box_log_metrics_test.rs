@@ -0,0 +1,38 @@
+#[cfg(test)]
+use super::rs_box_log;
+#[cfg(test)]
+use super::rs_box_log_test::test_log_dir;
+
+#[test]
+fn test_metrics_count_records_per_level_and_logger() {
+    let log_dir = test_log_dir("metrics");
+    let config = rs_box_log::LogConfig::new("metrics_logger", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7);
+    let logger = rs_box_log::LoggerManager::with_config(config);
+    let before = rs_box_log::log_metrics_snapshot();
+
+    logger.log_error_f("error one");
+    logger.log_error_f("error two");
+    logger.log_info_f("info one");
+    logger.log_trace_f("filtered out by level");
+
+    let after = rs_box_log::log_metrics_snapshot();
+    assert_eq!(after.logger_records("metrics_logger", rs_box_log::LogLevel::LogLevelError), 2);
+    assert_eq!(after.logger_records("metrics_logger", rs_box_log::LogLevel::LogLevelInfo), 1);
+    assert_eq!(after.logger_records("metrics_logger", rs_box_log::LogLevel::LogLevelTrace), 0);
+    assert!(after.records(rs_box_log::LogLevel::LogLevelError) >= before.records(rs_box_log::LogLevel::LogLevelError) + 2);
+    assert!(after.bytes_written > before.bytes_written);
+}
+
+#[test]
+fn test_metrics_prometheus_text() {
+    let config = rs_box_log::LogConfig::new("metrics \"quoted\"", false, "./logs", rs_box_log::LogLevel::LogLevelDebug, 7);
+    let logger = rs_box_log::LoggerManager::with_config(config);
+    logger.log_warning_f("counted warning");
+
+    let text = rs_box_log::log_metrics_snapshot().to_prometheus();
+    assert!(text.contains("# TYPE rs_box_log_records_total counter\n"));
+    assert!(text.contains("rs_box_log_records_total{level=\"WARNING\"} "));
+    assert!(text.contains("rs_box_log_logger_records_total{logger=\"metrics \\\"quoted\\\"\",level=\"WARNING\"} 1\n"));
+    assert!(text.contains("# TYPE rs_box_log_dropped_records_total counter\n"));
+    assert!(text.contains("\nrs_box_log_rotations_total "));
+}
@@ -0,0 +1,180 @@
+use std::fmt::Write as _;
+use std::time::Duration;
+use crate::rs_box_log::rs_box_log::{LogLevel, LogRecord, LogSink};
+use super::rs_box_log_fields::LogFieldValue;
+use super::rs_box_log_format::{host_name, json_escape};
+use super::rs_box_log_http::{BatchSettings, ExportResult, HttpEndpoint, LogBatcher};
+
+/// Where and how `LogOtlpSink` ships records (OTLP/HTTP with JSON encoding)
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogOtlpConfig {
+    endpoint: String,
+    service_name: String,
+    resource_attributes: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    batch: BatchSettings,
+    timeout: Duration,
+}
+
+impl LogOtlpConfig {
+    /// `endpoint` is the collector url, e.g. `http://127.0.0.1:4318` (`/v1/logs` is appended
+    /// when no path is given); `service_name` is usually the project name.
+    pub fn new(endpoint: &str, service_name: &str) -> Self {
+        LogOtlpConfig {
+            endpoint: endpoint.to_string(),
+            service_name: service_name.to_string(),
+            resource_attributes: Vec::new(),
+            headers: Vec::new(),
+            batch: BatchSettings::default(),
+            timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Extra resource attribute, e.g. `deployment.environment`
+    pub fn with_resource_attribute(mut self, key: &str, value: &str) -> Self {
+        self.resource_attributes.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Extra request header, e.g. an authorization token for the collector
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Records per request (default 512)
+    pub fn with_batch_size(mut self, max_batch: usize) -> Self {
+        self.batch.max_batch = max_batch.max(1);
+        self
+    }
+
+    /// Longest time a record waits for its batch to fill (default 1s)
+    pub fn with_flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.batch.flush_interval = flush_interval;
+        self
+    }
+
+    /// Records waiting for export before new ones are dropped (default 8192)
+    pub fn with_queue_capacity(mut self, queue_capacity: usize) -> Self {
+        self.batch.queue_capacity = queue_capacity.max(1);
+        self
+    }
+
+    /// Retries of a failed request, waiting `backoff` and doubling it each time (default 3, 200ms)
+    pub fn with_retry(mut self, max_retries: u32, backoff: Duration) -> Self {
+        self.batch.max_retries = max_retries;
+        self.batch.retry_backoff = backoff;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/// OTLP severity number for `level`
+fn otlp_severity_number(level: LogLevel) -> u8 {
+    match level {
+        LogLevel::LogLevelTrace => 1,
+        LogLevel::LogLevelDebug => 5,
+        LogLevel::LogLevelInfo => 9,
+        LogLevel::LogLevelWarning => 13,
+        LogLevel::LogLevelError => 17,
+    }
+}
+
+/// A sink exporting records to an OpenTelemetry collector in batches from a background thread.
+pub struct LogOtlpSink {
+    batcher: LogBatcher<String>,
+    timeout: Duration,
+}
+
+impl LogOtlpSink {
+    pub fn new(config: LogOtlpConfig) -> Result<Self, String> {
+        let endpoint = HttpEndpoint::parse(&config.endpoint, "/v1/logs")?;
+        let mut headers = vec![("Content-Type".to_string(), "application/json".to_string())];
+        headers.extend(config.headers.iter().cloned());
+        let resource = otlp_resource(&config);
+        let timeout = config.timeout;
+
+        let batcher = LogBatcher::start("rs_box_log_otlp", config.batch, move |records: &[String]| {
+            let body = format!(
+                "{{\"resourceLogs\":[{{\"resource\":{},\"scopeLogs\":[{{\"scope\":{{\"name\":\"rs_box_log\",\"version\":{}}},\"logRecords\":[{}]}}]}}]}}",
+                resource,
+                json_escape(crate::LIB_VERSION),
+                records.join(",")
+            );
+            ExportResult::from_status(endpoint.post(&headers, body.as_bytes(), timeout))
+        })
+        .map_err(|e| format!("failed to start OTLP exporter thread: {}", e))?;
+        Ok(LogOtlpSink { batcher, timeout })
+    }
+}
+
+impl LogSink for LogOtlpSink {
+    fn write_record(&self, record: &LogRecord) {
+        self.batcher.push(otlp_log_record(record));
+    }
+
+    fn flush(&self) {
+        self.batcher.flush(self.timeout * 4);
+    }
+}
+
+fn otlp_resource(config: &LogOtlpConfig) -> String {
+    let mut attributes = vec![
+        otlp_attribute("service.name", &LogFieldValue::from(config.service_name.as_str())),
+        otlp_attribute("host.name", &LogFieldValue::from(host_name())),
+        otlp_attribute("process.pid", &LogFieldValue::from(std::process::id())),
+    ];
+    for (key, value) in &config.resource_attributes {
+        attributes.push(otlp_attribute(key, &LogFieldValue::from(value)));
+    }
+    format!("{{\"attributes\":[{}]}}", attributes.join(","))
+}
+
+/// One entry of `logRecords` in the OTLP logs data model
+fn otlp_log_record(record: &LogRecord) -> String {
+    let time = record.time.timestamp_nanos_opt().unwrap_or(0);
+    let mut attributes = vec![otlp_attribute("logger.name", &LogFieldValue::from(&record.logger_name))];
+    if !record.location.is_empty() {
+        attributes.push(otlp_attribute("code.location", &LogFieldValue::from(&record.location)));
+    }
+    if let Some(ref thread_name) = record.thread_name {
+        attributes.push(otlp_attribute("thread.name", &LogFieldValue::from(thread_name)));
+    }
+    if let Some(thread_id) = record.thread_id {
+        attributes.push(otlp_attribute("thread.id", &LogFieldValue::from(thread_id)));
+    }
+    for (key, value) in &record.fields {
+        attributes.push(otlp_attribute(key, value));
+    }
+
+    let mut line = format!(
+        "{{\"timeUnixNano\":\"{}\",\"observedTimeUnixNano\":\"{}\",\"severityNumber\":{},\"severityText\":{}",
+        time,
+        time,
+        otlp_severity_number(record.level),
+        json_escape(record.level.to_str())
+    );
+    let _ = write!(
+        line,
+        ",\"body\":{{\"stringValue\":{}}},\"attributes\":[{}]}}",
+        json_escape(&record.message),
+        attributes.join(",")
+    );
+    line
+}
+
+/// `{"key":...,"value":{...}}` using the OTLP JSON `AnyValue` encoding (64-bit ints as strings)
+fn otlp_attribute(key: &str, value: &LogFieldValue) -> String {
+    let value = match value {
+        LogFieldValue::LogFieldValueStr(text) => format!("{{\"stringValue\":{}}}", json_escape(text)),
+        LogFieldValue::LogFieldValueI64(number) => format!("{{\"intValue\":\"{}\"}}", number),
+        LogFieldValue::LogFieldValueU64(number) => format!("{{\"intValue\":\"{}\"}}", number),
+        LogFieldValue::LogFieldValueF64(_) => format!("{{\"doubleValue\":{}}}", value.to_json()),
+        LogFieldValue::LogFieldValueBool(flag) => format!("{{\"boolValue\":{}}}", flag),
+    };
+    format!("{{\"key\":{},\"value\":{}}}", json_escape(key), value)
+}
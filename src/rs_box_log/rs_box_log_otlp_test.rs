@@ -0,0 +1,188 @@
+#[cfg(test)]
+use super::rs_box_log;
+#[cfg(test)]
+use std::io::{BufRead, BufReader, Read, Write};
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+#[cfg(test)]
+use std::time::Duration;
+
+/// A local HTTP server recording every request; answers with `statuses` in order, then 200.
+#[cfg(test)]
+pub(crate) struct MockCollector {
+    pub(crate) url: String,
+    /// `(request line, body)` per request
+    pub(crate) requests: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+#[cfg(test)]
+impl MockCollector {
+    pub(crate) fn start(statuses: Vec<u16>) -> Self {
+        MockCollector::start_with_responses(statuses.into_iter().map(|status| (status, String::new())).collect())
+    }
+
+    /// Answers with the given `(status, body)` pairs in turn, then with an empty 200
+    pub(crate) fn start_with_responses(responses: Vec<(u16, String)>) -> Self {
+        MockCollector::serve(responses, None)
+    }
+
+    /// Like `start_with_responses`, but sends the bodies chunked, `chunk_size` bytes a chunk
+    pub(crate) fn start_chunked(responses: Vec<(u16, String)>, chunk_size: usize) -> Self {
+        MockCollector::serve(responses, Some(chunk_size))
+    }
+
+    fn serve(responses: Vec<(u16, String)>, chunk_size: Option<usize>) -> Self {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        std::thread::spawn(move || {
+            let mut responses = responses.into_iter();
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':')
+                        && name.eq_ignore_ascii_case("content-length")
+                    {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                recorded
+                    .lock()
+                    .unwrap()
+                    .push((request_line.trim().to_string(), String::from_utf8(body).unwrap()));
+
+                let (status, body) = responses.next().unwrap_or((200, String::new()));
+                let _ = match chunk_size {
+                    Some(chunk_size) => {
+                        let chunks: String = body
+                            .as_bytes()
+                            .chunks(chunk_size)
+                            .map(|chunk| format!("{:x};mock=1\r\n{}\r\n", chunk.len(), String::from_utf8_lossy(chunk)))
+                            .collect();
+                        write!(
+                            stream,
+                            "HTTP/1.1 {} Mock\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n{}0\r\n\r\n",
+                            status, chunks
+                        )
+                    }
+                    None => write!(
+                        stream,
+                        "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        body
+                    ),
+                };
+            }
+        });
+        MockCollector { url, requests }
+    }
+
+    pub(crate) fn bodies(&self) -> Vec<String> {
+        self.requests.lock().unwrap().iter().map(|(_, body)| body.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+fn otlp_logger(name: &str, sink: rs_box_log::LogOtlpSink) -> (rs_box_log::LoggerManager, Arc<rs_box_log::LogOtlpSink>) {
+    let sink = Arc::new(sink);
+    let config = rs_box_log::LogConfig::new(name, false, "./logs", rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_sink(sink.clone());
+    (rs_box_log::LoggerManager::with_config(config), sink)
+}
+
+#[test]
+fn test_otlp_export_in_batches() {
+    let collector = MockCollector::start(Vec::new());
+    let sink = rs_box_log::LogOtlpSink::new(
+        rs_box_log::LogOtlpConfig::new(&collector.url, "otlp_pool")
+            .with_resource_attribute("deployment.environment", "test")
+            .with_batch_size(2)
+            .with_flush_interval(Duration::from_secs(3600)),
+    )
+    .unwrap();
+    let (logger, sink) = otlp_logger("otlp_pool", sink);
+
+    logger.log_with_fields(
+        rs_box_log::LogLevel::LogLevelInfo,
+        "share accepted",
+        vec![("diff".to_string(), 65536.into()), ("worker".to_string(), "rig01".into())],
+    );
+    logger.log_error_f("stratum disconnected");
+    logger.log_warning_f("left for flush");
+    rs_box_log::LogSink::flush(&*sink);
+
+    let requests = collector.requests.lock().unwrap().clone();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].0, "POST /v1/logs HTTP/1.1");
+    let first = &requests[0].1;
+    assert!(first.starts_with(r#"{"resourceLogs":[{"resource":{"attributes":[{"key":"service.name","value":{"stringValue":"otlp_pool"}}"#));
+    assert!(first.contains(r#"{"key":"deployment.environment","value":{"stringValue":"test"}}"#));
+    assert!(first.contains(r#""severityNumber":9,"severityText":"INFO","body":{"stringValue":"share accepted"}"#));
+    assert!(first.contains(r#"{"key":"diff","value":{"intValue":"65536"}},{"key":"worker","value":{"stringValue":"rig01"}}"#));
+    assert!(first.contains(r#""severityNumber":17,"severityText":"ERROR""#));
+    assert!(requests[1].1.contains("left for flush") && !requests[1].1.contains("share accepted"));
+}
+
+#[test]
+fn test_otlp_retries_server_errors_only() {
+    let collector = MockCollector::start(vec![503, 429]);
+    let sink = rs_box_log::LogOtlpSink::new(
+        rs_box_log::LogOtlpConfig::new(&format!("{}/custom/logs", collector.url), "otlp_retry")
+            .with_retry(3, Duration::from_millis(10)),
+    )
+    .unwrap();
+    let (logger, sink) = otlp_logger("otlp_retry", sink);
+    logger.log_info_f("eventually delivered");
+    rs_box_log::LogSink::flush(&*sink);
+
+    let requests = collector.requests.lock().unwrap().clone();
+    assert_eq!(requests.len(), 3);
+    assert!(requests.iter().all(|(line, body)| line == "POST /custom/logs HTTP/1.1" && body == &requests[0].1));
+
+    let rejecting = MockCollector::start(vec![400]);
+    let sink = rs_box_log::LogOtlpSink::new(
+        rs_box_log::LogOtlpConfig::new(&rejecting.url, "otlp_reject").with_retry(3, Duration::from_millis(10)),
+    )
+    .unwrap();
+    let (logger, sink) = otlp_logger("otlp_reject", sink);
+    let dropped_before = rs_box_log::log_metrics_snapshot().dropped_records;
+    logger.log_info_f("rejected");
+    rs_box_log::LogSink::flush(&*sink);
+    assert_eq!(rejecting.bodies().len(), 1);
+    assert!(rs_box_log::log_metrics_snapshot().dropped_records > dropped_before);
+}
+
+#[test]
+fn test_otlp_endpoint_validation() {
+    assert!(rs_box_log::LogOtlpSink::new(rs_box_log::LogOtlpConfig::new("https://collector:4318", "svc")).is_err());
+    assert!(rs_box_log::LogOtlpSink::new(rs_box_log::LogOtlpConfig::new("collector:4318", "svc")).is_err());
+    assert!(rs_box_log::LogOtlpSink::new(rs_box_log::LogOtlpConfig::new("http://collector:port", "svc")).is_err());
+}
+
+#[test]
+fn test_ipv6_endpoints() {
+    use super::rs_box_log_http::HttpEndpoint;
+    let endpoint = HttpEndpoint::parse("http://[::1]:4318", "/v1/logs").unwrap();
+    assert_eq!(endpoint.authority(), "[::1]:4318");
+    assert_eq!(endpoint.socket_addr().unwrap(), "[::1]:4318".parse().unwrap());
+    assert_eq!(HttpEndpoint::parse("http://[::1]/", "/v1/logs").unwrap().authority(), "[::1]:80");
+    assert_eq!(HttpEndpoint::parse("http://collector:4318/", "/v1/logs").unwrap().authority(), "collector:4318");
+    for invalid in ["http://[::1", "http://[::1]x/", "http://[::1]:port", "http://::1:4318", "http://[]:4318"] {
+        assert!(HttpEndpoint::parse(invalid, "/v1/logs").is_err(), "{}", invalid);
+    }
+}
@@ -0,0 +1,428 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use serde::Deserialize;
+use crate::rs_box_log::rs_box_log::{LogRecord, LogSink};
+use super::rs_box_log_format::{json_escape, render_json};
+use super::rs_box_log_http::{BatchFallback, BatchSettings, ExportResult, HttpEndpoint, LogBatcher};
+use super::rs_box_log_metrics::record_dropped;
+
+/// The parts of a `_bulk` response that decide what is sent again
+#[derive(Deserialize)]
+struct BulkResponse {
+    #[serde(default)]
+    errors: bool,
+    #[serde(default)]
+    items: Vec<HashMap<String, BulkItem>>,
+}
+
+/// Result of one action; `items` holds one `{"<action>": {...}}` object per entry sent
+#[derive(Deserialize)]
+struct BulkItem {
+    status: u16,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LogPushFormat {
+    /// Loki push API (`/loki/api/v1/push`), one stream per label set
+    LogPushFormatLoki,
+    /// Elasticsearch `_bulk` NDJSON into the given index
+    LogPushFormatElasticsearch(String),
+}
+
+impl LogPushFormat {
+    fn default_path(&self) -> &'static str {
+        match self {
+            LogPushFormat::LogPushFormatLoki => "/loki/api/v1/push",
+            LogPushFormat::LogPushFormatElasticsearch(_) => "/_bulk",
+        }
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            LogPushFormat::LogPushFormatLoki => "application/json",
+            LogPushFormat::LogPushFormatElasticsearch(_) => "application/x-ndjson",
+        }
+    }
+}
+
+/// Where and how `LogPushSink` ships records
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogPushConfig {
+    endpoint: String,
+    format: LogPushFormat,
+    labels: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    batch: BatchSettings,
+    timeout: Duration,
+    spool_dir: Option<PathBuf>,
+    spool_max_bytes: u64,
+}
+
+impl LogPushConfig {
+    /// `endpoint` is the server url; the format's API path is used when it has none.
+    /// Every record is labeled with `project` and its level.
+    pub fn new(endpoint: &str, format: LogPushFormat, project_name: &str) -> Self {
+        LogPushConfig {
+            endpoint: endpoint.to_string(),
+            format,
+            labels: vec![("project".to_string(), project_name.to_string())],
+            headers: Vec::new(),
+            batch: BatchSettings::default(),
+            timeout: Duration::from_secs(5),
+            spool_dir: None,
+            spool_max_bytes: 256 * 1024 * 1024,
+        }
+    }
+
+    /// Static label added to every record, e.g. `env=prod`
+    pub fn with_label(mut self, key: &str, value: &str) -> Self {
+        self.labels.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Extra request header, e.g. `Authorization` or Loki's `X-Scope-OrgID`
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Records per request (default 512)
+    pub fn with_batch_size(mut self, max_batch: usize) -> Self {
+        self.batch.max_batch = max_batch.max(1);
+        self
+    }
+
+    /// Longest time a record waits for its batch to fill (default 1s)
+    pub fn with_flush_interval(mut self, flush_interval: Duration) -> Self {
+        self.batch.flush_interval = flush_interval;
+        self
+    }
+
+    /// Records waiting for export before new ones are dropped (default 8192)
+    pub fn with_queue_capacity(mut self, queue_capacity: usize) -> Self {
+        self.batch.queue_capacity = queue_capacity.max(1);
+        self
+    }
+
+    /// Retries of a failed request, waiting `backoff` and doubling it each time (default 3, 200ms)
+    pub fn with_retry(mut self, max_retries: u32, backoff: Duration) -> Self {
+        self.batch.max_retries = max_retries;
+        self.batch.retry_backoff = backoff;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Keeps batches the server did not take after all retries, and records logged while
+    /// the queue is full, in `dir` and sends them ahead of the next batch. Past `max_bytes`
+    /// the oldest spooled batches are dropped.
+    pub fn with_spool(mut self, dir: &str, max_bytes: u64) -> Self {
+        self.spool_dir = Some(PathBuf::from(dir));
+        self.spool_max_bytes = max_bytes;
+        self
+    }
+}
+
+/// A record ready to push: its level label, time and JSON line
+#[derive(Clone, Debug, PartialEq)]
+struct PushItem {
+    level: String,
+    time_ns: i64,
+    line: String,
+}
+
+impl PushItem {
+    fn from_record(record: &LogRecord) -> Self {
+        PushItem {
+            level: record.level.to_str().to_lowercase(),
+            time_ns: record.time.timestamp_nanos_opt().unwrap_or(0),
+            line: render_json(record).trim_end().to_string(),
+        }
+    }
+
+    /// `level\ttime\tline`; JSON lines never contain raw tabs or newlines
+    fn to_spool_line(&self) -> String {
+        format!("{}\t{}\t{}\n", self.level, self.time_ns, self.line)
+    }
+
+    fn from_spool_line(line: &str) -> Option<Self> {
+        let mut parts = line.splitn(3, '\t');
+        Some(PushItem {
+            level: parts.next()?.to_string(),
+            time_ns: parts.next()?.parse().ok()?,
+            line: parts.next()?.to_string(),
+        })
+    }
+}
+
+/// Batches that could not be delivered, one file per batch, replayed oldest first
+struct PushSpool {
+    dir: PathBuf,
+    max_bytes: u64,
+    counter: AtomicU64,
+}
+
+impl PushSpool {
+    fn store(&self, items: &[PushItem]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let name = format!(
+            "{:020}-{:06}.spool",
+            chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0),
+            self.counter.fetch_add(1, Ordering::Relaxed)
+        );
+        self.write(&name, items)?;
+        self.enforce_limit();
+        Ok(())
+    }
+
+    /// Replaces a spooled batch with the part of it still to send
+    fn replace(&self, path: &Path, items: &[PushItem]) -> io::Result<()> {
+        match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => self.write(name, items),
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, "spool file has no name")),
+        }
+    }
+
+    /// Writes to a temporary file first so a crash never leaves half a batch behind
+    fn write(&self, name: &str, items: &[PushItem]) -> io::Result<()> {
+        let temp_path = self.dir.join(format!(".{}.tmp", name));
+        let mut file = fs::File::create(&temp_path)?;
+        for item in items {
+            file.write_all(item.to_spool_line().as_bytes())?;
+        }
+        file.sync_all()?;
+        fs::rename(&temp_path, self.dir.join(name))
+    }
+
+    fn pending(&self) -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut files: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|extension| extension == "spool"))
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn load(path: &PathBuf) -> Vec<PushItem> {
+        fs::read_to_string(path)
+            .map(|content| content.lines().filter_map(PushItem::from_spool_line).collect())
+            .unwrap_or_default()
+    }
+
+    fn discard(path: &PathBuf) {
+        for _ in PushSpool::load(path) {
+            record_dropped();
+        }
+        let _ = fs::remove_file(path);
+    }
+
+    fn enforce_limit(&self) {
+        let files = self.pending();
+        let sizes: Vec<u64> = files
+            .iter()
+            .map(|path| fs::metadata(path).map_or(0, |metadata| metadata.len()))
+            .collect();
+        let mut total: u64 = sizes.iter().sum();
+        for (path, size) in files.iter().zip(sizes) {
+            if total <= self.max_bytes {
+                break;
+            }
+            eprintln!("Log spool {} is full, dropping {}", self.dir.display(), path.display());
+            PushSpool::discard(path);
+            total -= size;
+        }
+    }
+}
+
+/// A sink pushing batches of records to Loki or Elasticsearch from a background thread.
+pub struct LogPushSink {
+    batcher: LogBatcher<PushItem>,
+    timeout: Duration,
+}
+
+impl LogPushSink {
+    pub fn new(config: LogPushConfig) -> Result<Self, String> {
+        let endpoint = HttpEndpoint::parse(&config.endpoint, config.format.default_path())?;
+        let mut headers = vec![("Content-Type".to_string(), config.format.content_type().to_string())];
+        headers.extend(config.headers.iter().cloned());
+        let spool = config.spool_dir.as_ref().map(|dir| {
+            Arc::new(PushSpool {
+                dir: dir.clone(),
+                max_bytes: config.spool_max_bytes,
+                counter: AtomicU64::new(0),
+            })
+        });
+        let format = config.format.clone();
+        let labels = config.labels.clone();
+        let timeout = config.timeout;
+
+        let send = move |items: &[PushItem]| match format {
+            LogPushFormat::LogPushFormatLoki => {
+                let body = loki_body(items, &labels);
+                ExportResult::from_status(endpoint.post(&headers, body.as_bytes(), timeout))
+            }
+            LogPushFormat::LogPushFormatElasticsearch(ref index) => {
+                let body = bulk_body(items, &labels, index);
+                match endpoint.post_with_body(&headers, body.as_bytes(), timeout) {
+                    Ok((status, response)) if (200..300).contains(&status) => bulk_result(&response, items.len()),
+                    result => ExportResult::from_status(result.map(|(status, _)| status)),
+                }
+            }
+        };
+        let replay_spool = spool.clone();
+        let export = move |items: &[PushItem]| {
+            if let Some(ref spool) = replay_spool {
+                for path in spool.pending() {
+                    let spooled = PushSpool::load(&path);
+                    match send(&spooled) {
+                        ExportResult::Exported => {
+                            let _ = fs::remove_file(&path);
+                        }
+                        ExportResult::Failed(e) => {
+                            eprintln!("Server rejected spooled log batch {}: {}", path.display(), e);
+                            PushSpool::discard(&path);
+                        }
+                        ExportResult::RetryItems(indices, e) => {
+                            let left: Vec<PushItem> = spooled
+                                .into_iter()
+                                .enumerate()
+                                .filter(|(index, _)| indices.contains(index))
+                                .map(|(_, item)| item)
+                                .collect();
+                            if let Err(e) = spool.replace(&path, &left) {
+                                eprintln!("Failed to update spooled log batch {}: {}", path.display(), e);
+                            }
+                            return ExportResult::Retry(e);
+                        }
+                        retry => return retry,
+                    }
+                }
+            }
+            send(items)
+        };
+        let fallback = spool.map(|spool| {
+            Arc::new(move |items: Vec<PushItem>| {
+                if let Err(e) = spool.store(&items) {
+                    eprintln!("Failed to spool {} log records: {}", items.len(), e);
+                    for _ in items {
+                        record_dropped();
+                    }
+                }
+            }) as BatchFallback<PushItem>
+        });
+
+        let batcher = LogBatcher::start_with_fallback("rs_box_log_push", config.batch, export, fallback)
+            .map_err(|e| format!("failed to start log push thread: {}", e))?;
+        Ok(LogPushSink { batcher, timeout })
+    }
+}
+
+impl LogSink for LogPushSink {
+    fn write_record(&self, record: &LogRecord) {
+        self.batcher.push(PushItem::from_record(record));
+    }
+
+    fn flush(&self) {
+        self.batcher.flush(self.timeout * 4);
+    }
+}
+
+fn labels_json(labels: &[(String, String)], level: &str) -> String {
+    let mut pairs: Vec<String> = labels
+        .iter()
+        .map(|(key, value)| format!("{}:{}", json_escape(key), json_escape(value)))
+        .collect();
+    pairs.push(format!("\"level\":{}", json_escape(level)));
+    format!("{{{}}}", pairs.join(","))
+}
+
+/// `{"streams":[{"stream":{labels},"values":[["<ns>","<line>"],...]},...]}`, one stream per level
+fn loki_body(items: &[PushItem], labels: &[(String, String)]) -> String {
+    let mut streams: Vec<(&str, Vec<String>)> = Vec::new();
+    for item in items {
+        let value = format!("[\"{}\",{}]", item.time_ns, json_escape(&item.line));
+        match streams.iter_mut().find(|(level, _)| *level == item.level) {
+            Some((_, values)) => values.push(value),
+            None => streams.push((&item.level, vec![value])),
+        }
+    }
+    let streams: Vec<String> = streams
+        .iter()
+        .map(|(level, values)| format!("{{\"stream\":{},\"values\":[{}]}}", labels_json(labels, level), values.join(",")))
+        .collect();
+    format!("{{\"streams\":[{}]}}", streams.join(","))
+}
+
+/// `_bulk` NDJSON: an index action plus the record with `@timestamp` and `labels` per item
+fn bulk_body(items: &[PushItem], labels: &[(String, String)], index: &str) -> String {
+    let action = format!("{{\"index\":{{\"_index\":{}}}}}\n", json_escape(index));
+    let mut body = String::new();
+    for item in items {
+        let timestamp = chrono::DateTime::from_timestamp_nanos(item.time_ns).to_rfc3339();
+        body.push_str(&action);
+        body.push_str(&format!(
+            "{{\"@timestamp\":{},\"labels\":{},{}\n",
+            json_escape(&timestamp),
+            labels_json(labels, &item.level),
+            &item.line[1..]
+        ));
+    }
+    body
+}
+
+/// Reads a 2xx `_bulk` response: entries rejected with 429 or 5xx are sent again,
+/// entries rejected for any other reason are dropped
+fn bulk_result(response: &str, count: usize) -> ExportResult {
+    // The 2xx status already says the request was taken; only a response reporting
+    // errors is looked into, so an empty or non-JSON body from a proxy counts as sent
+    let response = match serde_json::from_str::<BulkResponse>(response) {
+        Ok(response) if response.errors => response,
+        _ => return ExportResult::Exported,
+    };
+    let statuses: Option<Vec<u16>> = response
+        .items
+        .iter()
+        .map(|item| match item.values().collect::<Vec<_>>()[..] {
+            [result] => Some(result.status),
+            _ => None,
+        })
+        .collect();
+    let statuses = match statuses {
+        Some(statuses) if statuses.len() == count => statuses,
+        _ => return ExportResult::Retry("unreadable _bulk response with errors".to_string()),
+    };
+
+    let mut retry = Vec::new();
+    let mut rejected = 0;
+    for (index, status) in statuses.into_iter().enumerate() {
+        match status {
+            200..=299 => {}
+            429 | 500.. => retry.push(index),
+            _ => {
+                rejected += 1;
+                record_dropped();
+            }
+        }
+    }
+    if rejected > 0 {
+        eprintln!("Server rejected {} of {} log records in a _bulk request", rejected, count);
+    }
+    if retry.is_empty() {
+        ExportResult::Exported
+    } else {
+        let error = format!("{} of {} _bulk items failed", retry.len(), count);
+        ExportResult::RetryItems(retry, error)
+    }
+}
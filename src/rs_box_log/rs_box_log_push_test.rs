@@ -0,0 +1,179 @@
+#[cfg(test)]
+use super::rs_box_log;
+#[cfg(test)]
+use super::rs_box_log_otlp_test::MockCollector;
+#[cfg(test)]
+use super::rs_box_log_test::test_log_dir;
+#[cfg(test)]
+use std::sync::Arc;
+#[cfg(test)]
+use std::time::Duration;
+
+#[cfg(test)]
+fn push_logger(name: &str, config: rs_box_log::LogPushConfig) -> (rs_box_log::LoggerManager, Arc<rs_box_log::LogPushSink>) {
+    let sink = Arc::new(rs_box_log::LogPushSink::new(config.with_flush_interval(Duration::from_secs(3600))).unwrap());
+    let config = rs_box_log::LogConfig::new(name, false, "./logs", rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_sink(sink.clone());
+    (rs_box_log::LoggerManager::with_config(config), sink)
+}
+
+#[test]
+fn test_loki_push_groups_streams_by_level() {
+    let collector = MockCollector::start(Vec::new());
+    let (logger, sink) = push_logger(
+        "loki_pool",
+        rs_box_log::LogPushConfig::new(&collector.url, rs_box_log::LogPushFormat::LogPushFormatLoki, "loki_pool")
+            .with_label("env", "test"),
+    );
+    logger.log_info_f("first info");
+    logger.log_error_f("an error");
+    logger.log_info_f("second info");
+    rs_box_log::LogSink::flush(&*sink);
+
+    let requests = collector.requests.lock().unwrap().clone();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].0, "POST /loki/api/v1/push HTTP/1.1");
+    let body = &requests[0].1;
+    assert!(body.starts_with(r#"{"streams":[{"stream":{"project":"loki_pool","env":"test","level":"info"},"values":[[""#));
+    assert!(body.contains(r#"{"stream":{"project":"loki_pool","env":"test","level":"error"},"values":[[""#));
+    assert_eq!(body.matches("first info").count() + body.matches("second info").count(), 2);
+    assert!(body.find("first info").unwrap() < body.find("second info").unwrap());
+    assert!(body.find("second info").unwrap() < body.find("an error").unwrap());
+}
+
+#[test]
+fn test_elasticsearch_bulk_ndjson() {
+    let collector = MockCollector::start(Vec::new());
+    let (logger, sink) = push_logger(
+        "es_pool",
+        rs_box_log::LogPushConfig::new(
+            &collector.url,
+            rs_box_log::LogPushFormat::LogPushFormatElasticsearch("pool-logs".to_string()),
+            "es_pool",
+        ),
+    );
+    logger.log_warning_f("bulk record");
+    rs_box_log::LogSink::flush(&*sink);
+
+    let requests = collector.requests.lock().unwrap().clone();
+    assert_eq!(requests[0].0, "POST /_bulk HTTP/1.1");
+    let lines: Vec<&str> = requests[0].1.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0], r#"{"index":{"_index":"pool-logs"}}"#);
+    assert!(lines[1].starts_with(r#"{"@timestamp":""#));
+    assert!(lines[1].contains(r#""labels":{"project":"es_pool","level":"warning"},"time":"#));
+    assert!(lines[1].ends_with(r#""message":"bulk record"}"#));
+}
+
+#[test]
+fn test_spooled_batches_are_replayed() {
+    let spool_dir = test_log_dir("push_spool");
+    // The first batch fails its attempt and its one retry, then the server recovers
+    let collector = MockCollector::start(vec![503, 503]);
+    let (logger, sink) = push_logger(
+        "spool_pool",
+        rs_box_log::LogPushConfig::new(&collector.url, rs_box_log::LogPushFormat::LogPushFormatLoki, "spool_pool")
+            .with_retry(1, Duration::from_millis(10))
+            .with_spool(&spool_dir, 1024 * 1024),
+    );
+
+    logger.log_info_f("while down");
+    rs_box_log::LogSink::flush(&*sink);
+    assert_eq!(std::fs::read_dir(&spool_dir).unwrap().count(), 1);
+
+    logger.log_info_f("after recovery");
+    rs_box_log::LogSink::flush(&*sink);
+    let bodies = collector.bodies();
+    assert_eq!(bodies.len(), 4);
+    assert!(bodies[2].contains("while down") && !bodies[2].contains("after recovery"));
+    assert!(bodies[3].contains("after recovery"));
+    assert_eq!(std::fs::read_dir(&spool_dir).unwrap().count(), 0);
+}
+
+#[test]
+fn test_rejected_bulk_items_are_retried() {
+    let bulk_response = concat!(
+        r#"{"took":3,"errors":true,"items":["#,
+        r#"{"index":{"_index":"pool-logs","status":201}},"#,
+        r#"{"index":{"_index":"pool-logs","status":429,"error":{"type":"es_rejected_execution_exception","index":"pool-logs"}}},"#,
+        // The error object comes first here and carries a status of its own
+        r#"{"index":{"_index":"pool-logs","error":{"type":"mapper_parsing_exception","caused_by":{"status":500}},"status":400}}]}"#
+    );
+    let collector = MockCollector::start_with_responses(vec![(200, bulk_response.to_string())]);
+    let (logger, sink) = push_logger(
+        "bulk_retry",
+        rs_box_log::LogPushConfig::new(
+            &collector.url,
+            rs_box_log::LogPushFormat::LogPushFormatElasticsearch("pool-logs".to_string()),
+            "bulk_retry",
+        )
+        .with_retry(1, Duration::from_millis(10)),
+    );
+    logger.log_info_f("indexed");
+    logger.log_info_f("throttled");
+    logger.log_info_f("bad mapping");
+    rs_box_log::LogSink::flush(&*sink);
+
+    let bodies = collector.bodies();
+    assert_eq!(bodies.len(), 2);
+    assert_eq!(bodies[0].lines().count(), 6);
+    // Only the entry rejected with 429 is sent again
+    assert_eq!(bodies[1].lines().count(), 2);
+    assert!(bodies[1].contains("throttled"));
+}
+
+#[test]
+fn test_chunked_bulk_response_is_decoded() {
+    let bulk_response = concat!(
+        r#"{"took":3,"errors":true,"items":["#,
+        r#"{"index":{"_index":"pool-logs","status":201}},"#,
+        r#"{"index":{"_index":"pool-logs","status": 201}},"#,
+        r#"{"index":{"_index":"pool-logs","status":503,"error":{"type":"unavailable_shards_exception"}}}]}"#
+    );
+    // Small chunks split "status": 201 across chunk size lines
+    let collector = MockCollector::start_chunked(vec![(200, bulk_response.to_string())], 7);
+    let (logger, sink) = push_logger(
+        "bulk_chunked",
+        rs_box_log::LogPushConfig::new(
+            &collector.url,
+            rs_box_log::LogPushFormat::LogPushFormatElasticsearch("pool-logs".to_string()),
+            "bulk_chunked",
+        )
+        .with_retry(1, Duration::from_millis(10)),
+    );
+    logger.log_info_f("indexed");
+    logger.log_info_f("indexed too");
+    logger.log_info_f("unavailable");
+    rs_box_log::LogSink::flush(&*sink);
+
+    let bodies = collector.bodies();
+    assert_eq!(bodies.len(), 2);
+    // The indexed entries are not sent twice
+    assert_eq!(bodies[1].lines().count(), 2);
+    assert!(bodies[1].contains("unavailable"));
+}
+
+#[test]
+fn test_full_queue_spools_records() {
+    let spool_dir = test_log_dir("push_overflow");
+    // The worker is busy retrying the first record while the queue fills up
+    let collector = MockCollector::start(vec![503, 503]);
+    let (logger, sink) = push_logger(
+        "overflow_pool",
+        rs_box_log::LogPushConfig::new(&collector.url, rs_box_log::LogPushFormat::LogPushFormatLoki, "overflow_pool")
+            .with_batch_size(1)
+            .with_queue_capacity(1)
+            .with_retry(1, Duration::from_millis(300))
+            .with_spool(&spool_dir, 1024 * 1024),
+    );
+    for index in 0..4 {
+        logger.log_info_f(&format!("record {}", index));
+    }
+    rs_box_log::LogSink::flush(&*sink);
+
+    let bodies = collector.bodies().concat();
+    for index in 0..4 {
+        assert!(bodies.contains(&format!("record {}", index)));
+    }
+    assert_eq!(std::fs::read_dir(&spool_dir).unwrap().count(), 0);
+}
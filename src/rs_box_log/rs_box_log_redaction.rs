@@ -0,0 +1,198 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::sync::Arc;
+use regex::{Captures, Regex};
+use super::rs_box_log_fields::LogFieldValue;
+
+/// Keys masked by `LogRedactor::with_default_preset`
+const DEFAULT_REDACTED_KEYS: [&str; 17] = [
+    "password",
+    "passwd",
+    "pwd",
+    "rpcpassword",
+    "rpcauth",
+    "secret",
+    "secret_key",
+    "api_key",
+    "apikey",
+    "api-key",
+    "access_token",
+    "refresh_token",
+    "token",
+    "private_key",
+    "privkey",
+    "mnemonic",
+    "seed_phrase",
+];
+
+#[derive(Clone)]
+pub enum LogRedactionRule {
+    /// Masks the value of `"key":"value"`, `"key":value`, `\"key\":\"value\"`, `key=value` and
+    /// `key: "value"` occurrences of the key (case-insensitive); quoted values are masked up to
+    /// the closing quote, unquoted values of seed/mnemonic keys up to the end of the line
+    LogRedactionRuleKey(String),
+    /// Masks every match of the regex; if it has a capture group only group 1 is masked
+    LogRedactionRuleRegex(Regex),
+    /// Returns the rewritten message, or `None` to leave it untouched
+    LogRedactionRuleCustom(Arc<dyn Fn(&str) -> Option<String> + Send + Sync>),
+}
+
+impl fmt::Debug for LogRedactionRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogRedactionRule::LogRedactionRuleKey(key) => write!(f, "Key({})", key),
+            LogRedactionRule::LogRedactionRuleRegex(regex) => write!(f, "Regex({})", regex.as_str()),
+            LogRedactionRule::LogRedactionRuleCustom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+/// Masks secrets in log messages before any output sees the record.
+#[derive(Clone, Debug)]
+pub struct LogRedactor {
+    rules: Vec<LogRedactionRule>,
+    compiled: Vec<Regex>,
+    mask: String,
+}
+
+impl Default for LogRedactor {
+    fn default() -> Self {
+        LogRedactor {
+            rules: Vec::new(),
+            compiled: Vec::new(),
+            mask: "******".to_string(),
+        }
+    }
+}
+
+impl LogRedactor {
+    pub fn new() -> Self {
+        LogRedactor::default()
+    }
+
+    /// Common credential keys (`password`, `api_key`, `mnemonic`, ...), `rpcpassword=` style
+    /// config lines and HTTP `Authorization` headers.
+    pub fn with_default_preset() -> Self {
+        let mut redactor = LogRedactor::new();
+        for key in DEFAULT_REDACTED_KEYS {
+            redactor = redactor.with_key(key);
+        }
+        let authorization = Regex::new(r"(?i)authorization\s*:\s*(?:basic|bearer)\s+([A-Za-z0-9._~+/=-]+)").unwrap();
+        redactor.with_rule(LogRedactionRule::LogRedactionRuleRegex(authorization))
+    }
+
+    pub fn with_key(self, key: &str) -> Self {
+        self.with_rule(LogRedactionRule::LogRedactionRuleKey(key.to_string()))
+    }
+
+    pub fn with_regex(self, pattern: &str) -> Result<Self, regex::Error> {
+        let regex = Regex::new(pattern)?;
+        Ok(self.with_rule(LogRedactionRule::LogRedactionRuleRegex(regex)))
+    }
+
+    pub fn with_closure<F>(self, redact: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+    {
+        self.with_rule(LogRedactionRule::LogRedactionRuleCustom(Arc::new(redact)))
+    }
+
+    pub fn with_rule(mut self, rule: LogRedactionRule) -> Self {
+        match &rule {
+            LogRedactionRule::LogRedactionRuleKey(key) => self.compiled.push(key_regex(key)),
+            LogRedactionRule::LogRedactionRuleRegex(regex) => self.compiled.push(regex.clone()),
+            LogRedactionRule::LogRedactionRuleCustom(_) => {}
+        }
+        self.rules.push(rule);
+        self
+    }
+
+    /// Replacement written in place of masked content, `******` by default
+    pub fn with_mask(mut self, mask: &str) -> Self {
+        self.mask = mask.to_string();
+        self
+    }
+
+    pub fn rules(&self) -> &[LogRedactionRule] {
+        &self.rules
+    }
+
+    pub fn redact<'a>(&self, message: &'a str) -> Cow<'a, str> {
+        let mut redacted = Cow::Borrowed(message);
+        let mut compiled = self.compiled.iter();
+        for rule in &self.rules {
+            let replaced = match rule {
+                LogRedactionRule::LogRedactionRuleKey(_) | LogRedactionRule::LogRedactionRuleRegex(_) => {
+                    let regex = compiled.next().unwrap();
+                    mask_matches(regex, &redacted, &self.mask)
+                }
+                LogRedactionRule::LogRedactionRuleCustom(redact) => redact(&redacted),
+            };
+            if let Some(replaced) = replaced {
+                redacted = Cow::Owned(replaced);
+            }
+        }
+        redacted
+    }
+
+    /// Masks a structured field: the whole value if `key` is a key rule, otherwise
+    /// string values go through the same rules as messages.
+    pub fn redact_field(&self, key: &str, value: LogFieldValue) -> LogFieldValue {
+        let key_rule = self.rules.iter().any(|rule| match rule {
+            LogRedactionRule::LogRedactionRuleKey(rule_key) => rule_key.eq_ignore_ascii_case(key),
+            _ => false,
+        });
+        match value {
+            _ if key_rule => LogFieldValue::LogFieldValueStr(self.mask.clone()),
+            LogFieldValue::LogFieldValueStr(text) => match self.redact(&text) {
+                Cow::Borrowed(_) => LogFieldValue::LogFieldValueStr(text),
+                Cow::Owned(redacted) => LogFieldValue::LogFieldValueStr(redacted),
+            },
+            other => other,
+        }
+    }
+}
+
+/// Key fragments whose unquoted values may contain spaces, such as seed phrases
+const PHRASE_KEY_PARTS: [&str; 3] = ["mnemonic", "seed", "phrase"];
+
+/// Matches the value that follows `key` in JSON (`"key":"v"`, `"key":123`), escaped JSON
+/// (`\"key\":\"v\"`) or config/query form (`key=v`, `key: "v w"`); the value is the first
+/// participating group. Unquoted values of phrase keys run to the end of the line.
+fn key_regex(key: &str) -> Regex {
+    let unquoted = if PHRASE_KEY_PARTS.iter().any(|part| key.to_ascii_lowercase().contains(part)) {
+        r"[^\r\n,;&]+"
+    } else {
+        r#"[^"'\s&,;]+"#
+    };
+    let key = regex::escape(key);
+    Regex::new(&format!(
+        concat!(
+            r#"(?i)"{key}"\s*:\s*(?:"((?:[^"\\]|\\.)*)"|([^,}}\]\s]+))"#,
+            r#"|\\"{key}\\"\s*:\s*(?:\\"((?:[^"\\]|\\[^"])*)\\"|([^,}}\]\s\\]+))"#,
+            r#"|\b{key}\s*[=:]\s*(?:"((?:[^"\\]|\\.)*)"|'([^']*)'|({unquoted}))"#
+        ),
+        key = key,
+        unquoted = unquoted
+    ))
+    .unwrap()
+}
+
+fn mask_matches(regex: &Regex, message: &str, mask: &str) -> Option<String> {
+    if !regex.is_match(message) {
+        return None;
+    }
+    let replaced = regex.replace_all(message, |caps: &Captures| {
+        let whole = caps.get(0).unwrap();
+        // Mask the first participating group, or the whole match if the regex has none
+        match caps.iter().skip(1).flatten().next() {
+            Some(group) => {
+                let start = group.start() - whole.start();
+                let end = group.end() - whole.start();
+                format!("{}{}{}", &whole.as_str()[..start], mask, &whole.as_str()[end..])
+            }
+            None => mask.to_string(),
+        }
+    });
+    Some(replaced.into_owned())
+}
@@ -0,0 +1,77 @@
+#[cfg(test)]
+use super::rs_box_log;
+#[cfg(test)]
+use super::rs_box_log_test::test_log_dir;
+
+#[test]
+fn test_default_preset_masks_credentials() {
+    let redactor = rs_box_log::LogRedactor::with_default_preset();
+
+    let stratum = r#"{"id":1,"method":"login","params":{"login":"worker1","password":"x-s3cret","api_key":"abc123"}}"#;
+    assert_eq!(
+        redactor.redact(stratum),
+        r#"{"id":1,"method":"login","params":{"login":"worker1","password":"******","api_key":"******"}}"#
+    );
+    assert_eq!(redactor.redact("rpcuser=miner\nrpcpassword=hunter2"), "rpcuser=miner\nrpcpassword=******");
+    assert_eq!(
+        redactor.redact(r#"wallet {"Mnemonic": "abandon abandon about", "index": 0}"#),
+        r#"wallet {"Mnemonic": "******", "index": 0}"#
+    );
+    assert_eq!(redactor.redact("Authorization: Bearer eyJhbGciOi"), "Authorization: Bearer ******");
+    assert_eq!(redactor.redact("share accepted diff=65536"), "share accepted diff=65536");
+}
+
+#[test]
+fn test_multi_word_and_escaped_values() {
+    let redactor = rs_box_log::LogRedactor::with_default_preset();
+
+    // Unquoted seed phrases are masked to the end of the line
+    assert_eq!(
+        redactor.redact("restore mnemonic=abandon ability able about\nnext line"),
+        "restore mnemonic=******\nnext line"
+    );
+    assert_eq!(redactor.redact("seed_phrase: legal winner thank year"), "seed_phrase: ******");
+    // Quoted values are masked up to the closing quote
+    assert_eq!(
+        redactor.redact(r#"rpcpassword="hunter two three" rpcport=8332"#),
+        r#"rpcpassword="******" rpcport=8332"#
+    );
+    assert_eq!(redactor.redact("password='a b c'"), "password='******'");
+    // JSON embedded in a JSON string
+    assert_eq!(
+        redactor.redact(r#"{"body":"{\"password\":\"s3cret\",\"login\":\"w1\"}"}"#),
+        r#"{"body":"{\"password\":\"******\",\"login\":\"w1\"}"}"#
+    );
+    assert_eq!(redactor.redact(r#"{\"token\": 12345}"#), r#"{\"token\": ******}"#);
+}
+
+#[test]
+fn test_regex_and_closure_rules() {
+    let redactor = rs_box_log::LogRedactor::new()
+        .with_regex(r"0x[0-9a-fA-F]{64}")
+        .unwrap()
+        .with_regex(r"card=(\d{12})\d{4}")
+        .unwrap()
+        .with_closure(|message| message.contains("internal").then(|| message.replace("internal", "[hidden]")))
+        .with_mask("<redacted>");
+
+    assert_eq!(
+        redactor.redact(&format!("key 0x{} loaded", "ab".repeat(32))),
+        "key <redacted> loaded"
+    );
+    assert_eq!(redactor.redact("card=1234567890121234"), "card=<redacted>1234");
+    assert_eq!(redactor.redact("internal host"), "[hidden] host");
+}
+
+#[test]
+fn test_redaction_applies_before_output() {
+    let log_dir = test_log_dir("redaction");
+    let config = rs_box_log::LogConfig::new("redaction", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_redactor(rs_box_log::LogRedactor::with_default_preset());
+    let logger = rs_box_log::LoggerManager::with_config(config);
+    logger.log_info_f(r#"rpc request {"rpcpassword":"topsecret"}"#);
+
+    let content = std::fs::read_to_string(format!("{}/redaction/run.log", log_dir)).unwrap();
+    assert!(content.contains(r#"{"rpcpassword":"******"}"#));
+    assert!(!content.contains("topsecret"));
+}
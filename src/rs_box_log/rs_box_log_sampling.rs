@@ -0,0 +1,138 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use crate::rs_box_log::rs_box_log::LogLevel;
+
+/// Which share of a logger's noisy records is kept
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogSampling {
+    /// Keeps the 1st, (N+1)th, (2N+1)th ... record
+    LogSamplingEveryNth(u64),
+    /// Keeps each record with the given probability, from 0.0 to 1.0
+    LogSamplingProbability(f64),
+    /// Keeps the first N records of every second, by the logger's clock
+    LogSamplingFirstPerSecond(u64),
+}
+
+impl LogSampling {
+    /// Reads back the marker written by `Display`, e.g. `1/10`, `p=0.25` or `100/s`
+    pub(crate) fn from_marker(marker: &str) -> Option<Self> {
+        if let Some(rate) = marker.strip_prefix("p=") {
+            return rate.parse().ok().map(LogSampling::LogSamplingProbability);
+        }
+        if let Some(count) = marker.strip_suffix("/s") {
+            return count.parse().ok().map(LogSampling::LogSamplingFirstPerSecond);
+        }
+        marker.strip_prefix("1/")?.parse().ok().map(LogSampling::LogSamplingEveryNth)
+    }
+}
+
+impl fmt::Display for LogSampling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogSampling::LogSamplingEveryNth(n) => write!(f, "1/{}", n),
+            LogSampling::LogSamplingProbability(rate) => write!(f, "p={}", rate),
+            LogSampling::LogSamplingFirstPerSecond(count) => write!(f, "{}/s", count),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SamplerState {
+    seen: AtomicU64,
+    skipped: AtomicU64,
+    /// Current second and records kept in it, for `LogSamplingFirstPerSecond`
+    window: Mutex<(i64, u64)>,
+    seed: u64,
+}
+
+/// Samples the records of a logger at or below a level (DEBUG and TRACE by default);
+/// more severe records always pass. Clones share their counters, so a sampler set
+/// through `LoggerSettings` counts for the logger and the children inheriting it.
+#[derive(Clone, Debug)]
+pub struct LogSampler {
+    sampling: LogSampling,
+    max_level: LogLevel,
+    state: Arc<SamplerState>,
+}
+
+impl LogSampler {
+    pub fn new(sampling: LogSampling) -> Self {
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        LogSampler {
+            sampling,
+            max_level: LogLevel::LogLevelDebug,
+            state: Arc::new(SamplerState {
+                seen: AtomicU64::new(0),
+                skipped: AtomicU64::new(0),
+                window: Mutex::new((i64::MIN, 0)),
+                seed,
+            }),
+        }
+    }
+
+    /// Most severe level that is sampled, e.g. `LogLevelInfo` to sample INFO, DEBUG and TRACE
+    pub fn with_level(mut self, max_level: LogLevel) -> Self {
+        self.max_level = max_level;
+        self
+    }
+
+    pub fn sampling(&self) -> LogSampling {
+        self.sampling
+    }
+
+    pub fn max_level(&self) -> LogLevel {
+        self.max_level
+    }
+
+    /// Records this sampler has skipped so far
+    pub fn skipped(&self) -> u64 {
+        self.state.skipped.load(Ordering::Relaxed)
+    }
+
+    /// Records this sampler has let through so far
+    pub fn kept(&self) -> u64 {
+        // `seen` is bumped before `skipped`; read `skipped` first and saturate so a
+        // concurrent `decide` cannot make this underflow
+        let skipped = self.skipped();
+        self.state.seen.load(Ordering::Relaxed).saturating_sub(skipped)
+    }
+
+    /// `None` if `level` is not sampled, otherwise whether to keep the record. `second`
+    /// is the record's time in whole seconds.
+    pub(crate) fn decide(&self, level: LogLevel, second: i64) -> Option<bool> {
+        if level.severity() < self.max_level.severity() {
+            return None;
+        }
+        let index = self.state.seen.fetch_add(1, Ordering::Relaxed);
+        let keep = match self.sampling {
+            LogSampling::LogSamplingEveryNth(n) => index.is_multiple_of(n.max(1)),
+            LogSampling::LogSamplingProbability(rate) => {
+                let draw = splitmix64(self.state.seed.wrapping_add(index)) as f64 / u64::MAX as f64;
+                draw < rate
+            }
+            LogSampling::LogSamplingFirstPerSecond(count) => {
+                let mut window = self.state.window.lock().unwrap();
+                if window.0 != second {
+                    *window = (second, 0);
+                }
+                window.1 += 1;
+                window.1 <= count
+            }
+        };
+        if !keep {
+            self.state.skipped.fetch_add(1, Ordering::Relaxed);
+        }
+        Some(keep)
+    }
+}
+
+/// Spreads a counter over the full `u64` range, a cheap stand-in for a random generator
+fn splitmix64(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
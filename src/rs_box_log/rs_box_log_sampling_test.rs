@@ -0,0 +1,118 @@
+#[cfg(test)]
+use super::rs_box_log;
+#[cfg(test)]
+use super::rs_box_log_hierarchy_test::MemorySink;
+#[cfg(test)]
+use super::rs_box_log_sampling::LogSampling;
+#[cfg(test)]
+use super::rs_box_log_test::{level_compiled_in, test_log_dir};
+#[cfg(test)]
+use chrono::{DateTime, Duration};
+#[cfg(test)]
+use std::sync::Arc;
+
+#[cfg(test)]
+fn sampled_logger(
+    project: &str,
+    log_dir: Option<&str>,
+    sampler: &rs_box_log::LogSampler,
+) -> (rs_box_log::LoggerManager, Arc<MemorySink>) {
+    let sink = Arc::new(MemorySink::default());
+    let config = rs_box_log::LogConfig::new(
+        project,
+        log_dir.is_some(),
+        log_dir.unwrap_or("./logs"),
+        rs_box_log::LogLevel::LogLevelTrace,
+        7,
+    )
+    .with_sampler(sampler.clone())
+    .with_sink(sink.clone());
+    (rs_box_log::LoggerManager::with_config(config), sink)
+}
+
+#[test]
+fn test_sample_every_nth_and_mark_records() {
+    if !level_compiled_in(rs_box_log::LogLevel::LogLevelDebug) {
+        return;
+    }
+    let log_dir = test_log_dir("sampling_nth");
+    let sampler = rs_box_log::LogSampler::new(LogSampling::LogSamplingEveryNth(3));
+    let (logger, sink) = sampled_logger("sampled_nth", Some(&log_dir), &sampler);
+
+    for index in 0..9 {
+        logger.log_debug_f(&format!("tick {}", index));
+    }
+    logger.log_error_f("never sampled");
+
+    let records = sink.records.lock().unwrap().clone();
+    let messages: Vec<&str> = records.iter().map(|record| record.message.as_str()).collect();
+    assert_eq!(messages, vec!["tick 0", "tick 3", "tick 6", "never sampled"]);
+    assert_eq!(records[0].sampling, Some(LogSampling::LogSamplingEveryNth(3)));
+    assert_eq!(records[3].sampling, None);
+    assert_eq!((sampler.kept(), sampler.skipped()), (3, 6));
+
+    let content = std::fs::read_to_string(format!("{}/sampled_nth/run.log", log_dir)).unwrap();
+    assert!(content.contains("[DEBUG]\x1b[0m [sampled:1/3]"));
+    assert!(!content.contains("[ERROR]\x1b[0m [sampled"));
+
+    for sampling in [
+        LogSampling::LogSamplingEveryNth(10),
+        LogSampling::LogSamplingProbability(0.25),
+        LogSampling::LogSamplingFirstPerSecond(100),
+    ] {
+        assert_eq!(LogSampling::from_marker(&sampling.to_string()), Some(sampling));
+    }
+}
+
+#[test]
+fn test_sample_by_probability() {
+    if !level_compiled_in(rs_box_log::LogLevel::LogLevelTrace) {
+        return;
+    }
+    let sampler = rs_box_log::LogSampler::new(LogSampling::LogSamplingProbability(0.25));
+    let (logger, sink) = sampled_logger("sampled_rate", None, &sampler);
+    for _ in 0..4000 {
+        logger.log_trace_f("noise");
+    }
+    let kept = sink.records.lock().unwrap().len() as u64;
+    assert!((800..1200).contains(&kept), "kept {}", kept);
+    assert_eq!(sampler.kept(), kept);
+    assert_eq!(sampler.skipped(), 4000 - kept);
+
+    let none = rs_box_log::LogSampler::new(LogSampling::LogSamplingProbability(0.0));
+    let (logger, sink) = sampled_logger("sampled_none", None, &none);
+    logger.log_debug_f("dropped");
+    assert!(sink.records.lock().unwrap().is_empty());
+}
+
+#[test]
+fn test_sample_first_per_second() {
+    let clock = Arc::new(rs_box_log::LogManualClock::new(DateTime::parse_from_rfc3339("2024-05-17T10:00:00.100+00:00").unwrap()));
+    let sampler = rs_box_log::LogSampler::new(LogSampling::LogSamplingFirstPerSecond(2)).with_level(rs_box_log::LogLevel::LogLevelInfo);
+    let sink = Arc::new(MemorySink::default());
+    let config = rs_box_log::LogConfig::new("sampled_burst", false, "./logs", rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_clock(clock.clone())
+        .with_sampler(sampler.clone())
+        .with_sink(sink.clone());
+    let logger = rs_box_log::LoggerManager::with_config(config);
+    let sampled_out_before = rs_box_log::log_metrics_snapshot().sampled_out_records;
+
+    for _ in 0..5 {
+        logger.log_info_f("first second");
+    }
+    logger.log_warning_f("not sampled");
+    clock.advance(Duration::milliseconds(900));
+    for _ in 0..3 {
+        logger.log_debug_f("next second");
+    }
+
+    let messages: Vec<String> = sink.records.lock().unwrap().iter().map(|record| record.message.clone()).collect();
+    // The DEBUG records of the next second only exist when DEBUG is compiled in
+    let next_second = if level_compiled_in(rs_box_log::LogLevel::LogLevelDebug) { 2 } else { 0 };
+    let mut expected = vec!["first second", "first second", "not sampled"];
+    expected.extend(std::iter::repeat_n("next second", next_second));
+    assert_eq!(messages, expected);
+    let skipped = 3 + next_second as u64 / 2;
+    assert_eq!(sampler.skipped(), skipped);
+    assert!(rs_box_log::log_metrics_snapshot().sampled_out_records >= sampled_out_before + skipped);
+}
@@ -0,0 +1,161 @@
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use chrono::{DateTime, SecondsFormat, Utc};
+use crate::rs_box_log::rs_box_log::{with_default_logger, LogConfig, LogOutputFormat, LogRecord};
+use super::rs_box_log_format::json_escape;
+use super::rs_box_log_metrics::{log_metrics_snapshot, log_started, recent_errors, LogMetricsSnapshot, LOG_LEVELS};
+
+/// Set once the shutdown summary of the current setup is written
+static SHUTDOWN_SUMMARY_WRITTEN: AtomicBool = AtomicBool::new(false);
+
+/// Health snapshot of a run: how long it took, what was logged and what went wrong.
+#[derive(Clone, Debug)]
+pub struct LogSummary {
+    /// When the first logger was set up
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub runtime: Duration,
+    pub metrics: LogMetricsSnapshot,
+    /// Latest ERROR records, oldest first
+    pub recent_errors: Vec<LogRecord>,
+}
+
+impl LogSummary {
+    /// Renders the summary the way file headers are written: a `#` block for text
+    /// output, a single `{"summary":{...}}` line for JSON output.
+    pub fn render(&self, format: LogOutputFormat) -> String {
+        match format {
+            LogOutputFormat::LogOutputFormatText => self.render_text(),
+            LogOutputFormat::LogOutputFormatJson => self.render_json(),
+        }
+    }
+
+    fn render_text(&self) -> String {
+        let mut fields = vec![
+            ("started_at".to_string(), self.started_at.to_rfc3339_opts(SecondsFormat::Millis, false)),
+            ("ended_at".to_string(), self.ended_at.to_rfc3339_opts(SecondsFormat::Millis, false)),
+            ("runtime".to_string(), format_runtime(self.runtime)),
+        ];
+        for level in LOG_LEVELS {
+            fields.push((format!("records.{}", level.to_str()), self.metrics.records(level).to_string()));
+        }
+        fields.extend(
+            self.counters()
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value.to_string())),
+        );
+        for (index, record) in self.recent_errors.iter().enumerate() {
+            fields.push((format!("last_error.{}", index + 1), format_error(record)));
+        }
+
+        let mut summary = String::from("# ==================== rs_box log summary ===================\n");
+        for (key, value) in &fields {
+            let _ = writeln!(summary, "# {:<24} = {}", key, value.replace('\n', "\\n"));
+        }
+        summary.push_str("# ============================================================\n");
+        summary
+    }
+
+    fn render_json(&self) -> String {
+        let records = LOG_LEVELS
+            .iter()
+            .map(|level| format!("{}:{}", json_escape(level.to_str()), self.metrics.records(*level)))
+            .collect::<Vec<_>>()
+            .join(",");
+        let counters = self
+            .counters()
+            .iter()
+            .map(|(key, value)| format!("{}:{}", json_escape(key), value))
+            .collect::<Vec<_>>()
+            .join(",");
+        let errors = self
+            .recent_errors
+            .iter()
+            .map(|record| {
+                format!(
+                    "{{\"time\":{},\"logger\":{},\"message\":{}}}",
+                    json_escape(&record.time.to_rfc3339()),
+                    json_escape(&record.logger_name),
+                    json_escape(&record.message)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            "{{\"summary\":{{\"started_at\":{},\"ended_at\":{},\"runtime_secs\":{:.3},\"records\":{{{}}},{},\"last_errors\":[{}]}}}}\n",
+            json_escape(&self.started_at.to_rfc3339()),
+            json_escape(&self.ended_at.to_rfc3339()),
+            self.runtime.as_secs_f64(),
+            records,
+            counters,
+            errors
+        )
+    }
+
+    fn counters(&self) -> [(&'static str, u64); 6] {
+        [
+            ("rotations", self.metrics.rotations),
+            ("deleted_files", self.metrics.deleted_files),
+            ("dropped_records", self.metrics.dropped_records),
+            ("sampled_out_records", self.metrics.sampled_out_records),
+            ("write_failures", self.metrics.write_failures),
+            ("bytes_written", self.metrics.bytes_written),
+        ]
+    }
+}
+
+/// `2d 03:04:05.678`, the day count only when there is one
+fn format_runtime(runtime: Duration) -> String {
+    let seconds = runtime.as_secs();
+    let clock = format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        seconds / 3600 % 24,
+        seconds / 60 % 60,
+        seconds % 60,
+        runtime.subsec_millis()
+    );
+    match seconds / 86400 {
+        0 => clock,
+        days => format!("{}d {}", days, clock),
+    }
+}
+
+fn format_error(record: &LogRecord) -> String {
+    format!(
+        "{} [{}] {}",
+        record.time.to_rfc3339_opts(SecondsFormat::Secs, false),
+        record.logger_name,
+        record.message
+    )
+}
+
+/// Summary of the run so far, over all loggers of the process
+pub fn log_summary() -> LogSummary {
+    let (started, started_at) = log_started();
+    LogSummary {
+        started_at,
+        ended_at: Utc::now(),
+        runtime: started.elapsed(),
+        metrics: log_metrics_snapshot(),
+        recent_errors: recent_errors(),
+    }
+}
+
+/// Writes `log_summary()` to the default logger's output, in its output format
+pub fn write_log_summary() {
+    let summary = log_summary();
+    with_default_logger(|logger| logger.write_raw(|config| summary.render(config.output_format())));
+}
+
+/// Writes the summary once per setup, if `config` asks for it
+pub(crate) fn write_shutdown_summary(config: &LogConfig) {
+    if config.shutdown_summary.is_some() && !SHUTDOWN_SUMMARY_WRITTEN.swap(true, Ordering::SeqCst) {
+        write_log_summary();
+    }
+}
+
+/// Lets the next graceful exit write a summary again
+pub(crate) fn rearm_shutdown_summary() {
+    SHUTDOWN_SUMMARY_WRITTEN.store(false, Ordering::SeqCst);
+}
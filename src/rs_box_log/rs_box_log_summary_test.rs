@@ -0,0 +1,67 @@
+#[cfg(test)]
+use super::rs_box_log;
+#[cfg(test)]
+use super::rs_box_log_test::{lock_global_config, test_log_dir};
+
+#[test]
+fn test_shutdown_summary_written_once_on_guard_drop() {
+    let _global = lock_global_config();
+    let log_dir = test_log_dir("summary_text");
+    let config = rs_box_log::LogConfig::new("summary_text", true, &log_dir, rs_box_log::LogLevel::LogLevelInfo, 7)
+        .with_shutdown_summary(100);
+    let guard = rs_box_log::setup_log_tools_with_config(config.clone());
+    let before = rs_box_log::log_metrics_snapshot();
+
+    rs_box_log::log_info("pool started");
+    rs_box_log::log_warning("share rejected");
+    for index in 1..=3 {
+        rs_box_log::log_error(&format!("disk full {}", index));
+    }
+    drop(guard);
+    // The guard already wrote it for this setup
+    rs_box_log::shutdown();
+
+    let content = std::fs::read_to_string(&config.log_files()[0].path).unwrap();
+    assert_eq!(content.matches("rs_box log summary").count(), 1);
+    let summary = &content[content.find("rs_box log summary").unwrap()..];
+    assert!(summary.contains("\n# runtime                  = 00:00:"));
+    let errors_line = summary.lines().find(|line| line.starts_with("# records.ERROR")).unwrap();
+    let errors: u64 = errors_line.rsplit(' ').next().unwrap().parse().unwrap();
+    assert!(errors >= before.records(rs_box_log::LogLevel::LogLevelError) + 3);
+    assert!(summary.contains("\n# deleted_files            = "));
+    let first = summary.find("[summary_text] disk full 1").unwrap();
+    assert!(first < summary.find("[summary_text] disk full 2").unwrap());
+    assert!(summary.find("[summary_text] disk full 2").unwrap() < summary.find("[summary_text] disk full 3").unwrap());
+    assert!(summary.ends_with("# ============================================================\n"));
+
+    // rs_box-logview leaves the block out instead of joining it to the last record
+    let entries = rs_box_log::parse_log_entries("summary_text", &content);
+    assert_eq!(entries.len(), 5);
+    assert_eq!(rs_box_log::strip_log_colors(&entries[4].line), content.lines().nth(4).map(rs_box_log::strip_log_colors).unwrap());
+}
+
+#[test]
+fn test_summary_json_and_error_limit() {
+    let _global = lock_global_config();
+    let log_dir = test_log_dir("summary_json");
+    let config = rs_box_log::LogConfig::new("summary_json", true, &log_dir, rs_box_log::LogLevel::LogLevelInfo, 7)
+        .with_output_format(rs_box_log::LogOutputFormat::LogOutputFormatJson)
+        .with_shutdown_summary(2);
+    let _guard = rs_box_log::setup_log_tools_with_config(config.clone());
+
+    for index in 1..=4 {
+        rs_box_log::log_error(&format!("pool \"{}\" down", index));
+    }
+    let summary = rs_box_log::log_summary();
+    assert!(summary.recent_errors.len() <= 2);
+    assert!(summary.runtime.as_secs_f64() >= 0.0 && summary.started_at <= summary.ended_at);
+
+    rs_box_log::write_log_summary();
+    let content = std::fs::read_to_string(&config.log_files()[0].path).unwrap();
+    let line = content.lines().last().unwrap();
+    assert!(line.starts_with("{\"summary\":{\"started_at\":"));
+    assert!(line.contains(",\"records\":{\"INFO\":"));
+    assert!(line.contains(",\"rotations\":") && line.contains(",\"deleted_files\":"));
+    assert!(line.contains("\"message\":\"pool \\\"4\\\" down\"}]}}"));
+    assert!(!line.contains("pool \\\"1\\\" down"));
+}
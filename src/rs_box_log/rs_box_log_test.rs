@@ -2,8 +2,9 @@
 use super::rs_box_log;
 #[test]
 fn test_logs_with_write_logfile() {
+    let _global = lock_global_config();
 
-    rs_box_log::setup_log_tools("test_project",true,"",rs_box_log::LogLevel::LogLevelDebug,7);
+    let _log_guard = rs_box_log::setup_log_tools("test_project",true,"",rs_box_log::LogLevel::LogLevelDebug,7);
     rs_box_log::log_info("This is an info message");
     rs_box_log::log_error("This is an error message");
     rs_box_log::log_warning("This is an warning message");
@@ -33,7 +34,8 @@ fn test_logs_with_write_logfile() {
 
 #[test]
 fn test_logs_with_terminal_show() {
-    rs_box_log::setup_log_tools("test_terminal_show",false,"",rs_box_log::LogLevel::LogLevelDebug,7);
+    let _global = lock_global_config();
+    let _log_guard = rs_box_log::setup_log_tools("test_terminal_show",false,"",rs_box_log::LogLevel::LogLevelDebug,7);
     rs_box_log::log_info("This is an info message", );
     rs_box_log::log_error("This is an error message");
     rs_box_log::log_warning("This is an warning message");
@@ -64,7 +66,8 @@ fn test_logs_with_terminal_show() {
 
 #[test]
 fn test_simple_log_output() {
-    rs_box_log::setup_log_tools("test_simple", false, "./logs", rs_box_log::LogLevel::LogLevelDebug, 7);
+    let _global = lock_global_config();
+    let _log_guard = rs_box_log::setup_log_tools("test_simple", false, "./logs", rs_box_log::LogLevel::LogLevelDebug, 7);
     rs_box_log::log_info("This is a debug message info");
     rs_box_log::log_error("This is a debug message err");
     rs_box_log::log_warning("This is a debug message war");
@@ -78,4 +81,269 @@ fn test_none_setup_status() {
     rs_box_log::log_warning("This is a debug message with none setup status warning");
     rs_box_log::log_error("This is a debug message with none setup status   error");
     rs_box_log::log_trace("This is a debug message with none setup status   trace");
-}
\ No newline at end of file
+}
+/// Serializes the tests that swap the global config
+#[cfg(test)]
+pub(crate) fn lock_global_config() -> std::sync::MutexGuard<'static, ()> {
+    static GLOBAL_CONFIG_TEST_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    GLOBAL_CONFIG_TEST_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+pub(crate) fn test_log_dir(name: &str) -> String {
+    let dir = std::env::temp_dir().join(format!("rs_box_log_test_{}_{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir.display().to_string()
+}
+
+/// Whether the `max_level_*` features keep records of `level`; tests of DEBUG and TRACE
+/// records skip those checks when the build compiles them out
+#[cfg(test)]
+#[allow(
+    clippy::absurd_extreme_comparisons,
+    reason = "the compiled-in maximum is 0 with `max_level_off`, which rejects every level"
+)]
+pub(crate) fn level_compiled_in(level: rs_box_log::LogLevel) -> bool {
+    level.severity() <= rs_box_log::STATIC_MAX_LEVEL_SEVERITY
+}
+
+#[test]
+fn test_buffered_write_policy_flush() {
+    let log_dir = test_log_dir("buffered");
+    let config = rs_box_log::LogConfig::new("buffered", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_write_policy(rs_box_log::LogWritePolicy::LogWritePolicyBuffered(std::time::Duration::from_secs(3600)));
+    let logger = rs_box_log::LoggerManager::with_config(config);
+    logger.log_info_f("buffered message");
+
+    rs_box_log::flush();
+    let content = std::fs::read_to_string(format!("{}/buffered/run.log", log_dir)).unwrap();
+    assert!(content.contains("buffered message"));
+}
+
+#[test]
+fn test_sync_on_error_write_policy() {
+    let log_dir = test_log_dir("sync_on_error");
+    let config = rs_box_log::LogConfig::new("sync_on_error", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_write_policy(rs_box_log::LogWritePolicy::LogWritePolicySyncOnError(std::time::Duration::from_secs(3600)));
+    let logger = rs_box_log::LoggerManager::with_config(config);
+    logger.log_info_f("info before error");
+    logger.log_error_f("error is synced");
+
+    // The ERROR record pushes out everything buffered before it
+    let content = std::fs::read_to_string(format!("{}/sync_on_error/run.log", log_dir)).unwrap();
+    assert!(content.contains("info before error"));
+    assert!(content.contains("error is synced"));
+}
+
+#[test]
+fn test_log_guard_flushes_on_drop() {
+    let _global = lock_global_config();
+    let log_dir = test_log_dir("guard");
+    let config = rs_box_log::LogConfig::new("guard", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_write_policy(rs_box_log::LogWritePolicy::LogWritePolicyBuffered(std::time::Duration::from_secs(3600)));
+    let logger = rs_box_log::LoggerManager::with_config(config);
+    {
+        let _guard = rs_box_log::setup_log_tools("test_guard", false, "", rs_box_log::LogLevel::LogLevelDebug, 7);
+        logger.log_warning_f("written before guard drop");
+    }
+    let content = std::fs::read_to_string(format!("{}/guard/run.log", log_dir)).unwrap();
+    assert!(content.contains("written before guard drop"));
+}
+
+#[test]
+fn test_logger_registry_returns_shared_instance() {
+    let first = rs_box_log::LoggerManager::get("registry_shared");
+    let second = rs_box_log::LoggerManager::get("registry_shared");
+    let other = rs_box_log::LoggerManager::get("registry_other");
+    assert!(std::sync::Arc::ptr_eq(&first, &second));
+    assert!(!std::sync::Arc::ptr_eq(&first, &other));
+}
+
+#[test]
+fn test_loggers_with_same_name_share_file_handle() {
+    let log_dir = test_log_dir("shared_handle");
+    let config = rs_box_log::LogConfig::new("shared_handle", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_write_policy(rs_box_log::LogWritePolicy::LogWritePolicyBuffered(std::time::Duration::from_secs(3600)));
+    let first = rs_box_log::LoggerManager::with_config(config.clone());
+    let second = rs_box_log::LoggerManager::with_config(config);
+
+    // With one shared buffer the lines keep their call order; separate buffers would group them per logger
+    first.log_info_f("line 1");
+    second.log_info_f("line 2");
+    first.log_info_f("line 3");
+    rs_box_log::flush();
+
+    let content = std::fs::read_to_string(format!("{}/shared_handle/run.log", log_dir)).unwrap();
+    let messages: Vec<&str> = content.lines().filter_map(|line| line.rsplit('[').next()).collect();
+    assert_eq!(messages, vec!["line 1]", "line 2]", "line 3]"]);
+    let date_dirs = std::fs::read_dir(format!("{}/shared_handle", log_dir)).unwrap().count();
+    assert_eq!(date_dirs, 2); // one date directory plus run.log
+}
+
+#[test]
+fn test_process_lock_waits_for_other_writers() {
+    let log_dir = test_log_dir("process_lock");
+    let config = rs_box_log::LogConfig::new("locked", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_process_lock(true);
+    let logger = rs_box_log::LoggerManager::with_config(config.clone());
+    logger.log_info_f("before lock");
+    let path = config.log_files()[0].path.clone();
+
+    // Stands in for another process holding the lock while it appends
+    let other = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+    other.lock().unwrap();
+    let writer = std::thread::spawn(move || logger.log_info_f("while locked"));
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    assert!(!std::fs::read_to_string(&path).unwrap().contains("while locked"));
+
+    other.unlock().unwrap();
+    writer.join().unwrap();
+    assert!(std::fs::read_to_string(&path).unwrap().contains("while locked"));
+}
+
+#[test]
+fn test_shared_handle_reconciles_settings() {
+    let log_dir = test_log_dir("shared_settings");
+    let config = rs_box_log::LogConfig::new("shared_settings", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7);
+    let plain = rs_box_log::LoggerManager::with_config(config.clone());
+    plain.log_info_f("plain");
+    let path = config.log_files()[0].path.clone();
+
+    // A logger asking for the process lock makes the shared handle lock for everyone
+    let locked = rs_box_log::LoggerManager::with_config(config.clone().with_process_lock(true));
+    locked.log_info_f("locked");
+    let other = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+    other.lock().unwrap();
+    let writer = std::thread::spawn(move || plain.log_info_f("plain while locked"));
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    assert!(!std::fs::read_to_string(&path).unwrap().contains("plain while locked"));
+    other.unlock().unwrap();
+    writer.join().unwrap();
+    assert!(std::fs::read_to_string(&path).unwrap().contains("plain while locked"));
+
+    // An integrity chain cannot join an unchained file
+    let chained = rs_box_log::LoggerManager::with_config(config.clone().with_integrity(rs_box_log::LogIntegrity::new(b"key")));
+    chained.log_info_f("chained");
+    let content = std::fs::read_to_string(&path).unwrap();
+    assert!(content.contains("plain while locked") && !content.contains("chained"));
+    assert!(!content.contains("# chain"));
+
+    // Nor can one with another key join a chained file
+    let config = rs_box_log::LogConfig::new("shared_chain", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7);
+    let first = rs_box_log::LoggerManager::with_config(config.clone().with_integrity(rs_box_log::LogIntegrity::new(b"key a")));
+    first.log_info_f("key a");
+    let second = rs_box_log::LoggerManager::with_config(config.clone().with_integrity(rs_box_log::LogIntegrity::new(b"key b")));
+    second.log_info_f("key b");
+    let content = std::fs::read_to_string(&config.log_files()[0].path).unwrap();
+    assert!(content.contains("[key a]") && !content.contains("[key b]"));
+
+    // The fallible constructor returns the refusal instead of logging to the console
+    let refused = rs_box_log::LoggerManager::try_with_config(config.clone().with_integrity(rs_box_log::LogIntegrity::new(b"key b")));
+    assert_eq!(refused.err().map(|e| e.kind()), Some(std::io::ErrorKind::InvalidInput));
+    assert!(rs_box_log::LoggerManager::try_with_config(config.with_integrity(rs_box_log::LogIntegrity::new(b"key a"))).is_ok());
+}
+
+#[test]
+fn test_reconfigure_reaches_live_loggers() {
+    let _global = lock_global_config();
+    let log_dir = test_log_dir("reconfigure");
+    let config_a = rs_box_log::LogConfig::new("reconf_a", true, &log_dir, rs_box_log::LogLevel::LogLevelInfo, 7)
+        .with_file_header(true)
+        .with_write_policy(rs_box_log::LogWritePolicy::LogWritePolicyBuffered(std::time::Duration::from_secs(3600)));
+    let _guard = rs_box_log::setup_log_tools_with_config(config_a.clone());
+    let named = rs_box_log::LoggerManager::new("reconf_named");
+
+    rs_box_log::log_info("a1");
+    named.log_debug_f("debug hidden");
+    rs_box_log::update_log_config(Some(rs_box_log::LogLevel::LogLevelDebug), None, None);
+    rs_box_log::log_info("a2");
+    named.log_debug_f("debug shown");
+    rs_box_log::flush();
+
+    // Same path: the buffered handle is kept, so nothing is lost and no second header appears
+    let content_a = std::fs::read_to_string(&config_a.log_files()[0].path).unwrap();
+    assert_eq!(content_a.matches("rs_box log header").count(), 1);
+    assert!(content_a.find("[a1]").unwrap() < content_a.find("[a2]").unwrap());
+    let named_config = rs_box_log::LogConfig::new("reconf_named", true, &log_dir, rs_box_log::LogLevel::LogLevelDebug, 7);
+    named.log_info_f("info shown");
+    let content_named = std::fs::read_to_string(&named_config.log_files()[0].path).unwrap();
+    assert!(!content_named.contains("debug hidden"));
+    if level_compiled_in(rs_box_log::LogLevel::LogLevelDebug) {
+        assert!(content_named.contains("debug shown"));
+    }
+
+    // A new project name moves the default logger to another file
+    let config_b = rs_box_log::LogConfig::new("reconf_b", true, &log_dir, rs_box_log::LogLevel::LogLevelInfo, 7);
+    rs_box_log::reconfigure_log(config_b.clone());
+    rs_box_log::log_info("b1");
+    assert!(std::fs::read_to_string(&config_b.log_files()[0].path).unwrap().contains("[b1]"));
+
+    // Disabling the file output sends later records to stdout only
+    rs_box_log::reconfigure_log(rs_box_log::LogConfig::new("reconf_b", false, &log_dir, rs_box_log::LogLevel::LogLevelInfo, 7));
+    rs_box_log::log_info("c1");
+    named.log_info_f("c2");
+    assert!(!std::fs::read_to_string(&config_b.log_files()[0].path).unwrap().contains("[c1]"));
+    assert!(!std::fs::read_to_string(&named_config.log_files()[0].path).unwrap().contains("[c2]"));
+}
+
+#[test]
+fn test_level_filter_follows_severity() {
+    let log_dir = test_log_dir("severity");
+    let config = rs_box_log::LogConfig::new("severity", true, &log_dir, rs_box_log::LogLevel::LogLevelWarning, 7);
+    let logger = rs_box_log::LoggerManager::with_config(config.clone());
+    assert!(logger.enabled(rs_box_log::LogLevel::LogLevelError));
+    assert!(!logger.enabled(rs_box_log::LogLevel::LogLevelInfo));
+
+    logger.log_error_f("error passes");
+    logger.log_warning_f("warning passes");
+    logger.log_info_f("info filtered");
+    logger.log_debug_f("debug filtered");
+
+    let content = std::fs::read_to_string(&config.log_files()[0].path).unwrap();
+    assert!(content.contains("error passes") && content.contains("warning passes"));
+    assert!(!content.contains("info filtered") && !content.contains("debug filtered"));
+}
+
+#[test]
+fn test_macros_skip_formatting_below_level() {
+    let _global = lock_global_config();
+    let _guard = rs_box_log::setup_log_tools("macro_level", false, "", rs_box_log::LogLevel::LogLevelInfo, 7);
+    let formatted = std::sync::atomic::AtomicUsize::new(0);
+    let argument = || {
+        formatted.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        "argument"
+    };
+
+    crate::log_tracef!("trace {}", argument());
+    crate::log_debugf!("debug {}", argument());
+    assert_eq!(formatted.load(std::sync::atomic::Ordering::SeqCst), 0);
+    crate::log_infof!("info {}", argument());
+    assert_eq!(formatted.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_static_max_level_drops_records_above_it() {
+    let log_dir = test_log_dir("static_max_level");
+    let config = rs_box_log::LogConfig::new("static_max_level", true, &log_dir, rs_box_log::LogLevel::LogLevelTrace, 7);
+    let logger = rs_box_log::LoggerManager::with_config(config.clone());
+    let levels = [
+        rs_box_log::LogLevel::LogLevelError,
+        rs_box_log::LogLevel::LogLevelWarning,
+        rs_box_log::LogLevel::LogLevelInfo,
+        rs_box_log::LogLevel::LogLevelDebug,
+        rs_box_log::LogLevel::LogLevelTrace,
+    ];
+    for level in levels {
+        logger.log_with_fields(level, &format!("{} record", level.to_str()), Vec::new());
+    }
+
+    let content = config
+        .log_files()
+        .first()
+        .map(|entry| std::fs::read_to_string(&entry.path).unwrap())
+        .unwrap_or_default();
+    for level in levels {
+        assert_eq!(logger.enabled(level), level_compiled_in(level), "{:?}", level);
+        assert_eq!(content.contains(&format!("[{} record]", level.to_str())), level_compiled_in(level), "{:?}", level);
+    }
+}
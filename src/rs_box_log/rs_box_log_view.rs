@@ -0,0 +1,512 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, NaiveDateTime, TimeZone};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use crate::rs_box_log::rs_box_log::{LogLevel, LogRecord};
+use super::rs_box_log_fields::LogFieldValue;
+use super::rs_box_log_format::render_json;
+use super::rs_box_log_integrity::is_chain_line;
+use super::rs_box_log_layout::LogFileLayout;
+use super::rs_box_log_sampling::LogSampling;
+
+static ANSI_ESCAPE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\x1b\[[0-9;]*m").unwrap());
+
+static TEXT_START: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^\[\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2} [+-]\d{2}:\d{2}\] \[[A-Z]+\]").unwrap());
+
+static TEXT_ENTRY: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(concat!(
+        r"(?s)^\[(?P<time>[^\]]+)\] \[(?P<level>[A-Z]+)\]",
+        r"(?: \[sampled:(?P<sampled>[^\]]*)\])?",
+        r"(?: \[logger:(?P<logger>[^\]]*)\])?",
+        r"(?: \[(?P<identity>(?:thread|tid|pid|host):[^\]]*)\])?",
+        r"(?: (?P<location>\[package:[^\]]*\]|unknown))?",
+        r" \[(?P<rest>.*)$"
+    ))
+    .unwrap()
+});
+
+static TEXT_FIELDS: Lazy<Regex> = Lazy::new(|| Regex::new(r#"^(?: [^\s=]+=(?:"(?:[^"\\]|\\.)*"|\S+))*$"#).unwrap());
+
+static TEXT_FIELD: Lazy<Regex> = Lazy::new(|| Regex::new(r#" ([^\s=]+)=("(?:[^"\\]|\\.)*"|\S+)"#).unwrap());
+
+static JSON_ENTRY: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"^\{"time":("(?:[^"\\]|\\.)*"),"level":"([A-Z]+)","logger":("(?:[^"\\]|\\.)*")"#).unwrap()
+});
+
+/// `line` without ANSI color codes
+pub fn strip_log_colors(line: &str) -> String {
+    ANSI_ESCAPE.replace_all(line, "").into_owned()
+}
+
+/// Parses `--since`/`--until` style times: RFC 3339, `YYYY-MM-DD HH:MM:SS` or `YYYY-MM-DD`, the
+/// last two in local time
+pub fn parse_log_view_time(value: &str) -> Result<DateTime<FixedOffset>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time);
+    }
+    let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|date| date.and_hms_opt(0, 0, 0).unwrap()))
+        .map_err(|_| format!("invalid time {:?}, expected YYYY-MM-DD[ HH:MM:SS] or RFC 3339", value))?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|time| time.fixed_offset())
+        .ok_or_else(|| format!("time {:?} does not exist in the local time zone", value))
+}
+
+/// One record read back from a log file, continuation lines included
+#[derive(Clone, Debug)]
+pub struct LogViewEntry {
+    /// Log directory of the record relative to `log_dir`, e.g. `pool` or `pool/stratum`
+    pub module: String,
+    pub time: DateTime<FixedOffset>,
+    pub level: LogLevel,
+    pub logger: String,
+    /// The record as written, possibly with color codes
+    pub line: String,
+    /// The parsed text record, used for JSON conversion; `None` for JSON lines
+    record: Option<LogRecord>,
+}
+
+impl LogViewEntry {
+    /// Parses one record in the default text format or the JSON format
+    pub fn parse(module: &str, line: &str) -> Option<Self> {
+        let plain = strip_log_colors(line);
+        if let Some(captures) = JSON_ENTRY.captures(&plain) {
+            let time = DateTime::parse_from_rfc3339(&json_unescape(&captures[1])?).ok()?;
+            return Some(LogViewEntry {
+                module: module.to_string(),
+                time,
+                level: LogLevel::from_name(&captures[2])?,
+                logger: json_unescape(&captures[3])?,
+                line: line.to_string(),
+                record: None,
+            });
+        }
+
+        let record = parse_text_record(module, &plain)?;
+        Some(LogViewEntry {
+            module: module.to_string(),
+            time: record.time.fixed_offset(),
+            level: record.level,
+            logger: record.logger_name.clone(),
+            line: line.to_string(),
+            record: Some(record),
+        })
+    }
+
+    /// The record as one JSON line, in the same layout `LogOutputFormatJson` writes
+    pub fn to_json(&self) -> String {
+        match self.record {
+            Some(ref record) => render_json(record).trim_end().to_string(),
+            None => strip_log_colors(&self.line),
+        }
+    }
+}
+
+fn parse_text_record(module: &str, plain: &str) -> Option<LogRecord> {
+    let captures = TEXT_ENTRY.captures(plain)?;
+    let time = DateTime::parse_from_str(&captures["time"], "%Y-%m-%d %H:%M:%S %:z").ok()?;
+    let rest = &captures["rest"];
+    // The message ends at the first `]` followed by nothing but ` key=value` fields
+    let end = rest
+        .match_indices(']')
+        .map(|(index, _)| index)
+        .find(|&index| TEXT_FIELDS.is_match(&rest[index + 1..]))?;
+
+    let mut record = LogRecord {
+        time: time.to_utc(),
+        level: LogLevel::from_name(&captures["level"])?,
+        logger_name: captures
+            .name("logger")
+            .map_or_else(|| module.replace('/', "."), |logger| logger.as_str().to_string()),
+        location: captures.name("location").map_or_else(String::new, |location| location.as_str().to_string()),
+        code_file: None,
+        code_line: None,
+        sampling: captures.name("sampled").and_then(|sampled| LogSampling::from_marker(sampled.as_str())),
+        message: rest[..end].to_string(),
+        thread_name: None,
+        thread_id: None,
+        pid: None,
+        host: None,
+        fields: TEXT_FIELD
+            .captures_iter(&rest[end + 1..])
+            .map(|field| (field[1].to_string(), parse_text_field_value(&field[2])))
+            .collect(),
+    };
+    if let Some(identity) = captures.name("identity") {
+        apply_identity(&mut record, identity.as_str());
+    }
+    Some(record)
+}
+
+/// Fills the `[thread:.. tid:.. pid:.. host:..]` block back into `record`
+fn apply_identity(record: &mut LogRecord, identity: &str) {
+    let mut parts: Vec<(&str, String)> = Vec::new();
+    for word in identity.split(' ') {
+        match word.split_once(':') {
+            Some((key, value)) if matches!(key, "thread" | "tid" | "pid" | "host") => parts.push((key, value.to_string())),
+            // Thread names may contain spaces
+            _ => {
+                if let Some((_, value)) = parts.last_mut() {
+                    value.push(' ');
+                    value.push_str(word);
+                }
+            }
+        }
+    }
+    for (key, value) in parts {
+        match key {
+            "thread" => record.thread_name = Some(value),
+            "tid" => record.thread_id = value.parse().ok(),
+            "pid" => record.pid = value.parse().ok(),
+            _ => record.host = Some(value),
+        }
+    }
+}
+
+fn parse_text_field_value(value: &str) -> LogFieldValue {
+    if value.starts_with('"') {
+        return json_unescape(value).map_or_else(|| LogFieldValue::from(value), LogFieldValue::from);
+    }
+    if let Ok(number) = value.parse::<i64>() {
+        return LogFieldValue::from(number);
+    }
+    if let Ok(number) = value.parse::<u64>() {
+        return LogFieldValue::from(number);
+    }
+    // Finite numbers only; words such as `inf` or `NaN` stay strings
+    if let Ok(number) = value.parse::<f64>()
+        && number.is_finite()
+    {
+        return LogFieldValue::from(number);
+    }
+    match value {
+        "true" => LogFieldValue::from(true),
+        "false" => LogFieldValue::from(false),
+        _ => LogFieldValue::from(value),
+    }
+}
+
+/// Decodes a quoted JSON string as written by `json_escape`
+pub(crate) fn json_unescape(quoted: &str) -> Option<String> {
+    let inner = quoted.strip_prefix('"')?.strip_suffix('"')?;
+    let mut value = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => value.push('\n'),
+            'r' => value.push('\r'),
+            't' => value.push('\t'),
+            'b' => value.push('\u{8}'),
+            'f' => value.push('\u{c}'),
+            'u' => {
+                let code: String = chars.by_ref().take(4).collect();
+                value.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+            }
+            other => value.push(other),
+        }
+    }
+    Some(value)
+}
+
+/// Whether `line` begins a new record rather than continuing a multi-line message
+fn starts_entry(line: &str) -> bool {
+    let plain = strip_log_colors(line);
+    TEXT_START.is_match(&plain) || JSON_ENTRY.is_match(&plain)
+}
+
+/// Whether `line` belongs to a file header or shutdown summary block or is an integrity tag
+/// rather than part of a record;
+/// `in_block` carries the state of a text block across lines
+fn skip_block_line(line: &str, in_block: &mut bool) -> bool {
+    if line.starts_with("{\"header\":") || line.starts_with("{\"summary\":") || is_chain_line(line) {
+        return true;
+    }
+    if line.starts_with("# ==================== rs_box log ") {
+        *in_block = true;
+        return true;
+    }
+    if *in_block {
+        *in_block = !line.starts_with("# ====");
+        return true;
+    }
+    false
+}
+
+/// Groups lines into records: lines that start no record are joined to the one before,
+/// header and summary blocks are left out
+#[derive(Debug)]
+struct LogEntryParser {
+    module: String,
+    chunk: Option<String>,
+    in_block: bool,
+}
+
+impl LogEntryParser {
+    fn new(module: &str) -> Self {
+        LogEntryParser {
+            module: module.to_string(),
+            chunk: None,
+            in_block: false,
+        }
+    }
+
+    /// Takes one line without its line ending
+    fn push_line(&mut self, line: &str, entries: &mut Vec<LogViewEntry>) {
+        if skip_block_line(line, &mut self.in_block) {
+            self.finish(entries);
+        } else if starts_entry(line) {
+            self.finish(entries);
+            self.chunk = Some(line.to_string());
+        } else if let Some(ref mut text) = self.chunk {
+            text.push('\n');
+            text.push_str(line);
+        }
+    }
+
+    /// Takes one line as read from a file, ending in `\n` unless it is the last one
+    fn push_bytes(&mut self, line: &[u8], entries: &mut Vec<LogViewEntry>) {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        self.push_line(&String::from_utf8_lossy(line), entries);
+    }
+
+    /// Emits the record held back for continuation lines
+    fn finish(&mut self, entries: &mut Vec<LogViewEntry>) {
+        if let Some(text) = self.chunk.take() {
+            entries.extend(LogViewEntry::parse(&self.module, &text));
+        }
+    }
+}
+
+/// Parses the content of one log file; lines that start no record are joined to the one before,
+/// header and summary blocks are left out
+pub fn parse_log_entries(module: &str, content: &str) -> Vec<LogViewEntry> {
+    let mut entries = Vec::new();
+    let mut parser = LogEntryParser::new(module);
+    for line in content.lines() {
+        parser.push_line(line, &mut entries);
+    }
+    parser.finish(&mut entries);
+    entries
+}
+
+/// Parses one log file line by line, keeping the records passing `filter`
+fn read_file_entries(module: &str, path: &Path, filter: &LogViewFilter, entries: &mut Vec<LogViewEntry>) -> io::Result<()> {
+    let mut reader = BufReader::new(fs::File::open(path)?);
+    let mut parser = LogEntryParser::new(module);
+    let mut parsed = Vec::new();
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        parser.push_bytes(&line, &mut parsed);
+        line.clear();
+        entries.extend(parsed.drain(..).filter(|entry| filter.matches(entry)));
+    }
+    parser.finish(&mut parsed);
+    entries.extend(parsed.into_iter().filter(|entry| filter.matches(entry)));
+    Ok(())
+}
+
+/// Which records `rs_box-logview` shows
+#[derive(Clone, Debug, Default)]
+pub struct LogViewFilter {
+    level: Option<LogLevel>,
+    since: Option<DateTime<FixedOffset>>,
+    until: Option<DateTime<FixedOffset>>,
+    modules: Vec<String>,
+    pattern: Option<Regex>,
+}
+
+impl LogViewFilter {
+    pub fn new() -> Self {
+        LogViewFilter::default()
+    }
+
+    /// Keeps records of `level` and more severe ones
+    pub fn with_level(mut self, level: LogLevel) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Keeps records at or after `since`
+    pub fn with_since(mut self, since: DateTime<FixedOffset>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    /// Keeps records before `until`
+    pub fn with_until(mut self, until: DateTime<FixedOffset>) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    /// Keeps records of the module (and its sub-modules) or logger `name`; may be given several times
+    pub fn with_module(mut self, name: &str) -> Self {
+        self.modules.push(name.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Keeps records whose text, without colors, matches `pattern`
+    pub fn with_regex(mut self, pattern: &str) -> Result<Self, String> {
+        self.pattern = Some(Regex::new(pattern).map_err(|e| format!("invalid pattern {:?}: {}", pattern, e))?);
+        Ok(self)
+    }
+
+    pub fn matches(&self, entry: &LogViewEntry) -> bool {
+        if let Some(level) = self.level
+            && entry.level.severity() > level.severity()
+        {
+            return false;
+        }
+        if self.since.is_some_and(|since| entry.time < since) || self.until.is_some_and(|until| entry.time >= until) {
+            return false;
+        }
+        if !self.modules.is_empty()
+            && !self.modules.iter().any(|name| {
+                is_within(&entry.module, name, '/') || is_within(&entry.logger, name, '.')
+            })
+        {
+            return false;
+        }
+        match self.pattern {
+            Some(ref pattern) => pattern.is_match(&strip_log_colors(&entry.line)),
+            None => true,
+        }
+    }
+}
+
+/// `value` is `name` or below it, e.g. `pool/stratum` within `pool`
+fn is_within(value: &str, name: &str, separator: char) -> bool {
+    value.strip_prefix(name).is_some_and(|rest| rest.is_empty() || rest.starts_with(separator))
+}
+
+/// Modules below `log_dir`, i.e. directories holding files of `layout`, sorted by name
+pub fn list_log_modules(log_dir: &str, layout: &LogFileLayout) -> Vec<String> {
+    let mut modules = Vec::new();
+    find_modules(Path::new(log_dir), "", layout, log_dir, &mut modules);
+    modules.sort();
+    modules
+}
+
+fn find_modules(dir: &Path, relative: &str, layout: &LogFileLayout, log_dir: &str, modules: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if !entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        let module = if relative.is_empty() { name } else { format!("{}/{}", relative, name) };
+        if !layout.list_files(log_dir, &module).is_empty() {
+            modules.push(module.clone());
+        }
+        // Sub-modules of a hierarchical logger sit next to the parent's date directories
+        if module.matches('/').count() < 4 {
+            find_modules(&entry.path(), &module, layout, log_dir, modules);
+        }
+    }
+}
+
+/// All records of `modules` passing `filter`, merged in time order
+pub fn read_log_entries(log_dir: &str, layout: &LogFileLayout, modules: &[String], filter: &LogViewFilter) -> Vec<LogViewEntry> {
+    let mut entries = Vec::new();
+    for module in modules {
+        for file in layout.list_files(log_dir, module) {
+            // Unreadable files are skipped like missing ones; what was parsed before an error is kept
+            let _ = read_file_entries(module, &file.path, filter, &mut entries);
+        }
+    }
+    // Stable, so records of one module with equal times keep their file order
+    entries.sort_by_key(|entry| entry.time);
+    entries
+}
+
+/// Follows a module's `run.log`, reopening it when the link moves to a new file
+#[derive(Debug)]
+pub struct LogViewFollower {
+    path: PathBuf,
+    target: Option<PathBuf>,
+    position: u64,
+    /// Bytes of a line not yet ended, decoded once the line is complete
+    partial: Vec<u8>,
+    parser: LogEntryParser,
+}
+
+impl LogViewFollower {
+    /// Follows `path` starting at its current end
+    pub fn new(module: &str, path: &Path) -> Self {
+        LogViewFollower {
+            path: path.to_path_buf(),
+            target: fs::canonicalize(path).ok(),
+            position: fs::metadata(path).map_or(0, |metadata| metadata.len()),
+            partial: Vec::new(),
+            parser: LogEntryParser::new(module),
+        }
+    }
+
+    /// Records appended since the last call. A record is held back until the next one
+    /// starts or a call finds no new data, so multi-line messages come out whole.
+    pub fn poll(&mut self) -> Vec<LogViewEntry> {
+        let mut entries = Vec::new();
+        let target = fs::canonicalize(&self.path).ok();
+        if target != self.target {
+            // Finish the old file, keeping the new file's header out of its last record
+            if let Some(old) = self.target.clone() {
+                let data = self.read_new(&old);
+                self.take_lines(&data, &mut entries);
+            }
+            self.parser.finish(&mut entries);
+            self.target = target;
+            self.position = 0;
+            self.partial.clear();
+            self.parser.in_block = false;
+        }
+
+        let path = self.path.clone();
+        let data = self.read_new(&path);
+        let complete = self.take_lines(&data, &mut entries);
+        if data.is_empty() && !complete {
+            self.parser.finish(&mut entries);
+        }
+        entries
+    }
+
+    /// Bytes of `path` past the current position, starting over if the file shrank
+    fn read_new(&mut self, path: &Path) -> Vec<u8> {
+        let mut data = Vec::new();
+        if let Ok(mut file) = fs::File::open(path) {
+            if file.metadata().is_ok_and(|metadata| metadata.len() < self.position) {
+                self.position = 0;
+            }
+            if file.seek(SeekFrom::Start(self.position)).is_ok() {
+                let _ = file.read_to_end(&mut data);
+            }
+        }
+        self.position += data.len() as u64;
+        data
+    }
+
+    /// Groups the complete lines of `data` into records; false if no line was complete
+    /// Lines are decoded only once complete, so a UTF-8 character split across two
+    /// reads is not turned into replacement characters
+    fn take_lines(&mut self, data: &[u8], entries: &mut Vec<LogViewEntry>) -> bool {
+        self.partial.extend_from_slice(data);
+        let Some(complete) = self.partial.iter().rposition(|&b| b == b'\n') else {
+            return false;
+        };
+        let lines: Vec<u8> = self.partial.drain(..=complete).collect();
+        for line in lines.split_inclusive(|&b| b == b'\n') {
+            self.parser.push_bytes(line, entries);
+        }
+        true
+    }
+}
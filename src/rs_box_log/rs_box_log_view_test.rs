@@ -0,0 +1,138 @@
+#[cfg(test)]
+use super::rs_box_log;
+#[cfg(test)]
+use super::rs_box_log_test::test_log_dir;
+#[cfg(test)]
+use chrono::{DateTime, Duration};
+#[cfg(test)]
+use std::sync::Arc;
+
+#[cfg(test)]
+fn clocked_logger(project: &str, log_dir: &str, clock: &Arc<rs_box_log::LogManualClock>) -> rs_box_log::LoggerManager {
+    let config = rs_box_log::LogConfig::new(project, true, log_dir, rs_box_log::LogLevel::LogLevelDebug, 7)
+        .with_clock(clock.clone());
+    rs_box_log::LoggerManager::with_config(config)
+}
+
+#[cfg(test)]
+fn messages(entries: &[rs_box_log::LogViewEntry]) -> Vec<String> {
+    entries
+        .iter()
+        .map(|entry| {
+            let json = entry.to_json();
+            let start = json.find("\"message\":\"").unwrap() + 11;
+            json[start..json.len() - 2].to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn test_merge_and_filter_module_logs() {
+    let log_dir = test_log_dir("view_merge");
+    let clock = Arc::new(rs_box_log::LogManualClock::new(DateTime::parse_from_rfc3339("2024-05-17T09:59:58+00:00").unwrap()));
+    let pool = clocked_logger("viewpool", &log_dir, &clock);
+    let node = clocked_logger("viewnode", &log_dir, &clock);
+
+    pool.log_info_f("pool started");
+    clock.advance(Duration::seconds(1));
+    node.log_warning_f("node syncing\nheight 100 of 200");
+    clock.advance(Duration::seconds(1));
+    pool.log_with_fields(
+        rs_box_log::LogLevel::LogLevelError,
+        "share rejected",
+        vec![("worker".to_string(), "rig 01".into()), ("diff".to_string(), 65536.into())],
+    );
+    clock.advance(Duration::seconds(1));
+    node.log_info_f("node synced");
+
+    let layout = rs_box_log::LogFileLayout::default();
+    let modules = rs_box_log::list_log_modules(&log_dir, &layout);
+    assert_eq!(modules, vec!["viewnode", "viewpool"]);
+
+    let all = rs_box_log::read_log_entries(&log_dir, &layout, &modules, &rs_box_log::LogViewFilter::new());
+    assert_eq!(
+        messages(&all),
+        vec!["pool started", "node syncing\\nheight 100 of 200", "share rejected", "node synced"]
+    );
+    assert_eq!(all[1].module, "viewnode");
+    assert!(all[2].to_json().contains(r#""fields":{"worker":"rig 01","diff":65536}"#));
+
+    let filter = rs_box_log::LogViewFilter::new().with_level(rs_box_log::LogLevel::LogLevelWarning);
+    assert_eq!(
+        messages(&rs_box_log::read_log_entries(&log_dir, &layout, &modules, &filter)),
+        vec!["node syncing\\nheight 100 of 200", "share rejected"]
+    );
+    let filter = rs_box_log::LogViewFilter::new()
+        .with_since(DateTime::parse_from_rfc3339("2024-05-17T10:00:00+00:00").unwrap())
+        .with_until(DateTime::parse_from_rfc3339("2024-05-17T10:00:01+00:00").unwrap());
+    assert_eq!(messages(&rs_box_log::read_log_entries(&log_dir, &layout, &modules, &filter)), vec!["share rejected"]);
+    let filter = rs_box_log::LogViewFilter::new().with_module("viewnode").with_regex("height \\d+").unwrap();
+    assert_eq!(
+        messages(&rs_box_log::read_log_entries(&log_dir, &layout, &modules, &filter)),
+        vec!["node syncing\\nheight 100 of 200"]
+    );
+    assert!(rs_box_log::LogViewFilter::new().with_regex("(").is_err());
+}
+
+#[test]
+fn test_parse_text_and_json_lines() {
+    let text = "[2024-05-17 10:00:00 +00:00] \x1b[31m[ERROR]\x1b[0m [logger:pool.stratum] [thread:job worker tid:7 pid:42] [see [docs]] url=\"http://x/?a=b\" ok=true ratio=0.5";
+    let entries = rs_box_log::parse_log_entries("pool", text);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].level, rs_box_log::LogLevel::LogLevelError);
+    assert_eq!(entries[0].logger, "pool.stratum");
+    assert_eq!(
+        entries[0].to_json(),
+        concat!(
+            r#"{"time":"2024-05-17T10:00:00+00:00","level":"ERROR","logger":"pool.stratum","thread_name":"job worker","#,
+            r#""thread_id":7,"pid":42,"fields":{"url":"http://x/?a=b","ok":true,"ratio":0.5},"message":"see [docs]"}"#
+        )
+    );
+    assert_eq!(rs_box_log::strip_log_colors(&entries[0].line), rs_box_log::strip_log_colors(text));
+
+    let json = "{\"header\":{\"project\":\"pool\"}}\n{\"time\":\"2024-05-17T10:00:00+00:00\",\"level\":\"INFO\",\"logger\":\"pool\",\"message\":\"up\"}";
+    let entries = rs_box_log::parse_log_entries("pool", json);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].to_json(), json.lines().nth(1).unwrap());
+    assert!(rs_box_log::parse_log_view_time("2024-05-17").is_ok());
+    assert!(rs_box_log::parse_log_view_time("17/05/2024").is_err());
+}
+
+#[test]
+fn test_follow_run_log_across_rotation() {
+    let log_dir = test_log_dir("view_follow");
+    let clock = Arc::new(rs_box_log::LogManualClock::new(DateTime::parse_from_rfc3339("2024-05-17T09:59:59+00:00").unwrap()));
+    let logger = clocked_logger("followed", &log_dir, &clock);
+    logger.log_info_f("history");
+
+    let mut follower = rs_box_log::LogViewFollower::new("followed", &std::path::Path::new(&log_dir).join("followed/run.log"));
+    assert!(follower.poll().is_empty());
+    logger.log_info_f("first live");
+    clock.advance(Duration::seconds(1));
+    logger.log_info_f("after rotation");
+
+    let mut seen = follower.poll();
+    seen.extend(follower.poll());
+    seen.extend(follower.poll());
+    assert_eq!(messages(&seen), vec!["first live", "after rotation"]);
+}
+
+#[test]
+fn test_follow_line_split_inside_a_character() {
+    let log_dir = test_log_dir("view_follow_split");
+    let clock = Arc::new(rs_box_log::LogManualClock::new(DateTime::parse_from_rfc3339("2024-05-17T09:59:59+00:00").unwrap()));
+    clocked_logger("written", &log_dir, &clock).log_info_f("矿池 online");
+    let line = std::fs::read(std::path::Path::new(&log_dir).join("written/run.log")).unwrap();
+    let split = line.windows(3).position(|window| window == "矿".as_bytes()).unwrap() + 1;
+
+    let path = std::path::Path::new(&log_dir).join("split.log");
+    std::fs::write(&path, b"").unwrap();
+    let mut follower = rs_box_log::LogViewFollower::new("split", &path);
+    let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+    std::io::Write::write_all(&mut file, &line[..split]).unwrap();
+    let mut seen = follower.poll();
+    std::io::Write::write_all(&mut file, &line[split..]).unwrap();
+    seen.extend(follower.poll());
+    seen.extend(follower.poll());
+    assert_eq!(messages(&seen), vec!["矿池 online"]);
+}